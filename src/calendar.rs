@@ -0,0 +1,270 @@
+use crate::datetime::Timestamp;
+use crate::model::{BroadcastPeriod, BroadcastSchedule, Item};
+use crate::schedule;
+
+// bangumi-data does not record episode length, so events get a fixed slot.
+const DEFAULT_EVENT_DURATION: &str = "PT30M";
+const MAX_LINE_OCTETS: usize = 75;
+
+/// Renders an RFC 5545 calendar with one event per item.
+///
+/// Items with a `broadcast` recurrence become repeating events bounded by
+/// `end`; items with only a `begin` become a single premiere event, all-day
+/// when `begin` is a date without a time. When
+/// `site` is given, only items carrying that site are included and the
+/// site's own `begin`/`broadcast`/`end` take precedence over the item's.
+pub fn build_calendar(name: &str, items: &[Item], site: Option<&str>, now: Timestamp) -> String {
+    let mut lines = vec![
+        "BEGIN:VCALENDAR".to_string(),
+        "VERSION:2.0".to_string(),
+        "PRODID:-//Housou//Broadcast Schedule//EN".to_string(),
+        "CALSCALE:GREGORIAN".to_string(),
+        "METHOD:PUBLISH".to_string(),
+        format!("X-WR-CALNAME:{}", escape_text(name)),
+    ];
+
+    for item in items {
+        let site_entry = match site {
+            Some(key) => match item.sites.iter().find(|s| s.site == key) {
+                Some(entry) => Some(entry),
+                None => continue,
+            },
+            None => None,
+        };
+
+        let pick = |site_value: Option<&Option<String>>, item_value: &Option<String>| {
+            site_value
+                .and_then(|v| v.as_deref())
+                .filter(|v| !v.is_empty())
+                .or(item_value.as_deref())
+                .filter(|v| !v.is_empty())
+                .map(str::to_string)
+        };
+
+        let begin = pick(site_entry.map(|s| &s.begin), &item.begin);
+        let broadcast = pick(site_entry.map(|s| &s.broadcast), &item.broadcast);
+        let end = pick(site_entry.map(|s| &s.end), &item.end);
+
+        let schedule = broadcast
+            .as_deref()
+            .and_then(|b| b.parse::<BroadcastSchedule>().ok());
+        let start = match schedule.map(|s| s.start) {
+            Some(start) => start,
+            None => match begin.as_deref().and_then(Timestamp::parse) {
+                Some(start) => start,
+                None => continue,
+            },
+        };
+
+        // A premiere known only by its date has no slot to block out
+        let all_day = schedule.is_none() && begin.as_deref().is_some_and(|b| b.trim().len() == 10);
+
+        let mut rrule = schedule.as_ref().and_then(to_rrule);
+        if let Some(rule) = rrule.as_mut()
+            && !rule.contains("COUNT=")
+            && let Some(until) = end.as_deref().and_then(schedule::parse_end)
+        {
+            if until < start {
+                continue;
+            }
+            rule.push_str(&format!(";UNTIL={}", until.to_ics()));
+        }
+
+        let uid_source = format!("{}|{}|{}", item.title, site.unwrap_or(""), start.as_secs());
+        lines.push("BEGIN:VEVENT".to_string());
+        lines.push(format!("UID:{:016x}@housou", fnv1a(uid_source.as_bytes())));
+        lines.push(format!("DTSTAMP:{}", now.to_ics()));
+        if all_day {
+            lines.push(format!("DTSTART;VALUE=DATE:{}", start.to_ics_date()));
+            lines.push("DURATION:P1D".to_string());
+        } else {
+            lines.push(format!("DTSTART:{}", start.to_ics()));
+            lines.push(format!("DURATION:{}", DEFAULT_EVENT_DURATION));
+        }
+        if let Some(rule) = rrule {
+            lines.push(format!("RRULE:{}", rule));
+        }
+        let summary = item.display_title.as_deref().unwrap_or(&item.title);
+        lines.push(format!("SUMMARY:{}", escape_text(summary)));
+        if let Some(comment) = item.comment.as_deref().filter(|c| !c.is_empty()) {
+            lines.push(format!("DESCRIPTION:{}", escape_text(comment)));
+        }
+        if let Some(key) = site {
+            lines.push(format!("LOCATION:{}", escape_text(key)));
+        }
+        let url = site_entry
+            .and_then(|s| s.url.as_deref())
+            .unwrap_or(&item.official_site);
+        if !url.is_empty() {
+            lines.push(format!("URL:{}", url));
+        }
+        lines.push("END:VEVENT".to_string());
+    }
+
+    lines.push("END:VCALENDAR".to_string());
+
+    let mut out = String::new();
+    for line in lines {
+        fold_line(&line, &mut out);
+    }
+    out
+}
+
+fn to_rrule(schedule: &BroadcastSchedule) -> Option<String> {
+    let BroadcastPeriod { months, days } = schedule.period;
+    let rule = match (months, days) {
+        (0, 0) => return None,
+        (0, days) if days % 7 == 0 => frequency("WEEKLY", days / 7),
+        (0, days) => frequency("DAILY", days),
+        (months, 0) => frequency("MONTHLY", months),
+        // Mixed month/day periods have no single RRULE equivalent
+        _ => return None,
+    };
+
+    Some(match schedule.repetitions {
+        Some(count) => format!("{};COUNT={}", rule, count),
+        None => rule,
+    })
+}
+
+fn frequency(freq: &str, interval: u32) -> String {
+    if interval == 1 {
+        format!("FREQ={}", freq)
+    } else {
+        format!("FREQ={};INTERVAL={}", freq, interval)
+    }
+}
+
+fn escape_text(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace("\r\n", "\\n")
+        .replace('\n', "\\n")
+}
+
+// RFC 5545 3.1: lines longer than 75 octets are folded with CRLF + space,
+// never splitting a UTF-8 sequence.
+fn fold_line(line: &str, out: &mut String) {
+    let mut width = 0;
+    for c in line.chars() {
+        if width + c.len_utf8() > MAX_LINE_OCTETS {
+            out.push_str("\r\n ");
+            width = 1;
+        }
+        out.push(c);
+        width += c.len_utf8();
+    }
+    out.push_str("\r\n");
+}
+
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, b| {
+        (hash ^ *b as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::Site;
+
+    fn item(title: &str, begin: &str, broadcast: Option<&str>, end: &str) -> Item {
+        Item {
+            title: title.to_string(),
+            begin: Some(begin.to_string()),
+            broadcast: broadcast.map(str::to_string),
+            end: Some(end.to_string()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_to_rrule() {
+        let cases = vec![
+            ("R/2024-01-06T15:00:00.000Z/P7D", Some("FREQ=WEEKLY")),
+            ("R/2024-01-06T15:00:00Z/P1D", Some("FREQ=DAILY")),
+            (
+                "R/2024-01-06T15:00:00Z/P14D",
+                Some("FREQ=WEEKLY;INTERVAL=2"),
+            ),
+            ("R/2024-01-06T15:00:00Z/P1M", Some("FREQ=MONTHLY")),
+            ("R12/2024-01-06T15:00:00Z/P7D", Some("FREQ=WEEKLY;COUNT=12")),
+            ("R/2024-01-06T15:00:00Z/P0D", None),
+        ];
+        for (input, expected) in cases {
+            let schedule: BroadcastSchedule = input.parse().unwrap();
+            assert_eq!(
+                to_rrule(&schedule).as_deref(),
+                expected,
+                "Failed for input: {}",
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn test_build_calendar() {
+        let items = vec![
+            item(
+                "葬送のフリーレン",
+                "2023-09-29T14:00:00.000Z",
+                Some("R/2023-09-29T14:00:00.000Z/P7D"),
+                "2024-03-22T14:00:00.000Z",
+            ),
+            item("劇場版, テスト", "2024-02-16", None, ""),
+            item("No Date", "", None, ""),
+        ];
+
+        let ics = build_calendar("Housou", &items, None, Timestamp::from_secs(0));
+
+        assert!(ics.starts_with("BEGIN:VCALENDAR\r\n"));
+        assert!(ics.ends_with("END:VCALENDAR\r\n"));
+        assert_eq!(ics.matches("BEGIN:VEVENT").count(), 2);
+        assert!(ics.contains("DTSTART:20230929T140000Z\r\n"));
+        assert!(ics.contains("RRULE:FREQ=WEEKLY;UNTIL=20240322T140000Z\r\n"));
+        assert!(ics.contains("DTSTART;VALUE=DATE:20240216\r\nDURATION:P1D\r\n"));
+        assert!(ics.contains("SUMMARY:劇場版\\, テスト\r\n"));
+        assert!(ics.lines().all(|l| l.len() <= MAX_LINE_OCTETS + 1));
+    }
+
+    #[test]
+    fn test_build_calendar_site_filter() {
+        let mut with_site = item(
+            "Show",
+            "2024-01-06T15:00:00Z",
+            Some("R/2024-01-06T15:00:00Z/P7D"),
+            "",
+        );
+        with_site.sites = vec![Site {
+            site: "netflix".to_string(),
+            broadcast: Some("R/2024-01-11T08:00:00Z/P7D".to_string()),
+            url: Some("https://www.netflix.com/title/1".to_string()),
+            ..Default::default()
+        }];
+        let without_site = item("Other", "2024-01-06T15:00:00Z", None, "");
+
+        let ics = build_calendar(
+            "Housou",
+            &[with_site, without_site],
+            Some("netflix"),
+            Timestamp::from_secs(0),
+        );
+
+        assert_eq!(ics.matches("BEGIN:VEVENT").count(), 1);
+        assert!(ics.contains("DTSTART:20240111T080000Z\r\n"));
+        assert!(ics.contains("RRULE:FREQ=WEEKLY\r\n"));
+        assert!(ics.contains("LOCATION:netflix\r\n"));
+        assert!(ics.contains("URL:https://www.netflix.com/title/1\r\n"));
+    }
+
+    #[test]
+    fn test_fold_line() {
+        let mut out = String::new();
+        fold_line(&format!("SUMMARY:{}", "あ".repeat(40)), &mut out);
+        let lines: Vec<_> = out.split("\r\n").filter(|l| !l.is_empty()).collect();
+        assert!(lines.len() > 1);
+        assert!(lines.iter().all(|l| l.len() <= MAX_LINE_OCTETS));
+        assert!(lines[1..].iter().all(|l| l.starts_with(' ')));
+    }
+}
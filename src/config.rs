@@ -14,12 +14,84 @@ pub const CACHE_TTL_API: i32 = ONE_DAY; // 24 hours for API responses
 pub const CACHE_TTL_FINISHED: i32 = 30 * ONE_DAY; // 30 days for finished titles
 pub const CACHE_TTL_ONGOING: i32 = 7 * ONE_DAY; // 1 week for ongoing titles
 pub const CACHE_TTL_JIKAN: i32 = ONE_DAY; // 1 day for Jikan API responses
+pub const CACHE_TTL_SCHEDULE: i32 = ONE_HOUR; // 1 hour for airing schedules
 
 // Cache Version
 pub const CACHE_VERSION: &str = "v3";
 
 // Configuration
 pub const START_YEAR: i32 = 1943;
+pub const SCHEDULE_DEFAULT_TIME_ZONE: &str = "Asia/Tokyo";
+pub const SCHEDULE_DEFAULT_DAYS: i64 = 7;
+pub const SCHEDULE_MAX_DAYS: i64 = 31;
+pub const ITEMS_MAX_LIMIT: usize = 500;
+pub const SEARCH_DEFAULT_LIMIT: usize = 50;
+pub const SEARCH_MAX_LIMIT: usize = 200;
+pub const SEARCH_INDEX_BINDING: &str = "SEARCH_INDEX";
+pub const SEARCH_INDEX_KEY: &str = "search-index";
+// Values the postings are spread over, and documents stored per value
+pub const SEARCH_INDEX_SHARDS: usize = 32;
+pub const SEARCH_INDEX_CHUNK: usize = 50;
+// Past years re-fetched per cron run, on top of the current year. Each year
+// is 12 subrequests, so this stays within the free plan's limit of 50.
+pub const SEARCH_INDEX_YEARS_PER_RUN: usize = 3;
+
+// Manual metadata overrides, written through PUT /api/overrides with the
+// OVERRIDES_TOKEN secret as a bearer token
+pub const OVERRIDES_BINDING: &str = "OVERRIDES";
+pub const OVERRIDES_TOKEN: &str = "OVERRIDES_TOKEN";
+
+// Cross-reference of MAL, AniList, AniDB, Kitsu, TMDb and TVDB IDs, fetched
+// from Fribb's anime-lists by the scheduled handler
+pub const ID_MAP_URL: &str =
+    "https://raw.githubusercontent.com/Fribb/anime-lists/master/anime-list-full.json";
+pub const ID_MAP_BINDING: &str = "ID_MAP";
+// Values the mapping is spread over, each written on every refresh
+pub const ID_MAP_SHARDS: usize = 64;
+pub const ID_MAP_REFRESH_SECS: i64 = ONE_DAY as i64;
+
+// TMDB
+pub const TMDB_API_BASE: &str = "https://api.themoviedb.org/3";
+// Fallback order for descriptions missing in the requested locale, overridable
+// with the TMDB_DESCRIPTION_LANGUAGES variable
+pub const TMDB_DESCRIPTION_LANGUAGES: &str = "ja-JP,zh-CN,zh-TW,en-US";
+
+// Metadata search: the lowest score (0 to 1) a provider's best search
+// result needs before it's used
+pub const METADATA_MIN_CONFIDENCE: f64 = 0.5;
+// Search results requested from each provider
+pub const METADATA_SEARCH_LIMIT: usize = 10;
+// Providers tried for metadata, in order, overridable with the
+// METADATA_PROVIDERS variable. Leaving one out disables it.
+pub const METADATA_PROVIDERS: &str = "tmdb,mal,kitsu,bangumi,anidb,anilist";
+pub const METADATA_CANDIDATES_DEFAULT_LIMIT: usize = 10;
+pub const METADATA_CANDIDATES_MAX_LIMIT: usize = 30;
+// Cast and crew per /api/metadata/credits page; AniList serves at most 50
+pub const CREDITS_PER_PAGE_DEFAULT: usize = 25;
+pub const CREDITS_PER_PAGE_MAX: usize = 50;
+// Cast and crew /api/metadata shows from TMDb, out of its full credits
+pub const TMDB_CAST_PREVIEW: usize = 6;
+pub const TMDB_CREW_PREVIEW: usize = 10;
+// Provider requests made to build one relations graph, the first included
+pub const RELATIONS_MAX_FETCHES: usize = 8;
+pub const ANILIST_GRAPHQL_URL: &str = "https://graphql.anilist.co";
+pub const KITSU_API_BASE: &str = "https://kitsu.app/api/edge";
+// Pages of 20 episodes fetched per Kitsu lookup, each a subrequest
+pub const KITSU_EPISODE_PAGES: usize = 5;
+pub const BANGUMI_API_BASE: &str = "https://api.bgm.tv";
+// bgm.tv asks API clients to identify themselves
+pub const BANGUMI_USER_AGENT: &str = "Asutorufa/housou (https://github.com/Asutorufa/housou)";
+// Pages of 100 episodes fetched per Bangumi lookup
+pub const BANGUMI_EPISODE_PAGES: usize = 2;
+pub const ANIDB_API_URL: &str = "http://api.anidb.net:9001/httpapi";
+pub const ANIDB_IMAGE_BASE: &str = "https://cdn-eu.anidb.net/images/main";
+// AniDB's anime-titles.dat, uploaded to this namespace under ANIDB_TITLES_KEY
+// and indexed there by the scheduled handler
+pub const ANIDB_TITLES_BINDING: &str = "ANIDB";
+pub const ANIDB_TITLES_KEY: &str = "anime-titles";
+// Values each half of the index, titles to anime and anime to titles, is
+// spread over
+pub const ANIDB_TITLES_SHARDS: usize = 64;
 
 // TMDB Attribution URLs
 pub const TMDB_LOGO_SQUARE: &str = "https://www.themoviedb.org/assets/2/v4/logos/v2/blue_square_2-d537fb228cf3ed904132c3096b9736928c38cfe75196763ebd7e9f22e855d9e5.svg";
@@ -0,0 +1,284 @@
+use regex::Regex;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+use std::sync::OnceLock;
+
+static ISO_DATETIME_REGEX: OnceLock<Regex> = OnceLock::new();
+
+/// A UTC instant with second precision.
+///
+/// bangumi-data and Jikan both hand out ISO 8601 strings, so this only
+/// covers the subset they actually use: calendar dates, optional times with
+/// fractional seconds, and `Z` or `±HH:MM` offsets.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Timestamp(i64);
+
+impl Timestamp {
+    pub const fn from_secs(secs: i64) -> Self {
+        Self(secs)
+    }
+
+    pub const fn as_secs(self) -> i64 {
+        self.0
+    }
+
+    pub fn now() -> Self {
+        Self((js_sys::Date::now() / 1000.0) as i64)
+    }
+
+    pub fn from_civil(
+        year: i64,
+        month: u32,
+        day: u32,
+        hour: u32,
+        minute: u32,
+        second: u32,
+    ) -> Self {
+        let days = days_from_civil(year, month, day);
+        Self(days * 86400 + (hour * 3600 + minute * 60 + second) as i64)
+    }
+
+    /// Parses `YYYY-MM-DD` or `YYYY-MM-DDTHH:MM[:SS[.fff]][Z|±HH:MM]`.
+    /// Missing offsets are treated as UTC and empty strings yield `None`.
+    pub fn parse(s: &str) -> Option<Self> {
+        let re = ISO_DATETIME_REGEX.get_or_init(|| {
+            Regex::new(r"^(\d{4})-(\d{2})-(\d{2})(?:[T ](\d{2}):(\d{2})(?::(\d{2})(?:\.\d+)?)?)?\s*(Z|[+-]\d{2}:?\d{2})?$")
+                .expect("Invalid ISO Datetime Regex")
+        });
+
+        let caps = re.captures(s.trim())?;
+        let num = |i: usize| {
+            caps.get(i)
+                .map_or(Some(0), |m| m.as_str().parse::<u32>().ok())
+        };
+
+        let year: i64 = caps[1].parse().ok()?;
+        let (month, day) = (num(2)?, num(3)?);
+        let (hour, minute, second) = (num(4)?, num(5)?, num(6)?);
+
+        if !(1..=12).contains(&month)
+            || day == 0
+            || day > days_in_month(year, month)
+            || hour > 23
+            || minute > 59
+            || second > 60
+        {
+            return None;
+        }
+
+        let offset = match caps.get(7).map(|m| m.as_str()) {
+            None | Some("Z") => 0,
+            Some(tz) => {
+                let sign = if tz.starts_with('-') { -1 } else { 1 };
+                let digits: String = tz[1..].chars().filter(|c| *c != ':').collect();
+                let hours: i64 = digits[..2].parse().ok()?;
+                let minutes: i64 = digits[2..].parse().ok()?;
+                sign * (hours * 3600 + minutes * 60)
+            }
+        };
+
+        let local = Self::from_civil(year, month, day, hour, minute, second.min(59));
+        Some(Self(local.0 - offset))
+    }
+
+    /// Returns `(year, month, day)`.
+    pub fn date(self) -> (i64, u32, u32) {
+        civil_from_days(self.0.div_euclid(86400))
+    }
+
+    /// Returns `(hour, minute, second)`.
+    pub fn time(self) -> (u32, u32, u32) {
+        let secs = self.0.rem_euclid(86400) as u32;
+        (secs / 3600, secs / 60 % 60, secs % 60)
+    }
+
+    pub fn add_secs(self, secs: i64) -> Self {
+        Self(self.0 + secs)
+    }
+
+    /// Adds calendar months, clamping the day to the end of the target month.
+    pub fn add_months(self, months: i64) -> Self {
+        let (year, month, day) = self.date();
+        let (hour, minute, second) = self.time();
+        let total = year * 12 + (month as i64 - 1) + months;
+        let (year, month) = (total.div_euclid(12), total.rem_euclid(12) as u32 + 1);
+        let day = day.min(days_in_month(year, month));
+        Self::from_civil(year, month, day, hour, minute, second)
+    }
+
+    /// Formats as an iCalendar UTC date-time, e.g. `20240106T150000Z`.
+    pub fn to_ics(self) -> String {
+        let (y, mo, d) = self.date();
+        let (h, mi, s) = self.time();
+        format!("{:04}{:02}{:02}T{:02}{:02}{:02}Z", y, mo, d, h, mi, s)
+    }
+
+    /// Formats as an iCalendar date, e.g. `20240106`.
+    pub fn to_ics_date(self) -> String {
+        let (y, mo, d) = self.date();
+        format!("{:04}{:02}{:02}", y, mo, d)
+    }
+}
+
+impl fmt::Display for Timestamp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (y, mo, d) = self.date();
+        let (h, mi, s) = self.time();
+        write!(
+            f,
+            "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+            y, mo, d, h, mi, s
+        )
+    }
+}
+
+static LOCALE_DATETIME_REGEX: OnceLock<Regex> = OnceLock::new();
+
+/// Returns the offset from UTC in seconds for an IANA time zone at `at`,
+/// or `None` if the runtime does not know the zone.
+pub fn utc_offset(time_zone: &str, at: Timestamp) -> Option<i64> {
+    let date = js_sys::Date::new(&(at.as_secs() as f64 * 1000.0).into());
+    let options = js_sys::Object::new();
+    for (key, value) in [
+        ("timeZone", time_zone),
+        ("hourCycle", "h23"),
+        ("year", "numeric"),
+        ("month", "2-digit"),
+        ("day", "2-digit"),
+        ("hour", "2-digit"),
+        ("minute", "2-digit"),
+        ("second", "2-digit"),
+    ] {
+        js_sys::Reflect::set(&options, &key.into(), &value.into()).ok()?;
+    }
+
+    // Call through Reflect so an unknown zone surfaces as an Err instead of a trap
+    let to_locale_string =
+        js_sys::Function::from(js_sys::Reflect::get(&date, &"toLocaleString".into()).ok()?);
+    let formatted = to_locale_string
+        .call2(&date, &"en-US".into(), &options)
+        .ok()?
+        .as_string()?;
+
+    // e.g. "01/07/2024, 00:00:00"
+    let re = LOCALE_DATETIME_REGEX.get_or_init(|| {
+        Regex::new(r"^(\d{2})/(\d{2})/(\d{4}), (\d{2}):(\d{2}):(\d{2})$")
+            .expect("Invalid Locale Datetime Regex")
+    });
+    let caps = re.captures(&formatted)?;
+    let num = |i: usize| caps[i].parse::<u32>().ok();
+
+    let local = Timestamp::from_civil(
+        caps[3].parse().ok()?,
+        num(1)?,
+        num(2)?,
+        num(4)? % 24,
+        num(5)?,
+        num(6)?,
+    );
+    Some(local.as_secs() - at.as_secs())
+}
+
+impl Serialize for Timestamp {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Timestamp {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        Timestamp::parse(&s)
+            .ok_or_else(|| serde::de::Error::custom(format!("invalid timestamp: {}", s)))
+    }
+}
+
+fn is_leap_year(year: i64) -> bool {
+    (year % 4 == 0 && year % 100 != 0) || year % 400 == 0
+}
+
+pub fn days_in_month(year: i64, month: u32) -> u32 {
+    match month {
+        2 if is_leap_year(year) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+// Days since 1970-01-01 for a proleptic Gregorian date (Howard Hinnant's algorithm).
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let y = if month <= 2 { year - 1 } else { year };
+    let era = y.div_euclid(400);
+    let yoe = y.rem_euclid(400);
+    let mp = (month as i64 + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + day as i64 - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146097 + doe - 719468
+}
+
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_timestamp() {
+        let cases = vec![
+            ("1970-01-01T00:00:00Z", Some(0)),
+            ("2024-01-06T15:00:00.000Z", Some(1704553200)),
+            ("2024-01-07T00:00:00+09:00", Some(1704553200)),
+            ("2024-01-07T00:00:00+0900", Some(1704553200)),
+            ("2024-01-06T15:00Z", Some(1704553200)),
+            ("2024-01-06T15:00:00", Some(1704553200)),
+            ("2024-01-06", Some(1704499200)),
+            ("1999-10-20T00:00:00.000Z", Some(940377600)),
+            ("2024-02-29", Some(1709164800)),
+            ("2023-02-29", None),
+            ("2024-13-01", None),
+            ("", None),
+            ("Saturdays at 23:00 (JST)", None),
+        ];
+
+        for (input, expected) in cases {
+            assert_eq!(
+                Timestamp::parse(input).map(Timestamp::as_secs),
+                expected,
+                "Failed for input: {}",
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn test_civil_roundtrip() {
+        let ts = Timestamp::from_civil(2000, 2, 29, 23, 59, 58);
+        assert_eq!(ts.date(), (2000, 2, 29));
+        assert_eq!(ts.time(), (23, 59, 58));
+        assert_eq!(ts.to_string(), "2000-02-29T23:59:58Z");
+        assert_eq!(ts.to_ics(), "20000229T235958Z");
+
+        let before_epoch = Timestamp::from_civil(1943, 4, 1, 0, 0, 0);
+        assert_eq!(before_epoch.date(), (1943, 4, 1));
+    }
+
+    #[test]
+    fn test_add_months() {
+        let jan31 = Timestamp::parse("2024-01-31T15:00:00Z").unwrap();
+        assert_eq!(jan31.add_months(1).to_string(), "2024-02-29T15:00:00Z");
+        assert_eq!(jan31.add_months(13).to_string(), "2025-02-28T15:00:00Z");
+        assert_eq!(jan31.add_months(-2).to_string(), "2023-11-30T15:00:00Z");
+    }
+}
@@ -0,0 +1,478 @@
+use crate::datetime::Timestamp;
+use crate::model::{Item, ItemType, Language, SiteMeta, SiteType};
+use crate::schedule;
+use serde::Deserialize;
+use serde::de::IntoDeserializer;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    Upcoming,
+    Airing,
+    Finished,
+}
+
+impl Status {
+    /// Derives the airing status from `begin`/`end`. Items without a begin
+    /// date are announced but not yet scheduled, so they count as upcoming.
+    pub fn of(item: &Item, now: Timestamp) -> Self {
+        let begin = item.begin.as_deref().and_then(Timestamp::parse);
+        let end = item.end.as_deref().and_then(schedule::parse_end);
+        match (begin, end) {
+            (None, _) => Status::Upcoming,
+            (Some(b), _) if b > now => Status::Upcoming,
+            (_, Some(e)) if e < now => Status::Finished,
+            _ => Status::Airing,
+        }
+    }
+
+    /// The first instant after `now` at which [`Status::of`] changes.
+    pub fn changes_at(item: &Item, now: Timestamp) -> Option<Timestamp> {
+        let begin = item.begin.as_deref().and_then(Timestamp::parse);
+        let end = item.end.as_deref().and_then(schedule::parse_end);
+        [begin, end.map(|e| e.add_secs(1))]
+            .into_iter()
+            .flatten()
+            .filter(|at| *at > now)
+            .min()
+    }
+}
+
+/// Filters accepted by `/api/items`.
+#[derive(Debug, Default)]
+pub struct ItemFilter<'a> {
+    pub type_field: Option<ItemType>,
+    pub original_lang: Option<Language>,
+    pub site: Option<&'a str>,
+    pub region: Option<&'a str>,
+    pub status: Option<Status>,
+}
+
+impl<'a> ItemFilter<'a> {
+    /// Reads `type`, `original_lang`, `site`, `region` and `status`. Empty
+    /// values and `all` are ignored; anything else unrecognized is an error
+    /// message.
+    pub fn from_query(query: &'a HashMap<String, String>) -> Result<Self, String> {
+        let get = |key: &str| {
+            query
+                .get(key)
+                .map(|v| v.trim())
+                .filter(|v| !v.is_empty() && *v != "all")
+        };
+
+        let status = match get("status") {
+            None => None,
+            Some("upcoming") => Some(Status::Upcoming),
+            Some("airing") => Some(Status::Airing),
+            Some("finished") => Some(Status::Finished),
+            Some(other) => return Err(format!("unknown 'status' value '{}'", other)),
+        };
+
+        Ok(Self {
+            type_field: get("type").map(|v| parse_enum("type", v)).transpose()?,
+            original_lang: get("original_lang")
+                .map(|v| parse_enum("original_lang", v))
+                .transpose()?,
+            site: get("site"),
+            region: get("region"),
+            status,
+        })
+    }
+
+    /// When the result for `items` next changes by itself, since the status
+    /// filter depends on the time it's evaluated at.
+    pub fn stale_at(&self, items: &[Item], now: Timestamp) -> Option<Timestamp> {
+        self.status?;
+        items
+            .iter()
+            .filter_map(|item| Status::changes_at(item, now))
+            .min()
+    }
+
+    /// Whether the region filter needs site metadata to be evaluated.
+    pub fn needs_site_meta(&self) -> bool {
+        self.region.is_some()
+    }
+
+    /// `site_meta` supplies the default regions of each site; items' own
+    /// `regions` override it. Info sites such as MAL never satisfy a region.
+    pub fn matches(&self, item: &Item, site_meta: Option<&SiteMeta>, now: Timestamp) -> bool {
+        if self
+            .type_field
+            .as_ref()
+            .is_some_and(|t| *t != item.type_field)
+            || self.original_lang.as_ref().is_some_and(|l| *l != item.lang)
+            || self.status.is_some_and(|s| s != Status::of(item, now))
+        {
+            return false;
+        }
+        if self.site.is_none() && self.region.is_none() {
+            return true;
+        }
+
+        item.sites.iter().any(|site| {
+            if self.site.is_some_and(|s| s != site.site) {
+                return false;
+            }
+            let Some(region) = self.region else {
+                return true;
+            };
+
+            let meta = site_meta.and_then(|m| m.get(&site.site));
+            if meta.is_none_or(|m| m.type_field == Some(SiteType::Info)) {
+                return false;
+            }
+            // Sites without a region list stream everywhere
+            match site
+                .regions
+                .as_ref()
+                .or(meta.and_then(|m| m.regions.as_ref()))
+            {
+                Some(regions) => regions.iter().any(|r| r.eq_ignore_ascii_case(region)),
+                None => true,
+            }
+        })
+    }
+}
+
+pub(crate) fn parse_enum<T: for<'de> Deserialize<'de>>(
+    key: &str,
+    value: &str,
+) -> Result<T, String> {
+    T::deserialize(IntoDeserializer::<serde::de::value::Error>::into_deserializer(value))
+        .map_err(|_| format!("unknown '{}' value '{}'", key, value))
+}
+
+/// Slices out one page. The cursor is the offset of the page's first item;
+/// the returned cursor is `None` on the last page.
+pub fn paginate<T>(
+    mut items: Vec<T>,
+    limit: Option<usize>,
+    cursor: Option<&str>,
+) -> Result<(Vec<T>, Option<String>), String> {
+    let offset = match cursor.filter(|c| !c.is_empty()) {
+        Some(c) => c
+            .parse::<usize>()
+            .map_err(|_| format!("invalid 'cursor' value '{}'", c))?,
+        None => 0,
+    };
+    let Some(limit) = limit else {
+        return Ok((items.split_off(offset.min(items.len())), None));
+    };
+    if limit == 0 {
+        return Err("'limit' must be positive".to_string());
+    }
+
+    let total = items.len();
+    let page: Vec<T> = items.into_iter().skip(offset).take(limit).collect();
+    let end = offset.saturating_add(limit);
+    let next = (end < total).then(|| end.to_string());
+    Ok((page, next))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SortKey {
+    Begin,
+    Title,
+    BroadcastWeekday,
+    End,
+}
+
+/// Ordering requested with `sort`/`order`. Titles are compared in
+/// `title_lang`, the language the response is localized for.
+#[derive(Debug)]
+pub struct ItemSort {
+    pub key: SortKey,
+    pub descending: bool,
+    pub title_lang: Language,
+}
+
+impl ItemSort {
+    /// Reads `sort` and `order`. Without `sort` there is nothing to do and
+    /// items stay in source order.
+    pub fn from_query(
+        query: &HashMap<String, String>,
+        title_lang: Language,
+    ) -> Result<Option<Self>, String> {
+        let get = |key: &str| query.get(key).map(|v| v.trim()).filter(|v| !v.is_empty());
+
+        let key = match get("sort") {
+            None => return Ok(None),
+            Some("begin") => SortKey::Begin,
+            Some("title") => SortKey::Title,
+            Some("broadcast_weekday") => SortKey::BroadcastWeekday,
+            Some("end") => SortKey::End,
+            Some(other) => return Err(format!("unknown 'sort' value '{}'", other)),
+        };
+        let descending = match get("order") {
+            None | Some("asc") => false,
+            Some("desc") => true,
+            Some(other) => return Err(format!("unknown 'order' value '{}'", other)),
+        };
+        Ok(Some(Self {
+            key,
+            descending,
+            title_lang,
+        }))
+    }
+
+    /// Sorts in place. Items lacking the sort value go last in either
+    /// order, and ties are broken by the original title so the result does
+    /// not depend on the order the month files were fetched in.
+    ///
+    /// `collate` compares titles, locale-aware in production; `utc_offset`
+    /// gives the offset of the zone whose weekdays are used.
+    pub fn sort(
+        &self,
+        items: &mut Vec<Item>,
+        collate: impl Fn(&str, &str) -> Ordering,
+        utc_offset: impl Fn(Timestamp) -> i64,
+    ) {
+        // Every sort below is stable, so this survives as the tie-breaker
+        items.sort_by(|a, b| a.title.cmp(&b.title));
+
+        match self.key {
+            SortKey::Title => {
+                let lang = &self.title_lang;
+                items.sort_by(|a, b| self.direct(collate(a.title_in(lang), b.title_in(lang))));
+            }
+            SortKey::Begin => {
+                self.sort_by_value(items, |i| i.begin.as_deref().and_then(Timestamp::parse))
+            }
+            SortKey::End => {
+                self.sort_by_value(items, |i| i.end.as_deref().and_then(schedule::parse_end))
+            }
+            SortKey::BroadcastWeekday => self.sort_by_value(items, |i| {
+                let start = i.broadcast_schedule?.start;
+                let local = start.add_secs(utc_offset(start)).as_secs();
+                // 1970-01-01 was a Thursday; Sunday is 0 as in JavaScript
+                let weekday = (local.div_euclid(86400) + 4).rem_euclid(7);
+                Some((weekday, local.rem_euclid(86400)))
+            }),
+        }
+    }
+
+    fn sort_by_value<K: Ord>(&self, items: &mut Vec<Item>, value: impl Fn(&Item) -> Option<K>) {
+        let mut keyed: Vec<(Option<K>, Item)> = items.drain(..).map(|i| (value(&i), i)).collect();
+        keyed.sort_by(|(a, _), (b, _)| match (a, b) {
+            (Some(a), Some(b)) => self.direct(a.cmp(b)),
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (None, None) => Ordering::Equal,
+        });
+        items.extend(keyed.into_iter().map(|(_, i)| i));
+    }
+
+    fn direct(&self, ordering: Ordering) -> Ordering {
+        if self.descending {
+            ordering.reverse()
+        } else {
+            ordering
+        }
+    }
+}
+
+/// Compares strings with `Intl.Collator` for the language's locale.
+pub fn collator(lang: &Language) -> impl Fn(&str, &str) -> Ordering {
+    let collator = js_sys::Intl::Collator::new(
+        &js_sys::Array::of1(&lang.tag().into()),
+        &js_sys::Object::new(),
+    );
+    let compare = collator.compare();
+
+    move |a, b| {
+        compare
+            .call2(&collator, &a.into(), &b.into())
+            .ok()
+            .and_then(|r| r.as_f64())
+            .map_or(Ordering::Equal, |r| r.total_cmp(&0.0))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn items() -> Vec<Item> {
+        serde_json::from_str(
+            r#"[
+                {
+                    "title": "Finished", "titleTranslate": { "en": ["Zeta"] }, "type": "tv", "lang": "ja",
+                    "officialSite": "", "begin": "2023-10-01T15:00:00.000Z", "end": "2023-12-24",
+                    "broadcast": "R/2023-10-01T15:00:00.000Z/P7D",
+                    "sites": [{ "site": "netflix", "id": "1" }, { "site": "mal", "id": "2" }]
+                },
+                {
+                    "title": "Airing", "titleTranslate": {}, "type": "tv", "lang": "zh-Hans",
+                    "officialSite": "", "begin": "2024-01-06T15:00:00.000Z", "end": "",
+                    "broadcast": "R/2024-01-06T15:00:00.000Z/P7D",
+                    "sites": [{ "site": "bilibili", "id": "3" }]
+                },
+                {
+                    "title": "Upcoming", "titleTranslate": {}, "type": "movie", "lang": "ja",
+                    "officialSite": "", "begin": "2024-04-01T00:00:00.000Z",
+                    "sites": [{ "site": "bilibili", "id": "4", "regions": ["HK", "TW"] }]
+                }
+            ]"#,
+        )
+        .unwrap()
+    }
+
+    fn site_meta() -> SiteMeta {
+        serde_json::from_str(
+            r#"{
+                "netflix": { "title": "Netflix", "urlTemplate": "", "type": "onair" },
+                "bilibili": { "title": "哔哩哔哩", "urlTemplate": "", "regions": ["CN"], "type": "onair" },
+                "mal": { "title": "MyAnimeList", "urlTemplate": "", "type": "info" }
+            }"#,
+        )
+        .unwrap()
+    }
+
+    fn filtered(pairs: &[(&str, &str)]) -> Vec<String> {
+        let query: HashMap<String, String> = pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        let filter = ItemFilter::from_query(&query).unwrap();
+        let meta = site_meta();
+        let now = Timestamp::parse("2024-02-01").unwrap();
+        items()
+            .into_iter()
+            .filter(|item| filter.matches(item, Some(&meta), now))
+            .map(|item| item.title)
+            .collect()
+    }
+
+    #[test]
+    fn test_item_filter() {
+        assert_eq!(filtered(&[]).len(), 3);
+        assert_eq!(filtered(&[("type", "movie")]), vec!["Upcoming"]);
+        assert_eq!(filtered(&[("original_lang", "zh-Hans")]), vec!["Airing"]);
+        assert_eq!(
+            filtered(&[("site", "bilibili")]),
+            vec!["Airing", "Upcoming"]
+        );
+        assert_eq!(filtered(&[("status", "finished")]), vec!["Finished"]);
+        assert_eq!(filtered(&[("status", "airing")]), vec!["Airing"]);
+        assert_eq!(filtered(&[("status", "all"), ("type", "")]).len(), 3);
+    }
+
+    #[test]
+    fn test_item_filter_stale_at() {
+        let query = HashMap::from([("status".to_string(), "airing".to_string())]);
+        let filter = ItemFilter::from_query(&query).unwrap();
+
+        let now = Timestamp::parse("2023-12-01").unwrap();
+        // "Finished" ends on the 24th, through the end of that day
+        assert_eq!(
+            filter.stale_at(&items(), now),
+            Timestamp::parse("2023-12-25")
+        );
+        let now = Timestamp::parse("2024-02-01").unwrap();
+        assert_eq!(
+            filter.stale_at(&items(), now),
+            Timestamp::parse("2024-04-01")
+        );
+
+        // Other filters don't depend on the time
+        let filter = ItemFilter::default();
+        assert_eq!(filter.stale_at(&items(), now), None);
+    }
+
+    #[test]
+    fn test_item_filter_region() {
+        // Netflix has no region list; the item-level override beats site meta
+        assert_eq!(filtered(&[("region", "CN")]), vec!["Finished", "Airing"]);
+        assert_eq!(filtered(&[("region", "tw")]), vec!["Finished", "Upcoming"]);
+        // Site and region must hold for the same site
+        assert!(filtered(&[("site", "mal"), ("region", "JP")]).is_empty());
+    }
+
+    #[test]
+    fn test_item_filter_rejects_unknown_values() {
+        let query = HashMap::from([("type".to_string(), "anime".to_string())]);
+        assert!(ItemFilter::from_query(&query).is_err());
+        let query = HashMap::from([("status".to_string(), "paused".to_string())]);
+        assert!(ItemFilter::from_query(&query).is_err());
+    }
+
+    fn sorted(pairs: &[(&str, &str)], title_lang: Language) -> Vec<String> {
+        let query: HashMap<String, String> = pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        let sort = ItemSort::from_query(&query, title_lang).unwrap().unwrap();
+
+        let mut items = items();
+        for item in &mut items {
+            item.resolve_schedules();
+        }
+        sort.sort(&mut items, str::cmp, |_| 9 * 3600);
+        items.into_iter().map(|item| item.title).collect()
+    }
+
+    #[test]
+    fn test_item_sort() {
+        assert_eq!(
+            sorted(&[("sort", "begin")], Language::Ja),
+            vec!["Finished", "Airing", "Upcoming"]
+        );
+        assert_eq!(
+            sorted(&[("sort", "begin"), ("order", "desc")], Language::Ja),
+            vec!["Upcoming", "Airing", "Finished"]
+        );
+        // Only "Finished" has an end date; the rest keep title order
+        assert_eq!(
+            sorted(&[("sort", "end"), ("order", "desc")], Language::Ja),
+            vec!["Finished", "Airing", "Upcoming"]
+        );
+        assert_eq!(
+            sorted(&[("sort", "title")], Language::En),
+            vec!["Airing", "Upcoming", "Finished"]
+        );
+        // Sunday 00:00 JST, then Monday 00:00 JST, then no broadcast
+        assert_eq!(
+            sorted(&[("sort", "broadcast_weekday")], Language::Ja),
+            vec!["Airing", "Finished", "Upcoming"]
+        );
+    }
+
+    #[test]
+    fn test_item_sort_query() {
+        let query = HashMap::new();
+        assert!(
+            ItemSort::from_query(&query, Language::Ja)
+                .unwrap()
+                .is_none()
+        );
+        let query = HashMap::from([("sort".to_string(), "rating".to_string())]);
+        assert!(ItemSort::from_query(&query, Language::Ja).is_err());
+        let query = HashMap::from([
+            ("sort".to_string(), "title".to_string()),
+            ("order".to_string(), "up".to_string()),
+        ]);
+        assert!(ItemSort::from_query(&query, Language::Ja).is_err());
+    }
+
+    #[test]
+    fn test_paginate() {
+        let items: Vec<u32> = (0..5).collect();
+
+        let (page, next) = paginate(items.clone(), Some(2), None).unwrap();
+        assert_eq!((page, next.as_deref()), (vec![0, 1], Some("2")));
+
+        let (page, next) = paginate(items.clone(), Some(2), Some("4")).unwrap();
+        assert_eq!((page, next), (vec![4], None));
+
+        let (page, next) = paginate(items.clone(), None, None).unwrap();
+        assert_eq!((page.len(), next), (5, None));
+
+        let (page, next) = paginate(items.clone(), Some(usize::MAX), Some("1")).unwrap();
+        assert_eq!((page, next), (vec![1, 2, 3, 4], None));
+
+        assert!(paginate(items.clone(), Some(0), None).is_err());
+        assert!(paginate(items, Some(2), Some("abc")).is_err());
+    }
+}
@@ -0,0 +1,465 @@
+use crate::datetime::Timestamp;
+use crate::model::{Item, Language, TitleTranslate};
+use crate::provider::tmdb::normalize_title;
+use crate::search::normalize;
+use std::collections::{BTreeSet, HashMap};
+
+/// Sites whose IDs identify a show across data sources.
+const LINK_SITES: [&str; 6] = ["mal", "anilist", "bangumi", "tmdb", "kitsu", "anidb"];
+
+// Title matches further apart than this are treated as different seasons
+const MAX_BEGIN_DISTANCE_DAYS: i64 = 90;
+
+/// Collapses items describing the same show into one canonical item.
+///
+/// Items are linked when they share an ID for any of [`LINK_SITES`]. Items
+/// whose titles normalize to the same key are linked too when both have a
+/// `begin` within a season of each other and the same type; season suffixes
+/// are stripped from the key, so sequels are told apart by `begin`. Either
+/// way, groups that
+/// already carry different IDs for the same site are never linked. The first
+/// item of each group (in input order) becomes the canonical one; the others
+/// contribute their `sites`, `title_translate` and any fields it lacks.
+pub fn resolve(items: Vec<Item>) -> Vec<Item> {
+    let mut groups = Groups::new(&items);
+
+    let mut by_site_id: HashMap<(&str, &str), usize> = HashMap::new();
+    for (i, item) in items.iter().enumerate() {
+        for (site, id) in link_ids(item) {
+            match by_site_id.get(&(site, id)) {
+                // A shared ID alone doesn't link shows that disagree on another site
+                Some(&j) if !groups.conflicts(i, j) => groups.union(i, j),
+                Some(_) => {}
+                None => {
+                    by_site_id.insert((site, id), i);
+                }
+            }
+        }
+    }
+
+    let mut by_title: HashMap<String, Vec<usize>> = HashMap::new();
+    for (i, item) in items.iter().enumerate() {
+        let keys: BTreeSet<String> = title_keys(item).collect();
+        for key in keys {
+            let candidates = by_title.entry(key).or_default();
+            if let Some(&j) = candidates
+                .iter()
+                .find(|&&j| plausible(&items[i], &items[j]) && !groups.conflicts(i, j))
+            {
+                groups.union(i, j);
+            }
+            candidates.push(i);
+        }
+    }
+
+    let mut members: Vec<Vec<Item>> = Vec::new();
+    let mut slots: HashMap<usize, usize> = HashMap::new();
+    for (i, item) in items.into_iter().enumerate() {
+        let root = groups.find(i);
+        let slot = *slots.entry(root).or_insert_with(|| {
+            members.push(Vec::new());
+            members.len() - 1
+        });
+        members[slot].push(item);
+    }
+
+    members
+        .into_iter()
+        .filter_map(|group| {
+            let mut group = group.into_iter();
+            let canonical = group.next()?;
+            Some(group.fold(canonical, merge_item))
+        })
+        .collect()
+}
+
+/// Union-find over item indices, tracking the linked site IDs of each group.
+struct Groups {
+    parent: Vec<usize>,
+    ids: Vec<HashMap<&'static str, BTreeSet<String>>>,
+}
+
+impl Groups {
+    fn new(items: &[Item]) -> Self {
+        let ids = items
+            .iter()
+            .map(|item| {
+                let mut ids: HashMap<&'static str, BTreeSet<String>> = HashMap::new();
+                for (site, id) in link_ids(item) {
+                    ids.entry(site).or_default().insert(id.to_string());
+                }
+                ids
+            })
+            .collect();
+        Self {
+            parent: (0..items.len()).collect(),
+            ids,
+        }
+    }
+
+    fn find(&mut self, i: usize) -> usize {
+        let mut root = i;
+        while self.parent[root] != root {
+            root = self.parent[root];
+        }
+        let mut node = i;
+        while self.parent[node] != root {
+            let next = self.parent[node];
+            self.parent[node] = root;
+            node = next;
+        }
+        root
+    }
+
+    fn union(&mut self, a: usize, b: usize) {
+        let (a, b) = (self.find(a), self.find(b));
+        if a == b {
+            return;
+        }
+        // The earlier index stays root so the canonical item keeps input order
+        let (root, child) = if a < b { (a, b) } else { (b, a) };
+        self.parent[child] = root;
+        for (site, ids) in std::mem::take(&mut self.ids[child]) {
+            self.ids[root].entry(site).or_default().extend(ids);
+        }
+    }
+
+    // Groups conflict when they carry disjoint IDs for the same site
+    fn conflicts(&mut self, a: usize, b: usize) -> bool {
+        let (a, b) = (self.find(a), self.find(b));
+        a != b
+            && self.ids[a].iter().any(|(site, ids)| {
+                self.ids[b]
+                    .get(site)
+                    .is_some_and(|other| ids.is_disjoint(other))
+            })
+    }
+}
+
+// Whether a title match between the two is plausible on its own
+fn plausible(a: &Item, b: &Item) -> bool {
+    let begin = |item: &Item| item.begin.as_deref().and_then(Timestamp::parse);
+    let close = match (begin(a), begin(b)) {
+        (Some(x), Some(y)) => (x.as_secs() - y.as_secs()).abs() <= MAX_BEGIN_DISTANCE_DAYS * 86400,
+        _ => false,
+    };
+    a.type_field == b.type_field && close
+}
+
+fn merge_item(mut canonical: Item, other: Item) -> Item {
+    for site in other.sites {
+        if !canonical.sites.iter().any(|s| s.site == site.site) {
+            canonical.sites.push(site);
+        }
+    }
+
+    merge_translations(&mut canonical.title_translate, other.title_translate);
+    if other.title != canonical.title {
+        push_unique(
+            translations_mut(&mut canonical.title_translate, &other.lang),
+            other.title,
+        );
+    }
+
+    for (field, value) in [
+        (&mut canonical.begin, other.begin),
+        (&mut canonical.end, other.end),
+        (&mut canonical.broadcast, other.broadcast),
+        (&mut canonical.comment, other.comment),
+    ] {
+        if field.as_deref().is_none_or(str::is_empty)
+            && value.as_deref().is_some_and(|v| !v.is_empty())
+        {
+            *field = value;
+        }
+    }
+    if canonical.broadcast_schedule.is_none() {
+        canonical.broadcast_schedule = other.broadcast_schedule;
+    }
+    if canonical.official_site.is_empty() {
+        canonical.official_site = other.official_site;
+    }
+    canonical
+}
+
+fn merge_translations(target: &mut TitleTranslate, source: TitleTranslate) {
+    for (lang, titles) in [
+        (Language::Ja, source.ja),
+        (Language::En, source.en),
+        (Language::ZhHans, source.zh_hans),
+        (Language::ZhHant, source.zh_hant),
+    ] {
+        for title in titles.into_iter().flatten() {
+            push_unique(translations_mut(target, &lang), title);
+        }
+    }
+}
+
+fn translations_mut<'a>(
+    translate: &'a mut TitleTranslate,
+    lang: &Language,
+) -> &'a mut Option<Vec<String>> {
+    match lang {
+        Language::Ja => &mut translate.ja,
+        Language::En => &mut translate.en,
+        Language::ZhHans => &mut translate.zh_hans,
+        Language::ZhHant => &mut translate.zh_hant,
+    }
+}
+
+fn push_unique(list: &mut Option<Vec<String>>, title: String) {
+    let list = list.get_or_insert_with(Vec::new);
+    if !list.contains(&title) {
+        list.push(title);
+    }
+}
+
+fn link_ids(item: &Item) -> impl Iterator<Item = (&'static str, &str)> {
+    item.sites.iter().filter_map(|s| {
+        let site = LINK_SITES.into_iter().find(|l| *l == s.site)?;
+        let id = s.id.as_deref().filter(|id| !id.is_empty())?;
+        Some((site, id))
+    })
+}
+
+/// Every title of the item, reduced to a case- and punctuation-insensitive key.
+fn title_keys(item: &Item) -> impl Iterator<Item = String> + '_ {
+    let translate = &item.title_translate;
+    std::iter::once(&item.title)
+        .chain(
+            [
+                &translate.ja,
+                &translate.en,
+                &translate.zh_hans,
+                &translate.zh_hant,
+            ]
+            .into_iter()
+            .flatten()
+            .flatten(),
+        )
+        .map(|t| normalize(&normalize_title(t)))
+        .filter(|key| !key.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn items(json: &str) -> Vec<Item> {
+        serde_json::from_str(json).unwrap()
+    }
+
+    fn titles(items: &[Item]) -> Vec<&str> {
+        items.iter().map(|i| i.title.as_str()).collect()
+    }
+
+    #[test]
+    fn test_resolve_shared_site_id() {
+        // bangumi-data and Jikan disagree on the title but share the MAL ID
+        let resolved = resolve(items(
+            r#"[
+                {
+                    "title": "葬送のフリーレン",
+                    "titleTranslate": { "zh-Hans": ["葬送的芙莉莲"] },
+                    "type": "tv", "lang": "ja", "officialSite": "https://frieren-anime.jp/",
+                    "begin": "2023-09-29T14:00:00.000Z", "end": "",
+                    "sites": [
+                        { "site": "bangumi", "id": "400602" },
+                        { "site": "mal", "id": "52991" }
+                    ]
+                },
+                {
+                    "title": "葬送のフリーレン",
+                    "titleTranslate": {
+                        "ja": ["葬送のフリーレン"],
+                        "en": ["Frieren: Beyond Journey's End", "Sousou no Frieren"]
+                    },
+                    "type": "tv", "lang": "ja", "officialSite": "",
+                    "end": "2024-03-22T00:00:00+00:00",
+                    "sites": [
+                        { "site": "mal", "id": "52991" },
+                        { "site": "anilist", "id": "154587" }
+                    ]
+                }
+            ]"#,
+        ));
+
+        assert_eq!(titles(&resolved), vec!["葬送のフリーレン"]);
+        let item = &resolved[0];
+        let sites: Vec<_> = item.sites.iter().map(|s| s.site.as_str()).collect();
+        assert_eq!(sites, vec!["bangumi", "mal", "anilist"]);
+        assert_eq!(
+            item.title_translate.zh_hans,
+            Some(vec!["葬送的芙莉莲".to_string()])
+        );
+        assert_eq!(
+            item.title_translate.en,
+            Some(vec![
+                "Frieren: Beyond Journey's End".to_string(),
+                "Sousou no Frieren".to_string()
+            ])
+        );
+        assert_eq!(
+            item.title_translate.ja,
+            Some(vec!["葬送のフリーレン".to_string()])
+        );
+        assert_eq!(item.end.as_deref(), Some("2024-03-22T00:00:00+00:00"));
+        assert_eq!(item.official_site, "https://frieren-anime.jp/");
+    }
+
+    #[test]
+    fn test_resolve_transitive_ids() {
+        // A and C only meet through B, which carries both IDs
+        let resolved = resolve(items(
+            r#"[
+                { "title": "A", "titleTranslate": {}, "type": "tv", "lang": "ja", "officialSite": "",
+                  "sites": [{ "site": "mal", "id": "1" }] },
+                { "title": "B", "titleTranslate": {}, "type": "tv", "lang": "ja", "officialSite": "",
+                  "sites": [{ "site": "mal", "id": "1" }, { "site": "anilist", "id": "2" }] },
+                { "title": "C", "titleTranslate": {}, "type": "tv", "lang": "ja", "officialSite": "",
+                  "sites": [{ "site": "anilist", "id": "2" }] }
+            ]"#,
+        ));
+
+        assert_eq!(titles(&resolved), vec!["A"]);
+        assert_eq!(
+            resolved[0].title_translate.ja,
+            Some(vec!["B".to_string(), "C".to_string()])
+        );
+    }
+
+    #[test]
+    fn test_resolve_shared_id_with_conflict() {
+        // Same MAL ID, but AniList tells them apart
+        let resolved = resolve(items(
+            r#"[
+                { "title": "A", "titleTranslate": {}, "type": "tv", "lang": "ja", "officialSite": "",
+                  "sites": [{ "site": "mal", "id": "1" }, { "site": "anilist", "id": "2" }] },
+                { "title": "B", "titleTranslate": {}, "type": "tv", "lang": "ja", "officialSite": "",
+                  "sites": [{ "site": "mal", "id": "1" }, { "site": "anilist", "id": "3" }] }
+            ]"#,
+        ));
+
+        assert_eq!(titles(&resolved), vec!["A", "B"]);
+    }
+
+    #[test]
+    fn test_resolve_title_match() {
+        // No shared IDs, but a translation matches after normalization
+        let resolved = resolve(items(
+            r#"[
+                {
+                    "title": "進撃の巨人",
+                    "titleTranslate": { "en": ["Attack on Titan"] },
+                    "type": "tv", "lang": "ja", "officialSite": "",
+                    "begin": "2013-04-06T16:58:00.000Z",
+                    "sites": [{ "site": "bangumi", "id": "55770" }]
+                },
+                {
+                    "title": "ATTACK ON TITAN",
+                    "titleTranslate": {},
+                    "type": "tv", "lang": "en", "officialSite": "",
+                    "begin": "2013-04-07T00:00:00+00:00",
+                    "sites": [{ "site": "mal", "id": "16498" }]
+                }
+            ]"#,
+        ));
+
+        assert_eq!(titles(&resolved), vec!["進撃の巨人"]);
+        assert_eq!(resolved[0].sites.len(), 2);
+    }
+
+    #[test]
+    fn test_resolve_keeps_collisions_apart() {
+        let resolved = resolve(items(
+            r#"[
+                {
+                    "title": "進撃の巨人",
+                    "titleTranslate": {},
+                    "type": "tv", "lang": "ja", "officialSite": "",
+                    "begin": "2013-04-06T16:58:00.000Z",
+                    "sites": [{ "site": "mal", "id": "16498" }]
+                },
+                {
+                    "title": "進撃の巨人 Season 2",
+                    "titleTranslate": {},
+                    "type": "tv", "lang": "ja", "officialSite": "",
+                    "begin": "2017-04-01T15:00:00.000Z",
+                    "sites": []
+                },
+                {
+                    "title": "進撃の巨人",
+                    "titleTranslate": {},
+                    "type": "tv", "lang": "ja", "officialSite": "",
+                    "begin": "2013-05-01T00:00:00.000Z",
+                    "sites": [{ "site": "mal", "id": "99999" }]
+                },
+                {
+                    "title": "進撃の巨人",
+                    "titleTranslate": {},
+                    "type": "movie", "lang": "ja", "officialSite": "",
+                    "begin": "2013-05-01T00:00:00.000Z",
+                    "sites": []
+                }
+            ]"#,
+        ));
+
+        // Far-apart begin, conflicting MAL ID and different type respectively
+        assert_eq!(resolved.len(), 4);
+    }
+
+    #[test]
+    fn test_resolve_title_match_needs_begin_and_same_season() {
+        let resolved = resolve(items(
+            r#"[
+                { "title": "薬屋のひとりごと", "titleTranslate": {}, "type": "tv", "lang": "ja",
+                  "officialSite": "", "begin": "2023-10-21T15:00:00.000Z", "sites": [] },
+                { "title": "薬屋のひとりごと 第2期", "titleTranslate": {}, "type": "tv", "lang": "ja",
+                  "officialSite": "", "begin": "2025-01-10T00:00:00+00:00", "sites": [] },
+                { "title": "薬屋のひとりごと", "titleTranslate": {}, "type": "tv", "lang": "ja",
+                  "officialSite": "", "sites": [] }
+            ]"#,
+        ));
+
+        // A sequel a season or more later, or a missing begin, keeps title
+        // matches apart
+        assert_eq!(resolved.len(), 3);
+    }
+
+    #[test]
+    fn test_resolve_does_not_bridge_conflicts() {
+        // The ID-less item may join either group, but must not join both
+        let resolved = resolve(items(
+            r#"[
+                { "title": "Title", "titleTranslate": {}, "type": "tv", "lang": "ja", "officialSite": "",
+                  "begin": "2024-01-01", "sites": [{ "site": "mal", "id": "1" }] },
+                { "title": "Title", "titleTranslate": {}, "type": "tv", "lang": "ja", "officialSite": "",
+                  "begin": "2024-01-01", "sites": [{ "site": "mal", "id": "2" }] },
+                { "title": "Title", "titleTranslate": {}, "type": "tv", "lang": "ja", "officialSite": "",
+                  "begin": "2024-01-01", "sites": [{ "site": "bangumi", "id": "3" }] }
+            ]"#,
+        ));
+
+        assert_eq!(resolved.len(), 2);
+    }
+
+    #[test]
+    fn test_title_keys() {
+        let resolved = resolve(items(
+            r#"[
+                { "title": "葬送のフリーレン", "titleTranslate": { "zh-Hans": ["葬送的芙莉莲"] },
+                  "type": "tv", "lang": "ja", "officialSite": "",
+                  "begin": "2023-09-29T14:00:00.000Z", "sites": [{ "site": "bangumi", "id": "400602" }] },
+                { "title": "葬送的芙莉蓮", "titleTranslate": {}, "type": "tv", "lang": "ja",
+                  "officialSite": "", "begin": "2023-09-29", "sites": [{ "site": "mal", "id": "52991" }] },
+                { "title": "ONE PIECE", "titleTranslate": {}, "type": "tv", "lang": "ja",
+                  "officialSite": "", "begin": "1999-10-20", "sites": [] },
+                { "title": "One Piece (1999)", "titleTranslate": {}, "type": "tv", "lang": "ja",
+                  "officialSite": "", "begin": "1999-10-20", "sites": [] }
+            ]"#,
+        ));
+
+        // Traditional and simplified forms, case and a year suffix all fold
+        assert_eq!(titles(&resolved), vec!["葬送のフリーレン", "ONE PIECE"]);
+    }
+}
@@ -0,0 +1,256 @@
+use crate::config;
+use crate::datetime::Timestamp;
+use crate::kv::{self, KeyValueStore};
+use serde_derive::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use worker::Result;
+
+// When the mapping was last written, in seconds since the epoch
+const UPDATED_KEY: &str = "updated";
+
+/// Sites the mapping links, named as in `Item::sites` and, with an `_id`
+/// suffix, the `/api/ids` parameters.
+pub const SITES: [&str; 6] = ["mal", "anilist", "anidb", "kitsu", "tmdb", "tvdb"];
+
+/// One show's IDs across sites. `tmdb_id` is in the form the TMDb provider
+/// takes, such as `tv/1234/season/2` or `movie/5678`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IdMapping {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mal_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub anilist_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub anidb_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub kitsu_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tmdb_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tvdb_id: Option<String>,
+}
+
+impl IdMapping {
+    pub fn get(&self, site: &str) -> Option<&str> {
+        match site {
+            "mal" => self.mal_id.as_deref(),
+            "anilist" => self.anilist_id.as_deref(),
+            "anidb" => self.anidb_id.as_deref(),
+            "kitsu" => self.kitsu_id.as_deref(),
+            "tmdb" => self.tmdb_id.as_deref(),
+            "tvdb" => self.tvdb_id.as_deref(),
+            _ => None,
+        }
+    }
+
+    /// Reads an entry of the list, or `None` when it links fewer than two
+    /// sites and so has nothing to cross-reference.
+    fn from_entry(entry: &ListEntry) -> Option<Self> {
+        let tmdb_id = raw_id(&entry.tmdb_id).map(|id| {
+            let movie = entry
+                .type_field
+                .as_deref()
+                .is_some_and(|t| t.eq_ignore_ascii_case("movie"));
+            let season = entry
+                .season
+                .as_ref()
+                .and_then(|s| s.get("tmdb"))
+                .and_then(|s| s.as_u64());
+            match (movie, season) {
+                (true, _) => format!("movie/{}", id),
+                (false, Some(season)) => format!("tv/{}/season/{}", id, season),
+                (false, None) => format!("tv/{}", id),
+            }
+        });
+        let mapping = Self {
+            mal_id: raw_id(&entry.mal_id),
+            anilist_id: raw_id(&entry.anilist_id),
+            anidb_id: raw_id(&entry.anidb_id),
+            kitsu_id: raw_id(&entry.kitsu_id),
+            tmdb_id,
+            tvdb_id: raw_id(&entry.tvdb_id),
+        };
+        (mapping.keys().count() >= 2).then_some(mapping)
+    }
+
+    fn keys(&self) -> impl Iterator<Item = String> + '_ {
+        SITES
+            .into_iter()
+            .filter_map(|site| Some(key(site, self.get(site)?)))
+    }
+}
+
+/// An entry of Fribb's `anime-list-full.json`. IDs are numbers in most
+/// entries and strings in a few, so they are read loosely.
+#[derive(Debug, Deserialize)]
+pub struct ListEntry {
+    #[serde(default)]
+    mal_id: Option<serde_json::Value>,
+    #[serde(default)]
+    anilist_id: Option<serde_json::Value>,
+    #[serde(default)]
+    anidb_id: Option<serde_json::Value>,
+    #[serde(default)]
+    kitsu_id: Option<serde_json::Value>,
+    #[serde(default, rename = "themoviedb_id")]
+    tmdb_id: Option<serde_json::Value>,
+    #[serde(default, rename = "thetvdb_id")]
+    tvdb_id: Option<serde_json::Value>,
+    #[serde(default, rename = "type")]
+    type_field: Option<String>,
+    /// `{"tvdb": 1, "tmdb": 1}` for shows split into seasons.
+    #[serde(default)]
+    season: Option<serde_json::Value>,
+}
+
+fn raw_id(value: &Option<serde_json::Value>) -> Option<String> {
+    match value.as_ref()? {
+        serde_json::Value::Number(n) => Some(n.to_string()),
+        serde_json::Value::String(s) => Some(s.trim().to_string()).filter(|s| !s.is_empty()),
+        _ => None,
+    }
+}
+
+fn key(site: &str, id: &str) -> String {
+    format!("{}:{}", site.trim().to_lowercase(), id.trim())
+}
+
+fn shard_of(key: &str) -> usize {
+    kv::shard_of(key, config::ID_MAP_SHARDS)
+}
+
+fn shard_key(shard: usize) -> String {
+    format!("shard:{}", shard)
+}
+
+/// Whether the stored mapping is missing or older than
+/// [`config::ID_MAP_REFRESH_SECS`].
+pub async fn refresh_due(store: &impl KeyValueStore, now: Timestamp) -> Result<bool> {
+    let updated: Option<i64> = store
+        .get_text(UPDATED_KEY)
+        .await?
+        .and_then(|u| u.parse().ok());
+    Ok(updated.is_none_or(|u| now.as_secs() - u >= config::ID_MAP_REFRESH_SECS))
+}
+
+/// Replaces the stored mapping with `entries`, spread over
+/// [`config::ID_MAP_SHARDS`] values keyed by a hash of `site:id`. An ID
+/// listed more than once keeps its first entry. Returns the number of shows
+/// stored.
+pub async fn save(
+    store: &impl KeyValueStore,
+    entries: &[ListEntry],
+    now: Timestamp,
+) -> Result<usize> {
+    let mappings: Vec<IdMapping> = entries.iter().filter_map(IdMapping::from_entry).collect();
+
+    let mut shards: Vec<BTreeMap<String, &IdMapping>> =
+        vec![BTreeMap::new(); config::ID_MAP_SHARDS];
+    for mapping in &mappings {
+        for key in mapping.keys() {
+            shards[shard_of(&key)].entry(key).or_insert(mapping);
+        }
+    }
+
+    // Every shard is rewritten, so IDs dropped from the list go away too
+    for (i, shard) in shards.iter().enumerate() {
+        store
+            .put_text(&shard_key(i), serde_json::to_string(shard)?)
+            .await?;
+    }
+    store
+        .put_text(UPDATED_KEY, now.as_secs().to_string())
+        .await?;
+    Ok(mappings.len())
+}
+
+/// The mapping of the first `(site, id)` pair in `ids` that has one.
+pub async fn lookup(store: &impl KeyValueStore, ids: &[(&str, &str)]) -> Result<Option<IdMapping>> {
+    for (site, id) in ids {
+        let key = key(site, id);
+        let Some(text) = store.get_text(&shard_key(shard_of(&key))).await? else {
+            continue;
+        };
+        let mut shard: BTreeMap<String, IdMapping> = serde_json::from_str(&text)?;
+        if let Some(mapping) = shard.remove(&key) {
+            return Ok(Some(mapping));
+        }
+    }
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kv::MemoryStore;
+    use futures::executor::block_on;
+
+    fn entries() -> Vec<ListEntry> {
+        serde_json::from_str(
+            r#"[
+                { "anidb_id": 69, "anilist_id": 21, "kitsu_id": 12, "mal_id": 21,
+                  "themoviedb_id": 37854, "thetvdb_id": 81797, "type": "TV",
+                  "season": { "tvdb": 1, "tmdb": 1 }, "imdb_id": "tt0388629" },
+                { "anidb_id": 2847, "mal_id": 199, "themoviedb_id": "129",
+                  "type": "MOVIE", "anilist_id": 199 },
+                { "anidb_id": 17617, "mal_id": 52991, "themoviedb_id": 209867,
+                  "type": "TV", "anilist_id": 154587 },
+                { "anidb_id": 18001, "type": "ONA", "kitsu_id": "" },
+                { "mal_id": 21, "anilist_id": 99999, "type": "SPECIAL" }
+            ]"#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_from_entry() {
+        let mappings: Vec<IdMapping> = entries().iter().filter_map(IdMapping::from_entry).collect();
+        assert_eq!(mappings.len(), 4);
+        assert_eq!(
+            mappings[0],
+            IdMapping {
+                mal_id: Some("21".into()),
+                anilist_id: Some("21".into()),
+                anidb_id: Some("69".into()),
+                kitsu_id: Some("12".into()),
+                tmdb_id: Some("tv/37854/season/1".into()),
+                tvdb_id: Some("81797".into()),
+            }
+        );
+        assert_eq!(mappings[1].tmdb_id.as_deref(), Some("movie/129"));
+        assert_eq!(mappings[2].tmdb_id.as_deref(), Some("tv/209867"));
+    }
+
+    #[test]
+    fn test_save_and_lookup() {
+        let store = MemoryStore::default();
+        let now = Timestamp::from_secs(1_700_000_000);
+        assert!(block_on(refresh_due(&store, now)).unwrap());
+
+        assert_eq!(block_on(save(&store, &entries(), now)).unwrap(), 4);
+        assert_eq!(store.0.borrow().len(), config::ID_MAP_SHARDS + 1);
+        assert!(!block_on(refresh_due(&store, now.add_secs(60))).unwrap());
+        let later = now.add_secs(config::ID_MAP_REFRESH_SECS);
+        assert!(block_on(refresh_due(&store, later)).unwrap());
+
+        let found = block_on(lookup(&store, &[("mal", "52991")]))
+            .unwrap()
+            .unwrap();
+        assert_eq!(found.tmdb_id.as_deref(), Some("tv/209867"));
+        assert_eq!(found.anidb_id.as_deref(), Some("17617"));
+
+        // The first entry listing an ID keeps it
+        let found = block_on(lookup(&store, &[("mal", " 21 ")]))
+            .unwrap()
+            .unwrap();
+        assert_eq!(found.anilist_id.as_deref(), Some("21"));
+
+        // Pairs are tried in order until one is known
+        let ids = [("anidb", "1"), ("TMDB", "movie/129")];
+        let found = block_on(lookup(&store, &ids)).unwrap().unwrap();
+        assert_eq!(found.mal_id.as_deref(), Some("199"));
+
+        assert_eq!(block_on(lookup(&store, &[("kitsu", "1")])).unwrap(), None);
+    }
+}
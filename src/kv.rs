@@ -0,0 +1,39 @@
+use worker::Result;
+
+/// Minimal key-value storage, implemented by Workers KV in production.
+pub trait KeyValueStore {
+    async fn get_text(&self, key: &str) -> Result<Option<String>>;
+    async fn put_text(&self, key: &str, value: String) -> Result<()>;
+    async fn delete(&self, key: &str) -> Result<()>;
+}
+
+/// Which of `shards` values `key` is stored in. FNV-1a, so a key lands in the
+/// same shard in every build.
+pub fn shard_of(key: &str, shards: usize) -> usize {
+    let hash = key.bytes().fold(0x811c_9dc5_u32, |h, b| {
+        (h ^ b as u32).wrapping_mul(0x0100_0193)
+    });
+    hash as usize % shards
+}
+
+/// In-memory store for tests.
+#[cfg(test)]
+#[derive(Default)]
+pub struct MemoryStore(pub std::cell::RefCell<std::collections::HashMap<String, String>>);
+
+#[cfg(test)]
+impl KeyValueStore for MemoryStore {
+    async fn get_text(&self, key: &str) -> Result<Option<String>> {
+        Ok(self.0.borrow().get(key).cloned())
+    }
+
+    async fn put_text(&self, key: &str, value: String) -> Result<()> {
+        self.0.borrow_mut().insert(key.to_string(), value);
+        Ok(())
+    }
+
+    async fn delete(&self, key: &str) -> Result<()> {
+        self.0.borrow_mut().remove(key);
+        Ok(())
+    }
+}
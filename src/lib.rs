@@ -2,9 +2,19 @@ use serde_derive::Serialize;
 use std::sync::OnceLock;
 use worker::*;
 
+mod calendar;
 mod config;
+mod datetime;
+mod filter;
+mod identity;
+mod idmap;
+mod kv;
+mod locale;
 mod model;
+mod overrides;
 mod provider;
+mod schedule;
+mod search;
 mod utils;
 use model::{Item, SiteMeta, SiteMetadata, SiteType};
 
@@ -66,10 +76,23 @@ struct TmdbAttribution {
 pub async fn main(req: Request, env: Env, _ctx: Context) -> Result<Response> {
     let cache = Cache::open(format!("housou-cache-{}", config::CACHE_VERSION)).await;
     let url = req.url()?;
+    // Metadata depends on the overrides, so key it on their revision too
+    let revision = match url.path() {
+        "/api/metadata" => match env.kv(config::OVERRIDES_BINDING) {
+            Ok(kv) => overrides::revision(&kv).await.ok().flatten(),
+            Err(_) => None,
+        },
+        _ => None,
+    };
+    let cache_key = cache_key(
+        &url,
+        req.headers().get("Accept-Language")?.as_deref(),
+        revision.as_deref(),
+    );
 
     // 1. Handle caching and routing
     let resp = if req.method() == Method::Get {
-        if let Ok(Some(mut cached_resp)) = cache.get(url.as_str(), true).await {
+        if let Ok(Some(mut cached_resp)) = cache.get(cache_key.as_str(), true).await {
             // Use cached response, clone to make it mutable for adding security headers
             cached_resp.cloned()?
         } else {
@@ -84,7 +107,8 @@ pub async fn main(req: Request, env: Env, _ctx: Context) -> Result<Response> {
                         &format!("public, max-age={}", config::CACHE_TTL_API),
                     )?;
                 }
-                let _ = cache.put(url.as_str(), fresh_resp.cloned()?).await;
+                fresh_resp = fresh_resp.add_header("Vary", "Accept-Language")?;
+                let _ = cache.put(cache_key.as_str(), fresh_resp.cloned()?).await;
             }
             fresh_resp
         }
@@ -96,6 +120,94 @@ pub async fn main(req: Request, env: Env, _ctx: Context) -> Result<Response> {
     resp.add_security_headers()
 }
 
+// Responses may be localized from Accept-Language, which the Cache API does
+// not vary on. Key on the negotiated language rather than the raw header so
+// that equivalent headers share an entry.
+fn cache_key(url: &Url, accept_language: Option<&str>, revision: Option<&str>) -> String {
+    let language = accept_language
+        .and_then(locale::negotiate)
+        .unwrap_or_default();
+    let mut key = url.clone();
+    key.query_pairs_mut().append_pair("_lang", language.tag());
+    if let Some(revision) = revision {
+        key.query_pairs_mut().append_pair("_rev", revision);
+    }
+    key.to_string()
+}
+
+#[event(scheduled)]
+pub async fn scheduled(_event: ScheduledEvent, env: Env, _ctx: ScheduleContext) {
+    match env.kv(config::SEARCH_INDEX_BINDING) {
+        Ok(kv) => {
+            let current_year = js_sys::Date::new_0().get_full_year() as i32;
+            match search::index::update(&kv, &BangumiSource, current_year).await {
+                Ok(years) => console_log!("Search index refreshed for {:?}", years),
+                Err(e) => console_log!("Search index refresh failed: {:?}", e),
+            }
+        }
+        Err(e) => console_log!("Search index KV binding missing: {:?}", e),
+    }
+
+    // The ID mapping is optional, so a missing binding isn't logged
+    if let Ok(kv) = env.kv(config::ID_MAP_BINDING) {
+        match refresh_id_map(&kv).await {
+            Ok(Some(count)) => console_log!("ID mapping refreshed with {} shows", count),
+            Ok(None) => {}
+            Err(e) => console_log!("ID mapping refresh failed: {:?}", e),
+        }
+    }
+
+    // So is AniDB's titles dump, indexed once per upload
+    if let Ok(kv) = env.kv(config::ANIDB_TITLES_BINDING) {
+        match provider::anidb::refresh_titles(&kv).await {
+            Ok(Some(count)) => console_log!("AniDB titles indexed for {} anime", count),
+            Ok(None) => {}
+            Err(e) => console_log!("AniDB titles indexing failed: {:?}", e),
+        }
+    }
+}
+
+/// Re-fetches the ID mapping once it is a day old. One subrequest on top of
+/// the search index's, and only on the run that refreshes it.
+async fn refresh_id_map(kv: &worker::kv::KvStore) -> Result<Option<usize>> {
+    let now = datetime::Timestamp::now();
+    if !idmap::refresh_due(kv, now).await? {
+        return Ok(None);
+    }
+    let entries: Vec<idmap::ListEntry> = utils::fetch_json(config::ID_MAP_URL)
+        .await?
+        .ok_or_else(|| Error::RustError("ID mapping list not found".into()))?;
+    idmap::save(kv, &entries, now).await.map(Some)
+}
+
+impl kv::KeyValueStore for worker::kv::KvStore {
+    async fn get_text(&self, key: &str) -> Result<Option<String>> {
+        Ok(self.get(key).text().await?)
+    }
+
+    async fn put_text(&self, key: &str, value: String) -> Result<()> {
+        Ok(self.put(key, value)?.execute().await?)
+    }
+
+    async fn delete(&self, key: &str) -> Result<()> {
+        Ok(worker::kv::KvStore::delete(self, key).await?)
+    }
+}
+
+/// Reads a whole year of bangumi-data, one file per month.
+struct BangumiSource;
+
+impl search::index::ItemSource for BangumiSource {
+    async fn fetch_year(&self, year: i32) -> Result<Vec<(u32, Item)>> {
+        let tasks = (1..=12).map(|month| fetch_bangumi_month(year, month));
+        let mut items = Vec::new();
+        for (month, result) in (1..=12).zip(futures::future::join_all(tasks).await) {
+            items.extend(result?.into_iter().map(|item| (month, item)));
+        }
+        Ok(items)
+    }
+}
+
 async fn fetch_site_meta() -> Result<SiteMeta> {
     let mut sites: SiteMeta = std::collections::HashMap::new();
     let types = [
@@ -147,92 +259,148 @@ fn season_to_num(season: &str) -> i32 {
     }
 }
 
-async fn fetch_items_for_season(year: i32, season: Option<&str>) -> Result<Vec<Item>> {
+// Season names as used by bangumi-data month ranges and by Jikan's season API
+const SEASONS: [(&str, &str); 4] = [
+    ("Winter", "winter"),
+    ("Spring", "spring"),
+    ("Summer", "summer"),
+    ("Autumn", "fall"),
+];
+
+/// A season's items. `partial` is set when some upcoming seasons from Jikan
+/// were left out because it failed, in which case the result shouldn't be
+/// cached.
+struct SeasonItems {
+    items: Vec<Item>,
+    partial: bool,
+}
+
+async fn fetch_items_for_season(year: i32, season: Option<&str>) -> Result<SeasonItems> {
     let current_year = js_sys::Date::new_0().get_full_year() as i32;
-    let current_season_str = get_current_season();
-
-    // Determine if we should use Jikan (Future) or Bangumi (Past/Present)
-    // Future if:
-    // 1. Year > Current Year
-    // 2. Year == Current Year AND Season > Current Season
-    // Note: If season is "all" (None), we treat it as future if year > current_year.
-    // If year == current_year and season is "all", we technically have mixed data (past seasons + future seasons).
-    // The current bangumi-data implementation for "all" fetches all months (1-12).
-    // If we want to strictly follow the requirement "use Jikan for future", we might need to mix sources for the current year "all" request,
-    // but the requirement says "to current time data from bangumi-data", "future from jikan".
-    // A simple split is:
-    // If year > current_year: Use Jikan (All seasons).
-    // If year == current_year:
-    //    If specific season requested:
-    //       If season > current_season: Use Jikan.
-    //       Else: Use Bangumi.
-    //    If "all" requested:
-    //       Use Bangumi (it covers 1-12, potentially empty for future months but usually pre-filled? No, bangumi-data updates periodically).
-    //       Actually, bangumi-data might not have future data yet.
-    //       Let's stick to the prompt: "Future upcoming data use Jikan API".
-    //       Ideally for "all" in current year, we'd fetch past/current from Bangumi and future from Jikan, but that's complex to merge.
-    //       Let's assume "all" for current year uses Bangumi (safe default).
-    //       Only if user explicitly selects a future season or a future year we switch to Jikan.
-
-    let is_future = if year > current_year {
-        true
+    let current_season = season_to_num(get_current_season());
+
+    // bangumi-data covers the past and present; upcoming seasons come from Jikan.
+    // For the current year's "all" view, both are fetched and merged so that
+    // the remaining seasons of the year still show up.
+    let mut partial = false;
+    let mut items = if year > current_year {
+        let seasons = match season {
+            Some(s) => vec![s],
+            None => SEASONS.iter().map(|(name, _)| *name).collect(),
+        };
+        let fetched = fetch_jikan_seasons(year, &seasons).await?;
+        partial = fetched.partial;
+        fetched.items
     } else if year == current_year {
-        if let Some(s) = season {
-            season_to_num(s) > season_to_num(current_season_str)
-        } else {
-            false
+        match season {
+            Some(s) if season_to_num(s) > current_season => {
+                fetch_jikan_seasons(year, &[s]).await?.items
+            }
+            Some(s) => fetch_bangumi_months(year, season_months(Some(s))).await?,
+            None => {
+                let future: Vec<&str> = SEASONS
+                    .iter()
+                    .map(|(name, _)| *name)
+                    .filter(|name| season_to_num(name) > current_season)
+                    .collect();
+                let (bangumi, jikan) = futures::future::join(
+                    fetch_bangumi_months(year, season_months(None)),
+                    fetch_jikan_seasons(year, &future),
+                )
+                .await;
+                let jikan = match jikan {
+                    Ok(fetched) => {
+                        partial = fetched.partial;
+                        fetched.items
+                    }
+                    Err(e) => {
+                        console_log!("Leaving out upcoming seasons of {}: {:?}", year, e);
+                        partial = true;
+                        Vec::new()
+                    }
+                };
+                identity::resolve([bangumi?, jikan].concat())
+            }
         }
     } else {
-        false
+        fetch_bangumi_months(year, season_months(season)).await?
     };
 
-    if is_future {
-        if let Some(s) = season {
-            let jikan_season = match s {
-                "Autumn" => "fall",
-                _ => s,
-            };
-            return provider::jikan::fetch_season(year, &jikan_season.to_lowercase()).await;
-        } else {
-            // Fetch all 4 seasons from Jikan
-            let seasons = ["winter", "spring", "summer", "fall"]; // Jikan uses "fall" instead of "autumn"
-            let tasks = seasons
-                .iter()
-                .map(|s| provider::jikan::fetch_season(year, s));
-            let results = futures::future::join_all(tasks).await;
-            let mut all_items = Vec::new();
-            for items in results.into_iter().flatten() {
-                all_items.extend(items);
-            }
-            return Ok(all_items);
-        }
+    let now = datetime::Timestamp::now();
+    for item in &mut items {
+        resolve_item(item, now);
     }
+    Ok(SeasonItems { items, partial })
+}
+
+/// Fills in what an item derives from its raw data: `broadcastSchedule`
+/// and the projected `nextEpisode`.
+fn resolve_item(item: &mut Item, now: datetime::Timestamp) {
+    item.resolve_schedules();
+    let end = item.end.as_deref().and_then(schedule::parse_end);
+    item.next_episode =
+        schedule::project_next_episode(&[], item.broadcast_schedule.as_ref(), end, now);
+}
+
+/// `ttl`, shortened to expire when `stale_at` passes, though never below a
+/// minute.
+fn cache_ttl_until(
+    ttl: i32,
+    stale_at: Option<datetime::Timestamp>,
+    now: datetime::Timestamp,
+) -> i32 {
+    match stale_at {
+        Some(at) => ttl.min((at.as_secs() - now.as_secs()).clamp(60, i32::MAX as i64) as i32),
+        None => ttl,
+    }
+}
 
-    // Bangumi Data logic
-    let months = match season {
+fn season_months(season: Option<&str>) -> Vec<u32> {
+    match season {
         Some("Winter") => vec![1, 2, 3],
         Some("Spring") => vec![4, 5, 6],
         Some("Summer") => vec![7, 8, 9],
         Some("Autumn") => vec![10, 11, 12],
         _ => (1..=12).collect(),
-    };
+    }
+}
 
-    let mut all_items = Vec::new();
-    let mut futures = Vec::new();
-
-    for &month in &months {
-        let url = format!("{}items/{}/{:02}.json", config::BASE_DATA_URL, year, month);
-        futures.push(async move {
-            match utils::fetch_json::<Vec<Item>>(&url).await {
-                Ok(Some(items)) => Ok(items),
-                Ok(None) => {
-                    console_log!("Month data not found (404), skipping: {}", url);
-                    Ok(Vec::new())
-                }
-                Err(e) => Err(e),
+/// Jikan's listings for `seasons` of `year`. Seasons Jikan fails on are
+/// left out and the result marked partial; it's an error only when every
+/// season failed.
+async fn fetch_jikan_seasons(year: i32, seasons: &[&str]) -> Result<SeasonItems> {
+    let tasks = seasons.iter().filter_map(|season| {
+        SEASONS
+            .iter()
+            .find(|(name, _)| name == season)
+            .map(|(_, jikan_season)| provider::jikan::fetch_season(year, jikan_season))
+    });
+    let results = futures::future::join_all(tasks).await;
+
+    let mut fetched = SeasonItems {
+        items: Vec::new(),
+        partial: false,
+    };
+    let mut last_error = None;
+    for result in results {
+        match result {
+            Ok(items) => fetched.items.extend(items),
+            Err(e) => {
+                console_log!("Jikan season of {} failed: {:?}", year, e);
+                fetched.partial = true;
+                last_error = Some(e);
             }
-        });
+        }
+    }
+    match last_error {
+        Some(e) if fetched.items.is_empty() => Err(e),
+        _ => Ok(fetched),
     }
+}
+
+async fn fetch_bangumi_months(year: i32, months: Vec<u32>) -> Result<Vec<Item>> {
+    let mut all_items = Vec::new();
+    let futures = months.iter().map(|&month| fetch_bangumi_month(year, month));
 
     let results = futures::future::join_all(futures).await;
     for result in results {
@@ -241,11 +409,90 @@ async fn fetch_items_for_season(year: i32, season: Option<&str>) -> Result<Vec<I
     Ok(all_items)
 }
 
-async fn router(req: Request, env: Env) -> Result<Response> {
+async fn fetch_bangumi_month(year: i32, month: u32) -> Result<Vec<Item>> {
+    let url = format!("{}items/{}/{:02}.json", config::BASE_DATA_URL, year, month);
+    match utils::fetch_json::<Vec<Item>>(&url).await? {
+        Some(items) => Ok(items),
+        None => {
+            console_log!("Month data not found (404), skipping: {}", url);
+            Ok(Vec::new())
+        }
+    }
+}
+
+fn parse_season_query(
+    query: &std::collections::HashMap<String, String>,
+) -> Option<(i32, Option<&str>)> {
+    let year = query.get("year").and_then(|y| y.parse::<i32>().ok())?;
+    let season = match query.get("season").map(|s| s.as_str()) {
+        Some("all") | None | Some("") => None,
+        Some(s) => Some(s),
+    };
+    Some((year, season))
+}
+
+/// Reads what metadata search matches against: `title` plus any number of
+/// `alt_title`s, the year from `year` or `begin`, `type` and `episodes`.
+fn parse_match_query(
+    url: &Url,
+    query: &std::collections::HashMap<String, String>,
+) -> std::result::Result<provider::scoring::MatchQuery, String> {
+    let alt_titles: Vec<String> = url
+        .query_pairs()
+        .filter(|(k, _)| k == "alt_title")
+        .map(|(_, v)| v.into_owned())
+        .collect();
+    let mut match_query = provider::scoring::MatchQuery::new(
+        query
+            .get("title")
+            .into_iter()
+            .chain(&alt_titles)
+            .map(|t| t.as_str()),
+    );
+
+    match_query.year = query
+        .get("year")
+        .or_else(|| query.get("begin"))
+        .and_then(|d| d.get(0..4))
+        .and_then(|y| y.parse::<i32>().ok());
+    match_query.episodes = query.get("episodes").and_then(|e| e.parse().ok());
+    match_query.type_field = query
+        .get("type")
+        .filter(|t| !t.is_empty())
+        .map(|t| filter::parse_enum("type", t))
+        .transpose()?;
+    Ok(match_query)
+}
+
+/// The providers to use: the `providers` parameter when given, limited to
+/// those the deployment has enabled, otherwise all enabled ones.
+fn parse_providers(
+    query: &std::collections::HashMap<String, String>,
+    env: &Env,
+) -> std::result::Result<Vec<provider::priority::ProviderKind>, String> {
+    let requested = query
+        .get("providers")
+        .map(|p| provider::priority::parse(p))
+        .transpose()?;
+    Ok(provider::priority::select(
+        &provider::enabled_providers(env),
+        requested.as_deref(),
+    ))
+}
+
+async fn router(mut req: Request, env: Env) -> Result<Response> {
     let method = req.method();
     let path = req.path();
     let url = req.url()?;
     let query: std::collections::HashMap<_, _> = url.query_pairs().into_owned().collect();
+    let accept_language = req.headers().get("Accept-Language")?;
+    // Every route localizes for `lang`, falling back to Accept-Language
+    let requested_language = || {
+        locale::resolve(
+            query.get("lang").map(|s| s.as_str()),
+            accept_language.as_deref(),
+        )
+    };
 
     match (method, path.as_str()) {
         (Method::Get, "/api/config") => {
@@ -276,43 +523,515 @@ async fn router(req: Request, env: Env) -> Result<Response> {
                 )
         }
         (Method::Get, "/api/items") => {
-            let year_param = query.get("year").and_then(|y| y.parse::<i32>().ok());
-            let season_param = query.get("season").map(|s| s.as_str());
+            let Some((target_year, target_season)) = parse_season_query(&query) else {
+                return Response::error("Bad Request: 'year' parameter is required", 400);
+            };
 
-            if year_param.is_none() {
+            let item_filter = match filter::ItemFilter::from_query(&query) {
+                Ok(f) => f,
+                Err(e) => return Response::error(format!("Bad Request: {}", e), 400),
+            };
+            let limit = match query.get("limit").map(|l| l.parse::<usize>()) {
+                Some(Ok(l)) => Some(l.min(config::ITEMS_MAX_LIMIT)),
+                Some(Err(_)) => return Response::error("Bad Request: invalid 'limit'", 400),
+                None => None,
+            };
+            let language = match requested_language() {
+                Ok(l) => l,
+                Err(e) => return Response::error(format!("Bad Request: {}", e), 400),
+            };
+            let item_sort = match filter::ItemSort::from_query(&query, language.clone()) {
+                Ok(s) => s,
+                Err(e) => return Response::error(format!("Bad Request: {}", e), 400),
+            };
+
+            let SeasonItems { items, partial } =
+                fetch_items_for_season(target_year, target_season).await?;
+            let site_meta = if item_filter.needs_site_meta() {
+                Some(fetch_site_meta().await?)
+            } else {
+                None
+            };
+
+            let now = datetime::Timestamp::now();
+            let status_changes_at = item_filter.stale_at(&items, now);
+            let mut items: Vec<Item> = items
+                .into_iter()
+                .filter(|item| item_filter.matches(item, site_meta.as_ref(), now))
+                .collect();
+
+            if let Some(sort) = &item_sort {
+                // Weekdays follow the broadcasters' zone, as bangumi-data times do
+                sort.sort(&mut items, filter::collator(&sort.title_lang), |at| {
+                    datetime::utc_offset(config::SCHEDULE_DEFAULT_TIME_ZONE, at).unwrap_or(0)
+                });
+            }
+
+            let cursor = query.get("cursor").map(|c| c.as_str());
+            let (mut page, next_cursor) = match filter::paginate(items, limit, cursor) {
+                Ok(p) => p,
+                Err(e) => return Response::error(format!("Bad Request: {}", e), 400),
+            };
+            locale::localize(&mut page, &language);
+
+            // Don't keep serving a projected episode after it has aired, or a
+            // status filter's result after an item changes status
+            let stale_at = page
+                .iter()
+                .filter_map(|item| Some(item.next_episode.as_ref()?.airs_at))
+                .chain(status_changes_at)
+                .min();
+            let cache_control = if partial {
+                "no-store".to_string()
+            } else {
+                let max_age = cache_ttl_until(config::CACHE_TTL_API, stale_at, now);
+                format!("public, max-age={}", max_age)
+            };
+
+            let resp = Response::from_json(&page)?
+                .add_cors(&env)?
+                .add_header("Content-Language", language.tag())?
+                .add_header("Cache-Control", &cache_control)?;
+            match next_cursor {
+                Some(next) => resp
+                    .add_header("X-Next-Cursor", &next)?
+                    .add_header("Access-Control-Expose-Headers", "X-Next-Cursor"),
+                None => Ok(resp),
+            }
+        }
+        (Method::Get, "/api/calendar.ics") => {
+            let Some((target_year, target_season)) = parse_season_query(&query) else {
                 return Response::error("Bad Request: 'year' parameter is required", 400);
+            };
+            let site = query
+                .get("site")
+                .map(|s| s.as_str())
+                .filter(|s| !s.is_empty() && *s != "all");
+            let language = match requested_language() {
+                Ok(l) => l,
+                Err(e) => return Response::error(format!("Bad Request: {}", e), 400),
+            };
+
+            let SeasonItems { mut items, partial } =
+                fetch_items_for_season(target_year, target_season).await?;
+            locale::localize(&mut items, &language);
+
+            let name = format!("Housou {} {}", target_year, target_season.unwrap_or("All"));
+            let body = calendar::build_calendar(&name, &items, site, datetime::Timestamp::now());
+
+            let resp = Response::ok(body)?
+                .add_cors(&env)?
+                .add_header("Content-Type", "text/calendar; charset=utf-8")?
+                .add_header("Content-Language", language.tag())?;
+            if partial {
+                resp.add_header("Cache-Control", "no-store")
+            } else {
+                Ok(resp)
+            }
+        }
+        (Method::Get, "/api/schedule") => {
+            let time_zone = query
+                .get("tz")
+                .map(|s| s.as_str())
+                .filter(|s| !s.is_empty())
+                .unwrap_or(config::SCHEDULE_DEFAULT_TIME_ZONE);
+            let language = match requested_language() {
+                Ok(l) => l,
+                Err(e) => return Response::error(format!("Bad Request: {}", e), 400),
+            };
+
+            let now = datetime::Timestamp::now();
+            if datetime::utc_offset(time_zone, now).is_none() {
+                return Response::error("Bad Request: unknown 'tz' time zone", 400);
             }
-            let target_year = year_param.unwrap();
+            let offset = |at| datetime::utc_offset(time_zone, at).unwrap_or(0);
 
-            let target_season = match season_param {
-                Some("all") | None | Some("") => None,
-                Some(s) => Some(s),
+            let from = match query.get("from") {
+                Some(v) => match schedule::parse_bound(v, false, offset) {
+                    Some(t) => t,
+                    None => return Response::error("Bad Request: invalid 'from' date", 400),
+                },
+                None => now,
+            };
+            let to = match query.get("to") {
+                Some(v) => match schedule::parse_bound(v, true, offset) {
+                    Some(t) => t,
+                    None => return Response::error("Bad Request: invalid 'to' date", 400),
+                },
+                None => from.add_secs(config::SCHEDULE_DEFAULT_DAYS * 86400),
             };
+            if to < from || to.as_secs() - from.as_secs() > config::SCHEDULE_MAX_DAYS * 86400 {
+                return Response::error(
+                    format!(
+                        "Bad Request: window must be between 0 and {} days",
+                        config::SCHEDULE_MAX_DAYS
+                    ),
+                    400,
+                );
+            }
 
-            let items = fetch_items_for_season(target_year, target_season).await?;
+            // Long-running shows started in an earlier file, so include the previous year.
+            // Future years only have Jikan data, whose broadcasts are not ISO intervals.
+            let current_year = js_sys::Date::new_0().get_full_year() as i32;
+            let first_year = from.date().0 as i32 - 1;
+            let last_year = (to.date().0 as i32).min(current_year);
+            let tasks = (first_year..=last_year).map(|year| fetch_items_for_season(year, None));
 
-            Response::from_json(&items)?.add_cors(&env)
+            // Jikan's broadcasts are not ISO intervals, so a partial season
+            // makes no difference to the schedule
+            let mut items = Vec::new();
+            for result in futures::future::join_all(tasks).await {
+                items.extend(result?.items);
+            }
+            // Shows that premiered before that and are still airing, such as
+            // One Piece, come from the search index once it's built
+            if let Ok(kv) = env.kv(config::SEARCH_INDEX_BINDING) {
+                let ongoing = search::index::ongoing(&kv)
+                    .await
+                    .unwrap_or_else(|e| {
+                        console_log!("Ongoing shows lookup failed {:?}", e);
+                        None
+                    })
+                    .unwrap_or_default();
+                items.extend(
+                    ongoing
+                        .into_iter()
+                        .filter(|doc| doc.year < first_year)
+                        .map(|doc| {
+                            let mut item = doc.item;
+                            resolve_item(&mut item, now);
+                            item
+                        }),
+                );
+            }
+            locale::localize(&mut items, &language);
+
+            let days = schedule::group_airings(&items, from, to, offset);
+
+            Response::from_json(&schedule::ScheduleResponse {
+                time_zone,
+                from,
+                to,
+                days,
+            })?
+            .add_cors(&env)?
+            .add_header("Content-Language", language.tag())?
+            .add_header(
+                "Cache-Control",
+                &format!("public, max-age={}", config::CACHE_TTL_SCHEDULE),
+            )
+        }
+        (Method::Get, "/api/search") => {
+            let Some(q) = query.get("q").map(|q| q.trim()).filter(|q| !q.is_empty()) else {
+                return Response::error("Bad Request: 'q' parameter is required", 400);
+            };
+            let limit = query
+                .get("limit")
+                .and_then(|l| l.parse::<usize>().ok())
+                .unwrap_or(config::SEARCH_DEFAULT_LIMIT)
+                .min(config::SEARCH_MAX_LIMIT);
+
+            let year = query.get("year").and_then(|y| y.parse::<i32>().ok());
+            let language = match requested_language() {
+                Ok(l) => l,
+                Err(e) => return Response::error(format!("Bad Request: {}", e), 400),
+            };
+
+            let indexed = match env.kv(config::SEARCH_INDEX_BINDING) {
+                Ok(kv) => search::index::candidates(&kv, q).await?,
+                Err(_) => None,
+            };
+            let mut partial = false;
+            let mut candidates: Vec<(i32, u32, Item)> = match indexed {
+                Some(docs) => docs
+                    .into_iter()
+                    .filter(|doc| year.is_none_or(|y| doc.year == y))
+                    .map(|doc| (doc.year, doc.month, doc.item))
+                    .collect(),
+                // Until the scheduled handler has indexed every year, read bangumi-data
+                // directly. Every year at once would be about a thousand subrequests,
+                // so only a given year is searched.
+                None => {
+                    let Some(year) = year else {
+                        return Response::error(
+                            "Service Unavailable: the search index is not built yet, so 'year' is required",
+                            503,
+                        );
+                    };
+                    let tasks = (1..=12).map(|month| fetch_bangumi_month(year, month));
+
+                    let mut candidates = Vec::new();
+                    for (month, result) in (1..=12).zip(futures::future::join_all(tasks).await) {
+                        match result {
+                            Ok(items) => {
+                                candidates.extend(items.into_iter().map(|item| (year, month, item)))
+                            }
+                            // Search the rest of the year, but don't cache the result
+                            Err(e) => {
+                                console_log!("Skipping {}-{:02} in search: {:?}", year, month, e);
+                                partial = true;
+                            }
+                        }
+                    }
+                    candidates
+                }
+            };
+            for (_, _, item) in &mut candidates {
+                item.resolve_schedules();
+                item.display_title = Some(item.title_in(&language).to_string());
+            }
+
+            let hits = search::search(
+                q,
+                candidates
+                    .iter()
+                    .map(|(year, month, item)| (*year, *month, item)),
+                limit,
+            );
+
+            let response = Response::from_json(&hits)?
+                .add_cors(&env)?
+                .add_header("Content-Language", language.tag())?;
+            if partial {
+                response.add_header("Cache-Control", "no-store")
+            } else {
+                Ok(response)
+            }
         }
         (Method::Get, "/api/metadata") => {
             let tmdb_id = query.get("tmdb_id").map(|s| s.as_str());
             let mal_id = query.get("mal_id").map(|s| s.as_str());
+            let kitsu_id = query.get("kitsu_id").map(|s| s.as_str());
+            let bangumi_id = query.get("bangumi_id").map(|s| s.as_str());
+            let anidb_id = query.get("anidb_id").map(|s| s.as_str());
             let anilist_id = query.get("anilist_id").map(|s| s.as_str());
-            let title = query.get("title").map(|s| s.as_str());
-            let year = query
-                .get("begin")
-                .and_then(|d| d.get(0..4))
-                .and_then(|y| y.parse::<i32>().ok());
+            let match_query = match parse_match_query(&url, &query) {
+                Ok(q) => q,
+                Err(e) => return Response::error(format!("Bad Request: {}", e), 400),
+            };
+
+            let broadcast = query.get("broadcast").and_then(|b| b.parse().ok());
+            let language = match requested_language() {
+                Ok(l) => l,
+                Err(e) => return Response::error(format!("Bad Request: {}", e), 400),
+            };
+            let providers = match parse_providers(&query, &env) {
+                Ok(p) => p,
+                Err(e) => return Response::error(format!("Bad Request: {}", e), 400),
+            };
+
+            // Overrides are looked up by site ID, then by title and premiere date
+            let mut targets: Vec<overrides::OverrideTarget> = [
+                ("bangumi", "bangumi_id"),
+                ("tmdb", "tmdb_id"),
+                ("mal", "mal_id"),
+                ("kitsu", "kitsu_id"),
+                ("anidb", "anidb_id"),
+                ("anilist", "anilist_id"),
+            ]
+            .into_iter()
+            .filter_map(|(site, param)| {
+                Some(overrides::OverrideTarget::Site {
+                    site: site.to_string(),
+                    id: query.get(param).filter(|id| !id.is_empty())?.clone(),
+                })
+            })
+            .collect();
+            if let (Some(title), Some(begin)) = (query.get("title"), query.get("begin")) {
+                targets.push(overrides::OverrideTarget::Title {
+                    title: title.clone(),
+                    begin: begin.clone(),
+                });
+            }
 
             let args = provider::MetadataArgs {
                 tmdb_id,
                 mal_id,
+                kitsu_id,
+                bangumi_id,
+                anidb_id,
                 anilist_id,
-                title,
-                year,
+                targets,
+                query: match_query,
+                broadcast,
+                language,
+                providers,
+                merge: query.get("merge").is_some_and(|m| m == "true" || m == "1"),
             };
 
             provider::get_metadata(args, &env).await
         }
+        (Method::Get, "/api/ids") => {
+            let ids: Vec<(&str, &str)> = idmap::SITES
+                .iter()
+                .filter_map(|&site| {
+                    let id = query.get(format!("{}_id", site).as_str())?;
+                    Some((site, id.as_str())).filter(|(_, id)| !id.trim().is_empty())
+                })
+                .collect();
+            if ids.is_empty() {
+                return Response::error(
+                    "Bad Request: one of mal_id, anilist_id, anidb_id, kitsu_id, tmdb_id or tvdb_id is required",
+                    400,
+                );
+            }
+            let Ok(kv) = env.kv(config::ID_MAP_BINDING) else {
+                return Response::error("ID mapping is not configured", 503);
+            };
+
+            match idmap::lookup(&kv, &ids).await? {
+                Some(mapping) => Response::from_json(&mapping)?.add_cors(&env)?.add_header(
+                    "Cache-Control",
+                    &format!("public, max-age={}", config::CACHE_TTL_API),
+                ),
+                None => Response::error("Not Found", 404),
+            }
+        }
+        (Method::Get, "/api/metadata/credits") => {
+            let ids: Vec<_> = provider::priority::ProviderKind::ALL
+                .iter()
+                .filter_map(|&kind| {
+                    let id = query.get(format!("{}_id", kind.name()).as_str())?;
+                    Some((kind, id.as_str())).filter(|(_, id)| !id.trim().is_empty())
+                })
+                .collect();
+            if ids.is_empty() {
+                return Response::error(
+                    "Bad Request: one of tmdb_id, mal_id, kitsu_id, bangumi_id, anidb_id or anilist_id is required",
+                    400,
+                );
+            }
+            let page = query
+                .get("page")
+                .and_then(|p| p.parse::<usize>().ok())
+                .unwrap_or(1)
+                .max(1);
+            let per_page = query
+                .get("per_page")
+                .and_then(|p| p.parse::<usize>().ok())
+                .unwrap_or(config::CREDITS_PER_PAGE_DEFAULT)
+                .clamp(1, config::CREDITS_PER_PAGE_MAX);
+            let language = match requested_language() {
+                Ok(l) => l,
+                Err(e) => return Response::error(format!("Bad Request: {}", e), 400),
+            };
+            let providers = match parse_providers(&query, &env) {
+                Ok(p) => p,
+                Err(e) => return Response::error(format!("Bad Request: {}", e), 400),
+            };
+
+            let credits =
+                provider::get_credits(&ids, &providers, language.clone(), &env, page, per_page)
+                    .await?;
+            Response::from_json(&credits)?
+                .add_cors(&env)?
+                .add_header("Content-Language", language.tag())
+        }
+        (Method::Get, "/api/relations") => {
+            use provider::priority::ProviderKind;
+            let Some((kind, id)) = [
+                (ProviderKind::Anilist, "anilist_id"),
+                (ProviderKind::Mal, "mal_id"),
+                (ProviderKind::Tmdb, "tmdb_id"),
+            ]
+            .into_iter()
+            .find_map(|(kind, param)| {
+                let id = query.get(param)?;
+                Some((kind, id.as_str())).filter(|(_, id)| !id.trim().is_empty())
+            }) else {
+                return Response::error(
+                    "Bad Request: one of anilist_id, mal_id or tmdb_id is required",
+                    400,
+                );
+            };
+            if kind == ProviderKind::Tmdb
+                && !provider::tmdb::TmdbProvider::new(&env, Default::default()).has_read_token()
+            {
+                return Response::error(
+                    "Bad Request: TMDb relations need TMDB_READ_TOKEN, which is not set; use anilist_id or mal_id",
+                    400,
+                );
+            }
+            let language = match requested_language() {
+                Ok(l) => l,
+                Err(e) => return Response::error(format!("Bad Request: {}", e), 400),
+            };
+
+            let graph = provider::get_relations(kind, id, language.clone(), &env).await?;
+            Response::from_json(&graph)?
+                .add_cors(&env)?
+                .add_header("Content-Language", language.tag())
+        }
+        (Method::Get, "/api/metadata/candidates") => {
+            let match_query = match parse_match_query(&url, &query) {
+                Ok(q) if !q.titles.is_empty() => q,
+                Ok(_) => return Response::error("Bad Request: 'title' parameter is required", 400),
+                Err(e) => return Response::error(format!("Bad Request: {}", e), 400),
+            };
+            let limit = query
+                .get("limit")
+                .and_then(|l| l.parse::<usize>().ok())
+                .unwrap_or(config::METADATA_CANDIDATES_DEFAULT_LIMIT)
+                .min(config::METADATA_CANDIDATES_MAX_LIMIT);
+            let language = match requested_language() {
+                Ok(l) => l,
+                Err(e) => return Response::error(format!("Bad Request: {}", e), 400),
+            };
+            let providers = match parse_providers(&query, &env) {
+                Ok(p) => p,
+                Err(e) => return Response::error(format!("Bad Request: {}", e), 400),
+            };
+
+            let candidates =
+                provider::get_candidates(&match_query, language.clone(), &providers, &env, limit)
+                    .await;
+
+            Response::from_json(&candidates)?
+                .add_cors(&env)?
+                .add_header("Content-Language", language.tag())
+        }
+        // Preflight for browsers sending PUT and DELETE with a bearer token
+        (Method::Options, "/api/overrides") => Response::empty()?
+            .with_status(204)
+            .add_cors(&env)?
+            .add_header("Access-Control-Allow-Methods", "PUT, DELETE, OPTIONS")?
+            .add_header(
+                "Access-Control-Allow-Headers",
+                "Authorization, Content-Type",
+            )?
+            .add_header("Access-Control-Max-Age", "86400"),
+        (method @ (Method::Put | Method::Delete), "/api/overrides") => {
+            let Ok(token) = env
+                .secret(config::OVERRIDES_TOKEN)
+                .map(|s| s.to_string())
+                .or_else(|_| env.var(config::OVERRIDES_TOKEN).map(|s| s.to_string()))
+            else {
+                return Response::error("Overrides are not configured", 503);
+            };
+            let authorization = req.headers().get("Authorization")?;
+            if !overrides::authorized(authorization.as_deref(), &token) {
+                return Response::error("Unauthorized", 401);
+            }
+            let Ok(kv) = env.kv(config::OVERRIDES_BINDING) else {
+                return Response::error("Overrides are not configured", 503);
+            };
+
+            let request: overrides::OverrideRequest = match req.json().await {
+                Ok(r) => r,
+                Err(e) => return Response::error(format!("Bad Request: {}", e), 400),
+            };
+            let written_at = js_sys::Date::now() as u64;
+            if method == Method::Delete {
+                overrides::delete(&kv, &request.target, written_at).await?;
+                return Response::empty()?.with_status(204).add_cors(&env);
+            }
+            let (target, pin) = match request.into_parts() {
+                Ok(parts) => parts,
+                Err(e) => return Response::error(format!("Bad Request: {}", e), 400),
+            };
+            overrides::put(&kv, &target, &pin, written_at).await?;
+            Response::from_json(&pin)?.add_cors(&env)
+        }
         _ => Response::error("Not Found", 404),
     }
 }
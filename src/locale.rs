@@ -0,0 +1,144 @@
+use crate::model::{Item, Language};
+
+impl Language {
+    /// Maps a BCP 47 tag onto the languages bangumi-data translates into.
+    /// Bare `zh` and region-only Chinese tags follow the usual script split.
+    pub fn from_tag(tag: &str) -> Option<Self> {
+        let tag = tag.trim().to_ascii_lowercase().replace('_', "-");
+        let mut parts = tag.split('-');
+        match parts.next()? {
+            "ja" => Some(Language::Ja),
+            "en" => Some(Language::En),
+            "zh" => match parts.next() {
+                Some("hant" | "tw" | "hk" | "mo") => Some(Language::ZhHant),
+                _ => Some(Language::ZhHans),
+            },
+            _ => None,
+        }
+    }
+
+    /// The tag used in responses and cache keys.
+    pub fn tag(&self) -> &'static str {
+        match self {
+            Language::Ja => "ja",
+            Language::En => "en",
+            Language::ZhHans => "zh-Hans",
+            Language::ZhHant => "zh-Hant",
+        }
+    }
+
+    /// The locale passed to TMDb's `language` parameter.
+    pub fn tmdb_locale(&self) -> &'static str {
+        match self {
+            Language::Ja => "ja-JP",
+            Language::En => "en-US",
+            Language::ZhHans => "zh-CN",
+            Language::ZhHant => "zh-TW",
+        }
+    }
+}
+
+/// Normalizes a provider's locale (`ja-JP`, `zh-TW`) to the tags used in
+/// responses: a supported language's [`Language::tag`], otherwise the bare
+/// lowercase language subtag.
+pub fn language_tag(locale: &str) -> String {
+    match Language::from_tag(locale) {
+        Some(language) => language.tag().to_string(),
+        None => locale
+            .split(['-', '_'])
+            .next()
+            .unwrap_or_default()
+            .trim()
+            .to_ascii_lowercase(),
+    }
+}
+
+/// Picks the best supported language from an `Accept-Language` header,
+/// honouring q-values and falling back through the listed order.
+pub fn negotiate(accept_language: &str) -> Option<Language> {
+    let mut ranges: Vec<(f32, usize, &str)> = accept_language
+        .split(',')
+        .enumerate()
+        .filter_map(|(i, range)| {
+            let mut parts = range.split(';');
+            let tag = parts.next()?.trim();
+            let q = parts
+                .filter_map(|p| p.trim().strip_prefix("q="))
+                .find_map(|q| q.parse::<f32>().ok())
+                .unwrap_or(1.0);
+            (!tag.is_empty() && q > 0.0).then_some((q, i, tag))
+        })
+        .collect();
+    ranges.sort_by(|a, b| b.0.total_cmp(&a.0).then(a.1.cmp(&b.1)));
+    ranges
+        .into_iter()
+        .find_map(|(_, _, tag)| Language::from_tag(tag))
+}
+
+/// Resolves the response language from an explicit query parameter, then
+/// `Accept-Language`, then Japanese. An unsupported parameter is an error.
+pub fn resolve(param: Option<&str>, accept_language: Option<&str>) -> Result<Language, String> {
+    match param.map(str::trim).filter(|p| !p.is_empty()) {
+        Some(p) => Language::from_tag(p).ok_or_else(|| format!("unsupported language '{}'", p)),
+        None => Ok(accept_language.and_then(negotiate).unwrap_or_default()),
+    }
+}
+
+/// Sets `display_title` on each item to its title in `language`.
+pub fn localize(items: &mut [Item], language: &Language) {
+    for item in items {
+        item.display_title = Some(item.title_in(language).to_string());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_tag() {
+        let cases = vec![
+            ("ja", Some(Language::Ja)),
+            ("ja-JP", Some(Language::Ja)),
+            ("EN-gb", Some(Language::En)),
+            ("zh", Some(Language::ZhHans)),
+            ("zh-CN", Some(Language::ZhHans)),
+            ("zh-Hans-CN", Some(Language::ZhHans)),
+            ("zh-TW", Some(Language::ZhHant)),
+            ("zh_HK", Some(Language::ZhHant)),
+            ("zh-Hant", Some(Language::ZhHant)),
+            ("ko", None),
+            ("", None),
+        ];
+        for (tag, expected) in cases {
+            assert_eq!(Language::from_tag(tag), expected, "Failed for tag: {}", tag);
+        }
+    }
+
+    #[test]
+    fn test_language_tag() {
+        assert_eq!(language_tag("ja-JP"), "ja");
+        assert_eq!(language_tag("en-US"), "en");
+        assert_eq!(language_tag("zh-CN"), "zh-Hans");
+        assert_eq!(language_tag("zh-TW"), "zh-Hant");
+        assert_eq!(language_tag("ko-KR"), "ko");
+        assert_eq!(language_tag("FR"), "fr");
+    }
+
+    #[test]
+    fn test_negotiate() {
+        assert_eq!(negotiate("zh-TW,zh;q=0.9,en;q=0.8"), Some(Language::ZhHant));
+        assert_eq!(negotiate("ko-KR, en;q=0.5, ja;q=0.7"), Some(Language::Ja));
+        assert_eq!(negotiate("fr, *;q=0.1"), None);
+        assert_eq!(negotiate("en;q=0, ja"), Some(Language::Ja));
+        assert_eq!(negotiate(""), None);
+    }
+
+    #[test]
+    fn test_resolve() {
+        assert_eq!(resolve(Some("en"), Some("zh-CN")), Ok(Language::En));
+        assert_eq!(resolve(None, Some("zh-CN")), Ok(Language::ZhHans));
+        assert_eq!(resolve(Some(""), None), Ok(Language::Ja));
+        assert!(resolve(Some("ko"), None).is_err());
+    }
+}
@@ -1,5 +1,7 @@
+use crate::datetime::Timestamp;
 use serde_derive::Deserialize;
 use serde_derive::Serialize;
+use std::collections::BTreeMap;
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "lowercase")]
@@ -69,6 +71,43 @@ pub struct Item {
     pub end: Option<String>,
     pub comment: Option<String>,
     pub sites: Vec<Site>,
+    #[serde(default)]
+    pub broadcast_schedule: Option<BroadcastSchedule>,
+    #[serde(default)]
+    pub next_episode: Option<NextEpisode>,
+    /// `title_in` the language the response was localized for.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub display_title: Option<String>,
+}
+
+impl Item {
+    /// Parses the raw `broadcast` strings of the item and its sites into
+    /// `broadcast_schedule`.
+    pub fn resolve_schedules(&mut self) {
+        self.broadcast_schedule = self.broadcast.as_deref().and_then(|b| b.parse().ok());
+        for site in &mut self.sites {
+            site.broadcast_schedule = site.broadcast.as_deref().and_then(|b| b.parse().ok());
+        }
+    }
+
+    /// The title in `lang`: the original title when it is already in that
+    /// language, else the first translation, else the original title.
+    pub fn title_in(&self, lang: &Language) -> &str {
+        if self.lang == *lang {
+            return &self.title;
+        }
+        let translate = &self.title_translate;
+        let translations = match lang {
+            Language::Ja => &translate.ja,
+            Language::En => &translate.en,
+            Language::ZhHans => &translate.zh_hans,
+            Language::ZhHant => &translate.zh_hant,
+        };
+        translations
+            .as_ref()
+            .and_then(|t| t.first())
+            .map_or(&self.title, |t| t)
+    }
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -97,6 +136,163 @@ pub struct Site {
     pub comment: Option<String>,
     pub url: Option<String>,
     pub regions: Option<Vec<String>>,
+    #[serde(default)]
+    pub broadcast_schedule: Option<BroadcastSchedule>,
+}
+
+/// A parsed ISO 8601 repeating interval such as `R/2020-01-01T13:00:00Z/P7D`.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BroadcastSchedule {
+    pub start: Timestamp,
+    pub period: BroadcastPeriod,
+    /// Total number of airings when the interval is bounded (`R12/...`).
+    pub repetitions: Option<u32>,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BroadcastPeriod {
+    pub months: u32,
+    pub days: u32,
+}
+
+impl BroadcastSchedule {
+    /// Returns the `n`th airing, counting the start as 0.
+    pub fn occurrence(&self, n: u32) -> Timestamp {
+        self.start
+            .add_months(self.period.months as i64 * n as i64)
+            .add_secs(self.period.days as i64 * n as i64 * 86400)
+    }
+
+    /// Returns the index and time of the first airing at or after `at`.
+    pub fn next_after(&self, at: Timestamp) -> Option<(u32, Timestamp)> {
+        let limit = self.repetitions.unwrap_or(u32::MAX);
+        if self.period == BroadcastPeriod::default() {
+            return (limit > 0 && self.start >= at).then_some((0, self.start));
+        }
+
+        let mut n = self.skip_to(at).min(limit);
+        while n < limit {
+            let airs_at = self.occurrence(n);
+            if airs_at >= at {
+                return Some((n, airs_at));
+            }
+            n += 1;
+        }
+        None
+    }
+
+    // Index of an airing at or before `at`, using the longest possible period so none are skipped
+    fn skip_to(&self, at: Timestamp) -> u32 {
+        let max_period = (self.period.months as i64 * 31 + self.period.days as i64) * 86400;
+        let skip = (at.as_secs() - self.start.as_secs()).max(0) / max_period;
+        skip.min(u32::MAX as i64) as u32
+    }
+
+    /// Returns every airing within `[from, to]`, honouring `repetitions`.
+    pub fn occurrences(&self, from: Timestamp, to: Timestamp) -> Vec<Timestamp> {
+        let limit = self.repetitions.unwrap_or(u32::MAX);
+        if self.period == BroadcastPeriod::default() {
+            return if limit > 0 && (from..=to).contains(&self.start) {
+                vec![self.start]
+            } else {
+                Vec::new()
+            };
+        }
+
+        let mut n = self.skip_to(from).min(limit);
+
+        let mut result = Vec::new();
+        while n < limit {
+            let at = self.occurrence(n);
+            if at > to {
+                break;
+            }
+            if at >= from {
+                result.push(at);
+            }
+            n += 1;
+        }
+        result
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NextEpisode {
+    pub number: i32,
+    pub airs_at: Timestamp,
+}
+
+impl std::str::FromStr for BroadcastSchedule {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let err = || format!("invalid broadcast: {}", s);
+        let mut parts = s.split('/');
+
+        let repeat = parts
+            .next()
+            .and_then(|p| p.strip_prefix('R'))
+            .ok_or_else(err)?;
+        let repetitions = if repeat.is_empty() {
+            None
+        } else {
+            Some(repeat.parse::<u32>().map_err(|_| err())?)
+        };
+
+        let start = parts.next().and_then(Timestamp::parse).ok_or_else(err)?;
+
+        let mut rest = parts
+            .next()
+            .and_then(|p| p.strip_prefix('P'))
+            .filter(|p| !p.is_empty())
+            .ok_or_else(err)?;
+        if parts.next().is_some() {
+            return Err(err());
+        }
+
+        // Only date designators appear in bangumi-data, so time parts are rejected.
+        let mut period = BroadcastPeriod::default();
+        while !rest.is_empty() {
+            let split = rest.find(|c: char| !c.is_ascii_digit()).ok_or_else(err)?;
+            let amount: u32 = rest[..split].parse().map_err(|_| err())?;
+            // Checked, since the string may come from a query parameter
+            let (field, amount) = match rest[split..].chars().next() {
+                Some('Y') => (&mut period.months, amount.checked_mul(12)),
+                Some('M') => (&mut period.months, Some(amount)),
+                Some('W') => (&mut period.days, amount.checked_mul(7)),
+                Some('D') => (&mut period.days, Some(amount)),
+                _ => return Err(err()),
+            };
+            *field = amount.and_then(|a| field.checked_add(a)).ok_or_else(err)?;
+            rest = &rest[split + 1..];
+        }
+
+        Ok(Self {
+            start,
+            period,
+            repetitions,
+        })
+    }
+}
+
+impl std::fmt::Display for BroadcastSchedule {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "R")?;
+        if let Some(n) = self.repetitions {
+            write!(f, "{}", n)?;
+        }
+        write!(f, "/{}/P", self.start)?;
+        if self.period.months > 0 {
+            write!(f, "{}M", self.period.months)?;
+        }
+        if self.period.days > 0 || self.period.months == 0 {
+            write!(f, "{}D", self.period.days)?;
+        }
+        Ok(())
+    }
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -109,6 +305,10 @@ pub struct UnifiedMetadata {
     pub episodes: Option<i32>,
     pub genres: Vec<String>,
     pub description: Option<String>,
+    /// Language `description` is written in, as a tag like `ja`, `en` or
+    /// `zh-Hant`, which may differ from the one requested when the provider
+    /// had to fall back.
+    pub description_language: Option<String>,
     pub studios: Vec<String>,
     pub characters: Vec<UniversalCharacter>,
     pub staff: Vec<UniversalStaff>,
@@ -118,6 +318,24 @@ pub struct UnifiedMetadata {
     pub current_season: Option<i32>,
     pub runtime: Option<i32>,
     pub content_rating: Option<String>,
+    pub next_episode: Option<NextEpisode>,
+    /// Score of the search result this was fetched for, between 0 and 1;
+    /// `None` when looked up by ID.
+    pub match_confidence: Option<f64>,
+    /// IDs of the show on other sites, keyed by provider name (`mal`,
+    /// `anilist`, ...).
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub external_ids: BTreeMap<String, String>,
+    /// The show's official, social and streaming pages.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub external_links: Vec<ExternalLink>,
+    /// Episodes on legal streaming sites.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub streaming_episodes: Vec<StreamingEpisode>,
+    /// Provider each field came from, keyed by field name, when several
+    /// providers were merged.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub sources: BTreeMap<String, String>,
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -126,6 +344,43 @@ pub struct UniversalTitle {
     pub romaji: Option<String>,
     pub english: Option<String>,
     pub native: Option<String>,
+    /// Season name, for providers that title seasons apart from the show.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub season: Option<String>,
+    /// Synonyms and alternative titles, without the ones above.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub alternatives: Vec<AlternativeTitle>,
+}
+
+impl UniversalTitle {
+    /// Adds an alternative title unless it is empty or already present.
+    pub fn add_alternative(&mut self, alternative: AlternativeTitle) {
+        let title = alternative.title.trim().to_string();
+        let known = [&self.romaji, &self.english, &self.native]
+            .into_iter()
+            .flatten()
+            .chain(self.alternatives.iter().map(|a| &a.title))
+            .any(|t| *t == title);
+        if title.is_empty() || known {
+            return;
+        }
+        self.alternatives.push(AlternativeTitle {
+            title,
+            ..alternative
+        });
+    }
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AlternativeTitle {
+    pub title: String,
+    /// BCP 47 language tag, when the provider gives one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub language: Option<String>,
+    /// ISO 3166-1 country the title is used in, which TMDb tags titles with.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub region: Option<String>,
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -141,6 +396,13 @@ pub struct UniversalCharacter {
     pub name: String,
     pub voice_actor: Option<String>,
     pub role: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub image: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub voice_actor_image: Option<String>,
+    /// Language the voice actor performs in, such as `Japanese`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub voice_actor_language: Option<String>,
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -151,6 +413,35 @@ pub struct UniversalEpisode {
     pub air_date: Option<String>,
     pub overview: Option<String>,
     pub runtime: Option<i32>,
+    /// Still image for the episode, where the provider has one.
+    pub thumbnail: Option<String>,
+    /// The episode's title in each language the provider lists.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub titles: Vec<AlternativeTitle>,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExternalLink {
+    pub site: String,
+    pub url: String,
+    /// `STREAMING`, `INFO` or `SOCIAL`.
+    #[serde(rename = "type", default, skip_serializing_if = "Option::is_none")]
+    pub link_type: Option<String>,
+    /// Language of the site, for streaming sites serving one region.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub language: Option<String>,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StreamingEpisode {
+    /// Episode number, when the title gives one.
+    pub number: Option<i32>,
+    pub title: Option<String>,
+    pub thumbnail: Option<String>,
+    pub url: String,
+    pub site: String,
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -159,6 +450,8 @@ pub struct UniversalStaff {
     pub name: String,
     pub role: String,
     pub department: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub image: Option<String>,
 }
 
 #[cfg(test)]
@@ -199,5 +492,157 @@ mod tests {
         let item: Item = serde_json::from_str(json).unwrap();
         assert_eq!(item.type_field, ItemType::Tv);
         assert_eq!(item.lang, Language::Ja);
+        assert_eq!(item.title_in(&Language::Ja), "海賊王");
+        assert_eq!(item.title_in(&Language::ZhHans), "航海王");
+        assert_eq!(item.title_in(&Language::ZhHant), "海賊王");
+    }
+
+    #[test]
+    fn test_add_alternative() {
+        let mut title = UniversalTitle {
+            native: Some("葬送のフリーレン".into()),
+            ..Default::default()
+        };
+        let alt = |t: &str, lang: Option<&str>| AlternativeTitle {
+            title: t.into(),
+            language: lang.map(Into::into),
+            ..Default::default()
+        };
+
+        title.add_alternative(alt(" Frieren ", Some("en")));
+        title.add_alternative(alt("Frieren", None));
+        title.add_alternative(alt("葬送のフリーレン", Some("ja")));
+        title.add_alternative(alt("", None));
+
+        assert_eq!(title.alternatives, vec![alt("Frieren", Some("en"))]);
+    }
+
+    #[test]
+    fn test_parse_broadcast_schedule() {
+        let schedule: BroadcastSchedule = "R/2020-01-01T13:00:00Z/P7D".parse().unwrap();
+        assert_eq!(schedule.start.to_string(), "2020-01-01T13:00:00Z");
+        assert_eq!(schedule.period, BroadcastPeriod { months: 0, days: 7 });
+        assert_eq!(schedule.repetitions, None);
+        assert_eq!(schedule.to_string(), "R/2020-01-01T13:00:00Z/P7D");
+
+        let monthly: BroadcastSchedule = "R12/2024-01-31T15:00:00.000Z/P1M".parse().unwrap();
+        assert_eq!(monthly.repetitions, Some(12));
+        assert_eq!(monthly.period, BroadcastPeriod { months: 1, days: 0 });
+
+        assert_eq!(schedule.occurrence(2).to_string(), "2020-01-15T13:00:00Z");
+        assert_eq!(monthly.occurrence(1).to_string(), "2024-02-29T15:00:00Z");
+
+        let once: BroadcastSchedule = "R/2024-01-06T15:00:00Z/P0D".parse().unwrap();
+        assert_eq!(once.period, BroadcastPeriod::default());
+        assert_eq!(once.to_string(), "R/2024-01-06T15:00:00Z/P0D");
+
+        for invalid in [
+            "",
+            "Saturdays at 23:00 (JST)",
+            "R/2024-01-06T15:00:00Z",
+            "R/2024-01-06T15:00:00Z/P",
+            "R/2024-01-06T15:00:00Z/PT1H",
+            "Rx/2024-01-06T15:00:00Z/P7D",
+            "R/2024-01-06T15:00:00Z/P999999999Y",
+            "R/2024-01-06T15:00:00Z/P4294967295D1D",
+        ] {
+            assert!(
+                invalid.parse::<BroadcastSchedule>().is_err(),
+                "Expected error for: {}",
+                invalid
+            );
+        }
+    }
+
+    #[test]
+    fn test_broadcast_occurrences() {
+        let at = |s: &str| Timestamp::parse(s).unwrap();
+        let weekly: BroadcastSchedule = "R/2020-01-01T13:00:00Z/P7D".parse().unwrap();
+
+        let found = weekly.occurrences(at("2024-01-01"), at("2024-01-15"));
+        let found: Vec<_> = found.iter().map(|t| t.to_string()).collect();
+        assert_eq!(found, vec!["2024-01-03T13:00:00Z", "2024-01-10T13:00:00Z"]);
+        assert!(
+            weekly
+                .occurrences(at("2019-01-01"), at("2019-12-31"))
+                .is_empty()
+        );
+
+        let bounded: BroadcastSchedule = "R3/2024-01-06T15:00:00Z/P7D".parse().unwrap();
+        assert_eq!(
+            bounded
+                .occurrences(at("2024-01-01"), at("2024-12-31"))
+                .len(),
+            3
+        );
+        assert!(
+            bounded
+                .occurrences(at("2024-01-21"), at("2024-12-31"))
+                .is_empty()
+        );
+
+        let once: BroadcastSchedule = "R/2024-01-06T15:00:00Z/P0D".parse().unwrap();
+        assert_eq!(
+            once.occurrences(at("2024-01-01"), at("2024-01-31")).len(),
+            1
+        );
+        assert!(
+            once.occurrences(at("2024-02-01"), at("2024-02-28"))
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn test_broadcast_next_after() {
+        let at = |s: &str| Timestamp::parse(s).unwrap();
+        let weekly: BroadcastSchedule = "R/2024-01-06T15:00:00Z/P7D".parse().unwrap();
+
+        let (index, next) = weekly.next_after(at("2024-01-20T15:00:01Z")).unwrap();
+        assert_eq!(index, 3);
+        assert_eq!(next.to_string(), "2024-01-27T15:00:00Z");
+        assert_eq!(weekly.next_after(at("2023-12-01")).unwrap().0, 0);
+
+        let bounded: BroadcastSchedule = "R2/2024-01-06T15:00:00Z/P7D".parse().unwrap();
+        assert!(bounded.next_after(at("2024-01-14")).is_none());
+
+        let once: BroadcastSchedule = "R/2024-01-06T15:00:00Z/P0D".parse().unwrap();
+        assert_eq!(once.next_after(at("2024-01-01")).unwrap().0, 0);
+        assert!(once.next_after(at("2024-01-07")).is_none());
+    }
+
+    #[test]
+    fn test_resolve_schedules() {
+        let json = r#"{
+            "title": "Test",
+            "titleTranslate": {},
+            "type": "tv",
+            "lang": "ja",
+            "officialSite": "",
+            "begin": "2024-01-06T15:00:00.000Z",
+            "broadcast": "R/2024-01-06T15:00:00.000Z/P7D",
+            "end": "",
+            "sites": [
+                { "site": "netflix", "broadcast": "R/2024-01-11T08:00:00.000Z/P7D" },
+                { "site": "bangumi", "id": "1" }
+            ]
+        }"#;
+        let mut item: Item = serde_json::from_str(json).unwrap();
+        assert_eq!(item.broadcast_schedule, None);
+
+        item.resolve_schedules();
+        let schedule = item.broadcast_schedule.unwrap();
+        assert_eq!(schedule.start.to_string(), "2024-01-06T15:00:00Z");
+        assert!(item.sites[0].broadcast_schedule.is_some());
+        assert!(item.sites[1].broadcast_schedule.is_none());
+
+        let value = serde_json::to_value(&item).unwrap();
+        assert_eq!(
+            value["broadcastSchedule"],
+            serde_json::json!({
+                "start": "2024-01-06T15:00:00Z",
+                "period": { "months": 0, "days": 7 },
+                "repetitions": null
+            })
+        );
     }
 }
@@ -0,0 +1,245 @@
+use crate::kv::KeyValueStore;
+use crate::search::normalize;
+use serde_derive::{Deserialize, Serialize};
+use worker::Result;
+
+// The time of the last change, so cached metadata responses can be keyed on
+// it. A timestamp needs no read first, so concurrent writes can't collide on
+// one revision the way an incremented counter read from KV could.
+const REVISION_KEY: &str = "revision";
+
+/// The item an override applies to: one of its bangumi-data site IDs, or
+/// its title and premiere date.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(untagged)]
+pub enum OverrideTarget {
+    Site { site: String, id: String },
+    Title { title: String, begin: String },
+}
+
+impl OverrideTarget {
+    fn key(&self) -> String {
+        match self {
+            Self::Site { site, id } => format!("site:{}:{}", site.trim().to_lowercase(), id.trim()),
+            // Only the date, so any time of day or zone suffix still matches
+            Self::Title { title, begin } => {
+                format!(
+                    "title:{}:{}",
+                    normalize(title),
+                    begin.get(0..10).unwrap_or(begin)
+                )
+            }
+        }
+    }
+}
+
+/// Provider IDs pinned to an item, used instead of the ones it carries.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Override {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tmdb_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mal_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub kitsu_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bangumi_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub anidb_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub anilist_id: Option<String>,
+}
+
+/// Body of `PUT /api/overrides` and `DELETE /api/overrides`.
+#[derive(Debug, Deserialize)]
+pub struct OverrideRequest {
+    #[serde(flatten)]
+    pub target: OverrideTarget,
+    #[serde(flatten)]
+    pub pin: Override,
+}
+
+impl OverrideRequest {
+    /// Validates the request, dropping blank IDs.
+    pub fn into_parts(self) -> std::result::Result<(OverrideTarget, Override), String> {
+        let blank = |s: &str| s.trim().is_empty();
+        let target_ok = match &self.target {
+            OverrideTarget::Site { site, id } => !blank(site) && !blank(id),
+            OverrideTarget::Title { title, begin } => !normalize(title).is_empty() && !blank(begin),
+        };
+        if !target_ok {
+            return Err("either 'site' and 'id' or 'title' and 'begin' are required".into());
+        }
+
+        let clean = |id: Option<String>| id.map(|s| s.trim().to_string()).filter(|s| !s.is_empty());
+        let pin = Override {
+            tmdb_id: clean(self.pin.tmdb_id),
+            mal_id: clean(self.pin.mal_id),
+            kitsu_id: clean(self.pin.kitsu_id),
+            bangumi_id: clean(self.pin.bangumi_id),
+            anidb_id: clean(self.pin.anidb_id),
+            anilist_id: clean(self.pin.anilist_id),
+        };
+        if pin == Override::default() {
+            return Err(
+                "at least one of 'tmdbId', 'malId', 'kitsuId', 'bangumiId', 'anidbId' or 'anilistId' is required".into(),
+            );
+        }
+        Ok((self.target, pin))
+    }
+}
+
+/// Checks an `Authorization: Bearer` header against the configured token,
+/// in constant time.
+pub fn authorized(header: Option<&str>, token: &str) -> bool {
+    let Some(given) = header.and_then(|h| h.strip_prefix("Bearer ")) else {
+        return false;
+    };
+    let (given, token) = (given.trim().as_bytes(), token.as_bytes());
+    !token.is_empty()
+        && given.len() == token.len()
+        && given.iter().zip(token).fold(0, |acc, (a, b)| acc | (a ^ b)) == 0
+}
+
+/// The first override found for any of `targets`, in order.
+pub async fn lookup(
+    store: &impl KeyValueStore,
+    targets: &[OverrideTarget],
+) -> Result<Option<Override>> {
+    for target in targets {
+        if let Some(text) = store.get_text(&target.key()).await? {
+            return Ok(Some(serde_json::from_str(&text)?));
+        }
+    }
+    Ok(None)
+}
+
+/// Stores `pin`, recording `written_at` (milliseconds since the epoch) as
+/// the revision.
+pub async fn put(
+    store: &impl KeyValueStore,
+    target: &OverrideTarget,
+    pin: &Override,
+    written_at: u64,
+) -> Result<()> {
+    store
+        .put_text(&target.key(), serde_json::to_string(pin)?)
+        .await?;
+    set_revision(store, written_at).await
+}
+
+pub async fn delete(
+    store: &impl KeyValueStore,
+    target: &OverrideTarget,
+    written_at: u64,
+) -> Result<()> {
+    store.delete(&target.key()).await?;
+    set_revision(store, written_at).await
+}
+
+pub async fn revision(store: &impl KeyValueStore) -> Result<Option<String>> {
+    store.get_text(REVISION_KEY).await
+}
+
+async fn set_revision(store: &impl KeyValueStore, written_at: u64) -> Result<()> {
+    store.put_text(REVISION_KEY, written_at.to_string()).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kv::MemoryStore;
+    use futures::executor::block_on;
+
+    fn request(json: serde_json::Value) -> OverrideRequest {
+        serde_json::from_value(json).unwrap()
+    }
+
+    #[test]
+    fn test_parse_request() {
+        let site = request(serde_json::json!({
+            "site": "bangumi", "id": "400602", "anilistId": "154587"
+        }));
+        assert_eq!(
+            site.target,
+            OverrideTarget::Site {
+                site: "bangumi".into(),
+                id: "400602".into()
+            }
+        );
+        let (_, pin) = site.into_parts().unwrap();
+        assert_eq!(pin.anilist_id.as_deref(), Some("154587"));
+
+        let title = request(serde_json::json!({
+            "title": "葬送のフリーレン", "begin": "2023-09-29T14:00:00.000Z", "tmdbId": "tv/209867"
+        }));
+        assert!(matches!(title.target, OverrideTarget::Title { .. }));
+        assert!(title.into_parts().is_ok());
+
+        let no_pin = request(serde_json::json!({ "site": "bangumi", "id": "1", "malId": " " }));
+        assert!(no_pin.into_parts().is_err());
+        let no_target = request(serde_json::json!({ "site": "", "id": "1", "malId": "1" }));
+        assert!(no_target.into_parts().is_err());
+    }
+
+    #[test]
+    fn test_authorized() {
+        assert!(authorized(Some("Bearer s3cret"), "s3cret"));
+        assert!(!authorized(Some("Bearer s3cre"), "s3cret"));
+        assert!(!authorized(Some("s3cret"), "s3cret"));
+        assert!(!authorized(None, "s3cret"));
+        assert!(!authorized(Some("Bearer "), ""));
+    }
+
+    #[test]
+    fn test_put_lookup_delete() {
+        let store = MemoryStore::default();
+        let site = OverrideTarget::Site {
+            site: "Bangumi".into(),
+            id: "400602".into(),
+        };
+        let title = OverrideTarget::Title {
+            title: "葬送のフリーレン".into(),
+            begin: "2023-09-29T14:00:00.000Z".into(),
+        };
+        let pin = Override {
+            anilist_id: Some("154587".into()),
+            ..Default::default()
+        };
+
+        block_on(put(&store, &title, &pin, 1_700_000_000_000)).unwrap();
+        assert_eq!(
+            block_on(revision(&store)).unwrap().as_deref(),
+            Some("1700000000000")
+        );
+
+        // Titles match after normalization and on the date alone
+        let lookup_title = OverrideTarget::Title {
+            title: "葬送の フリーレン".into(),
+            begin: "2023-09-29".into(),
+        };
+        let found = block_on(lookup(&store, &[site.clone(), lookup_title])).unwrap();
+        assert_eq!(found, Some(pin.clone()));
+
+        let site_pin = Override {
+            mal_id: Some("52991".into()),
+            ..Default::default()
+        };
+        block_on(put(&store, &site, &site_pin, 1_700_000_000_001)).unwrap();
+        let lowercase = OverrideTarget::Site {
+            site: "bangumi".into(),
+            id: "400602".into(),
+        };
+        // Earlier targets win
+        let found = block_on(lookup(&store, &[lowercase, title.clone()])).unwrap();
+        assert_eq!(found, Some(site_pin));
+
+        block_on(delete(&store, &site, 1_700_000_000_002)).unwrap();
+        assert_eq!(block_on(lookup(&store, &[site])).unwrap(), None);
+        assert_eq!(
+            block_on(revision(&store)).unwrap().as_deref(),
+            Some("1700000000002")
+        );
+    }
+}
@@ -1,70 +1,479 @@
+pub mod anidb;
 pub mod anilist;
+pub mod bangumi;
+pub mod credits;
 pub mod jikan;
+pub mod kitsu;
+pub mod merge;
+pub mod priority;
+pub mod relations;
+pub mod scoring;
 pub mod tmdb;
 
-use crate::{ResponseExt, model};
+use crate::datetime::Timestamp;
+use crate::{ResponseExt, config, idmap, model, overrides, schedule};
+use priority::ProviderKind;
+use serde_derive::Serialize;
 use worker::*;
 
 #[derive(Debug, Default)]
 pub struct MetadataArgs<'a> {
     pub tmdb_id: Option<&'a str>,
     pub mal_id: Option<&'a str>,
+    pub kitsu_id: Option<&'a str>,
+    pub bangumi_id: Option<&'a str>,
+    pub anidb_id: Option<&'a str>,
     pub anilist_id: Option<&'a str>,
-    pub title: Option<&'a str>,
-    pub year: Option<i32>,
+    /// Keys a pinned override may be stored under, most specific first.
+    pub targets: Vec<overrides::OverrideTarget>,
+    pub query: scoring::MatchQuery,
+    pub broadcast: Option<model::BroadcastSchedule>,
+    pub language: model::Language,
+    /// Providers to try, in order. See [`enabled_providers`].
+    pub providers: Vec<ProviderKind>,
+    /// Query every provider and combine their results field by field.
+    pub merge: bool,
 }
 
 pub trait MetadataProvider {
+    /// Searches by title, in the provider's own order.
+    async fn search(&self, query: &scoring::MatchQuery) -> Result<Vec<scoring::Candidate>>;
+
+    async fn fetch_by_id(&self, id: &str) -> Result<model::UnifiedMetadata>;
+
+    /// Looks up `id` when given, otherwise the best scored search result,
+    /// recording its score in `match_confidence`.
     async fn fetch(
         &self,
         id: Option<&str>,
-        title: Option<&str>,
-        year: Option<i32>,
-    ) -> Result<model::UnifiedMetadata>;
+        query: &scoring::MatchQuery,
+    ) -> Result<model::UnifiedMetadata> {
+        if let Some(id) = id {
+            return self.fetch_by_id(id).await;
+        }
+        if query.titles.is_empty() {
+            return Err(Error::RustError("ID or Title required".into()));
+        }
+
+        let best = scoring::best(query, self.search(query).await?)
+            .ok_or_else(|| Error::RustError("No suitable match found".into()))?;
+        let mut unified = self.fetch_by_id(&best.candidate.id).await?;
+        unified.match_confidence = Some(best.confidence);
+        Ok(unified)
+    }
 }
 
 pub async fn get_metadata(args: MetadataArgs<'_>, env: &Env) -> Result<Response> {
-    // 1. Try TMDb first if TMDB ID is present or configured
-    if args.tmdb_id.is_some() {
-        let tmdb = tmdb::TmdbProvider::new(env);
-        match tmdb.fetch(args.tmdb_id, args.title, args.year).await {
-            Ok(unified) => return create_response(&unified, env, None),
-            Err(e) => console_log!("TMDb fetch failed {:?}", e),
+    // 0. A pinned override replaces whatever IDs the item carries
+    let pin = match env.kv(config::OVERRIDES_BINDING) {
+        Ok(kv) => overrides::lookup(&kv, &args.targets)
+            .await
+            .unwrap_or_else(|e| {
+                console_log!("Override lookup failed {:?}", e);
+                None
+            }),
+        Err(_) => None,
+    };
+    let (tmdb_id, mal_id, kitsu_id, bangumi_id, anidb_id, anilist_id) = match &pin {
+        Some(pin) => (
+            pin.tmdb_id.as_deref(),
+            pin.mal_id.as_deref(),
+            pin.kitsu_id.as_deref(),
+            pin.bangumi_id.as_deref(),
+            pin.anidb_id.as_deref(),
+            pin.anilist_id.as_deref(),
+        ),
+        None => (
+            args.tmdb_id,
+            args.mal_id,
+            args.kitsu_id,
+            args.bangumi_id,
+            args.anidb_id,
+            args.anilist_id,
+        ),
+    };
+    // Tried in this order when looking the item up in the ID mapping
+    let given = [
+        (ProviderKind::Mal, mal_id),
+        (ProviderKind::Anilist, anilist_id),
+        (ProviderKind::Anidb, anidb_id),
+        (ProviderKind::Kitsu, kitsu_id),
+        (ProviderKind::Tmdb, tmdb_id),
+        (ProviderKind::Bangumi, bangumi_id),
+    ];
+    let id_of = |provider: ProviderKind| {
+        given
+            .iter()
+            .find(|(p, _)| *p == provider)
+            .and_then(|(_, id)| *id)
+    };
+
+    // 1. IDs the item lacks are filled in from the ID mapping
+    let mapped = if args.providers.iter().any(|&p| id_of(p).is_none()) {
+        cross_reference(env, &given).await
+    } else {
+        None
+    };
+    let mut ids: Vec<(ProviderKind, Option<&str>)> = args
+        .providers
+        .iter()
+        .map(|&provider| {
+            let id = id_of(provider).or_else(|| mapped.as_ref()?.get(provider.name()));
+            (provider, id)
+        })
+        .collect();
+    ids.retain(|(p, id)| !p.by_id_only() || (id.is_some() && !args.merge));
+
+    if args.merge {
+        return merge_metadata(&args, env, &ids).await;
+    }
+
+    // Providers are tried for the IDs the item has, in order. The last one
+    // also searches by title when it has no ID.
+    let Some(((last, last_id), rest)) = ids.split_last() else {
+        return Err(Error::RustError("No metadata providers enabled".into()));
+    };
+    for &(provider, id) in rest.iter().filter(|(_, id)| id.is_some()) {
+        match provider.fetch(env, &args.language, id, &args.query).await {
+            Ok(unified) => return create_response(unified, &args, env, provider.cache_ttl()),
+            Err(e) => console_log!("{} fetch failed {:?}", provider.name(), e),
+        }
+    }
+
+    if last_id.is_none() && args.query.titles.is_empty() {
+        return Err(Error::RustError(
+            "Title required for metadata lookup".into(),
+        ));
+    }
+    let unified = last
+        .fetch(env, &args.language, *last_id, &args.query)
+        .await?;
+    create_response(unified, &args, env, last.cache_ttl())
+}
+
+// The ID mapping entry for the first of `given` that has one, if the mapping
+// is configured
+async fn cross_reference(
+    env: &Env,
+    given: &[(ProviderKind, Option<&str>)],
+) -> Option<idmap::IdMapping> {
+    let kv = env.kv(config::ID_MAP_BINDING).ok()?;
+    let known: Vec<(&str, &str)> = given
+        .iter()
+        .filter_map(|(p, id)| Some((p.name(), (*id)?)))
+        .filter(|(site, _)| idmap::SITES.contains(site))
+        .collect();
+    if known.is_empty() {
+        return None;
+    }
+    idmap::lookup(&kv, &known).await.unwrap_or_else(|e| {
+        console_log!("ID mapping lookup failed {:?}", e);
+        None
+    })
+}
+
+async fn merge_metadata(
+    args: &MetadataArgs<'_>,
+    env: &Env,
+    ids: &[(ProviderKind, Option<&str>)],
+) -> Result<Response> {
+    // Providers with an ID go first, so the IDs on other sites they return
+    // can spare the rest a title search
+    let (with_id, without_id): (Vec<_>, Vec<_>) =
+        ids.iter().copied().partition(|(_, id)| id.is_some());
+    let mut fetched = fetch_each(args, env, &with_id).await;
+    let bridged: Vec<(ProviderKind, Option<String>)> = without_id
+        .iter()
+        .map(|&(provider, _)| {
+            let id = fetched.iter().find_map(|(_, result)| {
+                result
+                    .as_ref()
+                    .ok()?
+                    .external_ids
+                    .get(provider.name())
+                    .cloned()
+            });
+            (provider, id)
+        })
+        .collect();
+    let bridged: Vec<_> = bridged.iter().map(|(p, id)| (*p, id.as_deref())).collect();
+    fetched.extend(fetch_each(args, env, &bridged).await);
+    fetched.sort_by_key(|(p, _)| ids.iter().position(|(q, _)| q == p));
+
+    let mut results = Vec::new();
+    for (provider, result) in fetched {
+        match result {
+            Ok(unified) => results.push((provider.name(), unified)),
+            Err(e) => console_log!("{} fetch failed {:?}", provider.name(), e),
+        }
+    }
+
+    let unified = merge::merge(results)
+        .ok_or_else(|| Error::RustError("No provider returned metadata".into()))?;
+    // Anything taken from a provider with a shorter TTL is cached for less
+    let ttl_override = ids
+        .iter()
+        .filter(|(p, _)| unified.sources.values().any(|s| s == p.name()))
+        .filter_map(|(p, _)| p.cache_ttl())
+        .min();
+    create_response(unified, args, env, ttl_override)
+}
+
+async fn fetch_each(
+    args: &MetadataArgs<'_>,
+    env: &Env,
+    ids: &[(ProviderKind, Option<&str>)],
+) -> Vec<(ProviderKind, Result<model::UnifiedMetadata>)> {
+    let fetches = ids
+        .iter()
+        .map(|&(provider, id)| provider.fetch(env, &args.language, id, &args.query));
+    let fetched = futures::future::join_all(fetches).await;
+    ids.iter().map(|(p, _)| *p).zip(fetched).collect()
+}
+
+/// The providers this deployment uses, in order: `METADATA_PROVIDERS` or
+/// the default, without TMDb when no token is configured and without AniDB
+/// when no client is registered.
+pub fn enabled_providers(env: &Env) -> Vec<ProviderKind> {
+    let configured = env
+        .var("METADATA_PROVIDERS")
+        .map(|v| v.to_string())
+        .unwrap_or_else(|_| config::METADATA_PROVIDERS.to_string());
+    let providers = priority::parse(&configured).unwrap_or_else(|e| {
+        console_log!("Invalid METADATA_PROVIDERS, using the default: {}", e);
+        ProviderKind::ALL.to_vec()
+    });
+
+    let has_tmdb_token = tmdb::TmdbProvider::new(env, Default::default()).has_token();
+    let has_anidb_client = anidb::HttpApi::from_env(env).is_some();
+    providers
+        .into_iter()
+        .filter(|p| match p {
+            ProviderKind::Tmdb => has_tmdb_token,
+            ProviderKind::Anidb => has_anidb_client,
+            _ => true,
+        })
+        .collect()
+}
+
+impl ProviderKind {
+    async fn fetch(
+        self,
+        env: &Env,
+        language: &model::Language,
+        id: Option<&str>,
+        query: &scoring::MatchQuery,
+    ) -> Result<model::UnifiedMetadata> {
+        match self {
+            ProviderKind::Tmdb => {
+                tmdb::TmdbProvider::new(env, language.clone())
+                    .fetch(id, query)
+                    .await
+            }
+            ProviderKind::Mal => jikan::JikanProvider.fetch(id, query).await,
+            ProviderKind::Kitsu => kitsu::KitsuProvider.fetch(id, query).await,
+            ProviderKind::Bangumi => {
+                bangumi::BangumiProvider::new(language.clone())
+                    .fetch(id, query)
+                    .await
+            }
+            ProviderKind::Anidb => anidb_provider(env, language)?.fetch(id, query).await,
+            ProviderKind::Anilist => anilist::AnilistProvider.fetch(id, query).await,
+        }
+    }
+
+    async fn search(
+        self,
+        env: &Env,
+        language: &model::Language,
+        query: &scoring::MatchQuery,
+    ) -> Result<Vec<scoring::Candidate>> {
+        match self {
+            ProviderKind::Tmdb => {
+                tmdb::TmdbProvider::new(env, language.clone())
+                    .search(query)
+                    .await
+            }
+            ProviderKind::Mal => jikan::JikanProvider.search(query).await,
+            ProviderKind::Kitsu => kitsu::KitsuProvider.search(query).await,
+            ProviderKind::Bangumi => {
+                bangumi::BangumiProvider::new(language.clone())
+                    .search(query)
+                    .await
+            }
+            ProviderKind::Anidb => anidb_provider(env, language)?.search(query).await,
+            ProviderKind::Anilist => anilist::AnilistProvider.search(query).await,
         }
     }
 
-    // 2. Try Jikan if MAL ID is present (or no TMDB ID was found)
-    if args.mal_id.is_some() {
-        let jikan = jikan::JikanProvider;
-        match jikan.fetch(args.mal_id, args.title, args.year).await {
-            Ok(unified) => {
-                return create_response(&unified, env, Some(crate::config::CACHE_TTL_JIKAN));
+    // AniList and Jikan have credits APIs of their own, and TMDb's metadata
+    // lookup only keeps a preview; the rest return cast and crew whole from a
+    // metadata lookup
+    async fn credits(
+        self,
+        env: &Env,
+        language: &model::Language,
+        id: &str,
+        page: usize,
+        per_page: usize,
+    ) -> Result<credits::CreditsPage> {
+        use credits::CreditsSource;
+        match self {
+            ProviderKind::Anilist => anilist::AnilistProvider.credits(id, page, per_page).await,
+            ProviderKind::Mal => jikan::JikanProvider.credits(id, page, per_page).await,
+            ProviderKind::Tmdb => {
+                tmdb::TmdbProvider::new(env, language.clone())
+                    .credits(id, page, per_page)
+                    .await
+            }
+            _ => {
+                let unified = self
+                    .fetch(env, language, Some(id), &Default::default())
+                    .await?;
+                Ok(credits::CreditsPage::slice(
+                    unified.characters,
+                    unified.staff,
+                    page,
+                    per_page,
+                ))
             }
-            Err(e) => console_log!("Jikan fetch failed {:?}", e),
         }
     }
 
-    // 3. Fallback to AniList
-    let anilist = anilist::AnilistProvider;
-    let fallback_title = args
-        .title
-        .ok_or_else(|| Error::RustError("Title required for metadata lookup".into()))?;
-
-    match anilist
-        .fetch(args.anilist_id, Some(fallback_title), args.year)
-        .await
-    {
-        Ok(unified) => create_response(&unified, env, None),
-        Err(e) => Err(e),
+    // Jikan is rate limited and its data changes more often
+    fn cache_ttl(self) -> Option<i32> {
+        match self {
+            ProviderKind::Mal => Some(config::CACHE_TTL_JIKAN),
+            _ => None,
+        }
     }
 }
 
+fn anidb_provider(
+    env: &Env,
+    language: &model::Language,
+) -> Result<anidb::AnidbProvider<kv::KvStore, anidb::HttpApi>> {
+    let api = anidb::HttpApi::from_env(env)
+        .ok_or_else(|| Error::RustError("AniDB client not configured".into()))?;
+    Ok(anidb::AnidbProvider::new(
+        env.kv(config::ANIDB_TITLES_BINDING).ok(),
+        api,
+        language.clone(),
+        Timestamp::now(),
+    ))
+}
+
+/// Credits from one provider.
+#[derive(Debug, Serialize)]
+pub struct ProviderCredits {
+    pub provider: &'static str,
+    pub id: String,
+    #[serde(flatten)]
+    pub credits: credits::CreditsPage,
+}
+
+/// A page of the full cast and crew from the first of `providers` with an
+/// ID in `ids`.
+pub async fn get_credits(
+    ids: &[(ProviderKind, &str)],
+    providers: &[ProviderKind],
+    language: model::Language,
+    env: &Env,
+    page: usize,
+    per_page: usize,
+) -> Result<ProviderCredits> {
+    let (provider, id) = providers
+        .iter()
+        .find_map(|p| ids.iter().find(|(kind, _)| kind == p))
+        .ok_or_else(|| Error::RustError("No enabled provider for the given IDs".into()))?;
+    let credits = provider.credits(env, &language, id, page, per_page).await?;
+    Ok(ProviderCredits {
+        provider: provider.name(),
+        id: id.to_string(),
+        credits,
+    })
+}
+
+/// The graph of works related to `id`, from AniList, MAL or TMDb.
+pub async fn get_relations(
+    provider: ProviderKind,
+    id: &str,
+    language: model::Language,
+    env: &Env,
+) -> Result<relations::RelationGraph> {
+    let max = config::RELATIONS_MAX_FETCHES;
+    match provider {
+        ProviderKind::Anilist => relations::build_graph(&anilist::AnilistProvider, id, max).await,
+        ProviderKind::Mal => relations::build_graph(&jikan::JikanProvider, id, max).await,
+        ProviderKind::Tmdb => {
+            relations::build_graph(&tmdb::TmdbProvider::new(env, language), id, max).await
+        }
+        _ => Err(Error::RustError(format!(
+            "Relations are not available from {}",
+            provider.name()
+        ))),
+    }
+}
+
+/// A search result from one provider. `id` goes in that provider's ID
+/// parameter (`tmdb_id`, `mal_id`, `kitsu_id`, `bangumi_id`, `anidb_id` or
+/// `anilist_id`) to pin the match.
+#[derive(Debug, Serialize)]
+pub struct ProviderCandidate {
+    pub provider: &'static str,
+    #[serde(flatten)]
+    pub scored: scoring::ScoredCandidate,
+}
+
+/// Searches every provider and returns the `limit` best scored results of
+/// each, best first, including ones below the confidence `get_metadata`
+/// requires. A provider that fails is left out.
+pub async fn get_candidates(
+    query: &scoring::MatchQuery,
+    language: model::Language,
+    providers: &[ProviderKind],
+    env: &Env,
+    limit: usize,
+) -> Vec<ProviderCandidate> {
+    let searches = providers.iter().map(|p| p.search(env, &language, query));
+    let found = futures::future::join_all(searches).await;
+
+    let mut candidates = Vec::new();
+    for (provider, result) in providers.iter().zip(found) {
+        match result {
+            Ok(found) => {
+                candidates.extend(scoring::rank(query, found).into_iter().take(limit).map(
+                    |scored| ProviderCandidate {
+                        provider: provider.name(),
+                        scored,
+                    },
+                ))
+            }
+            Err(e) => console_log!("{} search failed {:?}", provider.name(), e),
+        }
+    }
+
+    candidates.sort_by(|a, b| b.scored.confidence.total_cmp(&a.scored.confidence));
+    candidates
+}
+
 fn create_response(
-    unified: &model::UnifiedMetadata,
+    mut unified: model::UnifiedMetadata,
+    args: &MetadataArgs<'_>,
     env: &Env,
     ttl_override: Option<i32>,
 ) -> Result<Response> {
-    let ttl = if let Some(t) = ttl_override {
+    let now = Timestamp::now();
+    if !unified.is_finished {
+        unified.next_episode = schedule::project_next_episode(
+            &unified.episodes_list,
+            args.broadcast.as_ref(),
+            None,
+            now,
+        );
+    }
+
+    let mut ttl = if let Some(t) = ttl_override {
         t
     } else if unified.is_finished {
         crate::config::CACHE_TTL_FINISHED
@@ -72,7 +481,14 @@ fn create_response(
         crate::config::CACHE_TTL_ONGOING
     };
 
-    Response::from_json(unified)?
+    // Don't keep serving a projected episode after it has aired
+    if let Some(next) = &unified.next_episode {
+        let until_airing = (next.airs_at.as_secs() - now.as_secs()).max(60);
+        ttl = ttl.min(until_airing.min(i32::MAX as i64) as i32);
+    }
+
+    Response::from_json(&unified)?
         .add_cors(env)?
-        .add_header("Cache-Control", &format!("public, max-age={}", ttl))
+        .add_header("Cache-Control", &format!("public, max-age={}", ttl))?
+        .add_header("Content-Language", args.language.tag())
 }
@@ -0,0 +1,776 @@
+use super::MetadataProvider;
+use super::scoring::{Candidate, MatchQuery};
+use crate::datetime::Timestamp;
+use crate::kv::{self, KeyValueStore};
+use crate::model::{
+    AlternativeTitle, Language, UnifiedMetadata, UniversalCharacter, UniversalCoverImage,
+    UniversalEpisode, UniversalStaff, UniversalTitle,
+};
+use crate::search::normalize;
+use crate::{ResponseExt, config, utils};
+use regex::Regex;
+use serde_derive::Deserialize;
+use std::collections::hash_map::Entry;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::OnceLock;
+use worker::*;
+
+static ANIDB_LINK_REGEX: OnceLock<Regex> = OnceLock::new();
+
+// Title types in the dump and the API
+const TITLE_MAIN: &str = "1";
+const CREATOR_STUDIO: &str = "Animation Work";
+// `epno type` of regular episodes, as opposed to specials, credits and trailers
+const EPISODE_REGULAR: u8 = 1;
+const GENRE_LIMIT: usize = 10;
+
+/// AniDB's HTTP API, behind a trait so tests can serve fixtures.
+pub trait AnidbApi {
+    /// The `request=anime` XML for `aid`, or `None` if it doesn't exist.
+    async fn anime_xml(&self, aid: u32) -> Result<Option<String>>;
+}
+
+/// The live API, as a client registered with AniDB.
+pub struct HttpApi {
+    base: String,
+    client: String,
+    client_version: String,
+}
+
+impl HttpApi {
+    /// `None` unless `ANIDB_CLIENT` and `ANIDB_CLIENT_VERSION` are set.
+    /// `ANIDB_API_URL` replaces the API address, e.g. with a proxy.
+    pub fn from_env(env: &Env) -> Option<Self> {
+        let var = |name: &str| {
+            env.var(name)
+                .map(|v| v.to_string())
+                .ok()
+                .filter(|v| !v.is_empty())
+        };
+        Some(Self {
+            base: var("ANIDB_API_URL").unwrap_or_else(|| config::ANIDB_API_URL.to_string()),
+            client: var("ANIDB_CLIENT")?,
+            client_version: var("ANIDB_CLIENT_VERSION")?,
+        })
+    }
+}
+
+impl AnidbApi for HttpApi {
+    async fn anime_xml(&self, aid: u32) -> Result<Option<String>> {
+        let url = format!(
+            "{}?request=anime&client={}&clientver={}&protover=1&aid={}",
+            self.base, self.client, self.client_version, aid
+        );
+        // AniDB bans clients that ask for the same anime more than once a
+        // day. It answers errors, bans included, with a 200, so only anime
+        // are cached, and by hand rather than by status.
+        let cache = Cache::default();
+        if let Some(mut cached) = cache.get(url.as_str(), false).await? {
+            return cached.text().await.map(Some);
+        }
+        let Some(xml) = utils::fetch_text(&url, utils::NO_CACHE).await? else {
+            return Ok(None);
+        };
+        if !is_error(&xml) {
+            let response = Response::ok(xml.clone())?.add_header(
+                "Cache-Control",
+                &format!("public, max-age={}", config::CACHE_TTL_API),
+            )?;
+            cache.put(url.as_str(), response).await?;
+        }
+        Ok(Some(xml))
+    }
+}
+
+// Whether an API response is an `<error>` document rather than an anime
+fn is_error(xml: &str) -> bool {
+    let body = xml.trim_start();
+    let body = match body.strip_prefix("<?xml") {
+        Some(rest) => rest.split_once("?>").map_or("", |(_, r)| r).trim_start(),
+        None => body,
+    };
+    body.starts_with("<error")
+}
+
+/// Looks titles up in the index of AniDB's titles dump kept in `titles`
+/// (see [`refresh_titles`]), and fetches anime through `api`. `now` decides
+/// whether a show has finished airing.
+pub struct AnidbProvider<S, A> {
+    titles: Option<S>,
+    api: A,
+    language: Language,
+    now: Timestamp,
+}
+
+impl<S: KeyValueStore, A: AnidbApi> AnidbProvider<S, A> {
+    pub fn new(titles: Option<S>, api: A, language: Language, now: Timestamp) -> Self {
+        Self {
+            titles,
+            api,
+            language,
+            now,
+        }
+    }
+}
+
+impl<S: KeyValueStore, A: AnidbApi> MetadataProvider for AnidbProvider<S, A> {
+    async fn search(&self, query: &MatchQuery) -> Result<Vec<Candidate>> {
+        let store = self
+            .titles
+            .as_ref()
+            .ok_or_else(|| Error::RustError("AniDB titles index not configured".into()))?;
+        search_titles(store, query, config::METADATA_SEARCH_LIMIT).await
+    }
+
+    async fn fetch_by_id(&self, id: &str) -> Result<UnifiedMetadata> {
+        let aid = id
+            .parse::<u32>()
+            .map_err(|e| Error::RustError(format!("Invalid AniDB ID: {}", e)))?;
+        let xml = self
+            .api
+            .anime_xml(aid)
+            .await?
+            .ok_or_else(|| Error::RustError("AniDB data not found".into()))?;
+        let anime = parse_anime(&xml)?;
+        Ok(anime_to_unified(anime, &self.language, self.now))
+    }
+}
+
+// One `aid|type|language|title` line of the dump; `#` lines are comments
+fn parse_line(line: &str) -> Option<(u32, &str, &str, &str)> {
+    if line.starts_with('#') {
+        return None;
+    }
+    let mut fields = line.splitn(4, '|');
+    let aid = fields.next()?.parse().ok()?;
+    Some((aid, fields.next()?, fields.next()?, fields.next()?.trim()))
+}
+
+fn shard_of(key: &str) -> usize {
+    kv::shard_of(key, config::ANIDB_TITLES_SHARDS)
+}
+
+// Normalized titles to the anime that have them
+fn title_shard_key(shard: usize) -> String {
+    format!("titles:{}", shard)
+}
+
+// Anime to all their titles, the main one first
+fn anime_shard_key(shard: usize) -> String {
+    format!("anime:{}", shard)
+}
+
+/// Indexes the titles dump uploaded under [`config::ANIDB_TITLES_KEY`] into
+/// `store` and deletes the upload. Returns the number of anime indexed, or
+/// `None` when nothing was uploaded.
+pub async fn refresh_titles(store: &impl KeyValueStore) -> Result<Option<usize>> {
+    let Some(dump) = store.get_text(config::ANIDB_TITLES_KEY).await? else {
+        return Ok(None);
+    };
+    let count = index_titles(store, &dump).await?;
+    store.delete(config::ANIDB_TITLES_KEY).await?;
+    Ok(Some(count))
+}
+
+/// Replaces the stored index with the titles in `dump`, spread over
+/// [`config::ANIDB_TITLES_SHARDS`] values of each kind. Returns the number
+/// of anime indexed.
+async fn index_titles(store: &impl KeyValueStore, dump: &str) -> Result<usize> {
+    let mut titles: BTreeMap<u32, Vec<String>> = BTreeMap::new();
+    let mut by_title: Vec<BTreeMap<String, Vec<u32>>> =
+        vec![BTreeMap::new(); config::ANIDB_TITLES_SHARDS];
+    for (aid, kind, _, title) in dump.lines().filter_map(parse_line) {
+        let list = titles.entry(aid).or_default();
+        if kind == TITLE_MAIN {
+            list.insert(0, title.to_string());
+        } else if !list.iter().any(|t| t == title) {
+            list.push(title.to_string());
+        }
+
+        let normalized = normalize(title);
+        if normalized.is_empty() {
+            continue;
+        }
+        let aids = by_title[shard_of(&normalized)]
+            .entry(normalized)
+            .or_default();
+        if !aids.contains(&aid) {
+            aids.push(aid);
+        }
+    }
+
+    let mut by_anime: Vec<BTreeMap<String, &Vec<String>>> =
+        vec![BTreeMap::new(); config::ANIDB_TITLES_SHARDS];
+    for (aid, list) in &titles {
+        let aid = aid.to_string();
+        by_anime[shard_of(&aid)].insert(aid, list);
+    }
+
+    // Every shard is rewritten, so anime dropped from the dump go away too
+    for (i, shard) in by_title.iter().enumerate() {
+        store
+            .put_text(&title_shard_key(i), serde_json::to_string(shard)?)
+            .await?;
+    }
+    for (i, shard) in by_anime.iter().enumerate() {
+        store
+            .put_text(&anime_shard_key(i), serde_json::to_string(shard)?)
+            .await?;
+    }
+    Ok(titles.len())
+}
+
+/// Finds anime with a title equal to one of the query's after normalizing,
+/// and lists all their titles with the main one first. The dump has no
+/// years or types, so scoring goes by title.
+async fn search_titles(
+    store: &impl KeyValueStore,
+    query: &MatchQuery,
+    limit: usize,
+) -> Result<Vec<Candidate>> {
+    let mut title_shards: HashMap<usize, BTreeMap<String, Vec<u32>>> = HashMap::new();
+    let mut aids = Vec::new();
+    for needle in query.titles.iter().map(|t| normalize(t)) {
+        if needle.is_empty() {
+            continue;
+        }
+        let shard = shard_of(&needle);
+        if let Entry::Vacant(entry) = title_shards.entry(shard) {
+            entry.insert(get_shard(store, &title_shard_key(shard)).await?);
+        }
+        for &aid in title_shards[&shard].get(&needle).into_iter().flatten() {
+            if !aids.contains(&aid) {
+                aids.push(aid);
+            }
+        }
+    }
+    aids.truncate(limit);
+
+    let mut anime_shards: HashMap<usize, BTreeMap<String, Vec<String>>> = HashMap::new();
+    let mut candidates = Vec::new();
+    for aid in aids {
+        let id = aid.to_string();
+        let shard = shard_of(&id);
+        if let Entry::Vacant(entry) = anime_shards.entry(shard) {
+            entry.insert(get_shard(store, &anime_shard_key(shard)).await?);
+        }
+        let titles = anime_shards
+            .get_mut(&shard)
+            .and_then(|s| s.remove(&id))
+            .unwrap_or_default();
+        candidates.push(Candidate {
+            id,
+            titles,
+            ..Default::default()
+        });
+    }
+    Ok(candidates)
+}
+
+async fn get_shard<T: for<'de> serde::Deserialize<'de> + Default>(
+    store: &impl KeyValueStore,
+    key: &str,
+) -> Result<T> {
+    match store.get_text(key).await? {
+        Some(text) => Ok(serde_json::from_str(&text)?),
+        None => Ok(T::default()),
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct AnimeXml {
+    #[serde(rename = "@id")]
+    id: u32,
+    #[serde(rename = "type")]
+    type_field: Option<String>,
+    episodecount: Option<i32>,
+    startdate: Option<String>,
+    enddate: Option<String>,
+    #[serde(default)]
+    titles: TitlesXml,
+    description: Option<String>,
+    picture: Option<String>,
+    ratings: Option<RatingsXml>,
+    #[serde(default)]
+    creators: CreatorsXml,
+    #[serde(default)]
+    tags: TagsXml,
+    #[serde(default)]
+    characters: CharactersXml,
+    #[serde(default)]
+    episodes: EpisodesXml,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct TitlesXml {
+    #[serde(default)]
+    title: Vec<TitleXml>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TitleXml {
+    #[serde(rename = "@xml:lang")]
+    lang: String,
+    #[serde(rename = "@type")]
+    type_field: Option<String>,
+    #[serde(rename = "$text")]
+    text: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct RatingsXml {
+    permanent: Option<ValueXml>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ValueXml {
+    #[serde(rename = "$text")]
+    value: f64,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct CreatorsXml {
+    #[serde(default)]
+    name: Vec<CreatorXml>,
+}
+
+#[derive(Debug, Deserialize)]
+struct CreatorXml {
+    #[serde(rename = "@type")]
+    type_field: String,
+    #[serde(rename = "$text")]
+    name: String,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct TagsXml {
+    #[serde(default)]
+    tag: Vec<TagXml>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TagXml {
+    #[serde(rename = "@weight", default)]
+    weight: u32,
+    #[serde(rename = "@localspoiler", default)]
+    local_spoiler: bool,
+    #[serde(rename = "@globalspoiler", default)]
+    global_spoiler: bool,
+    name: String,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct CharactersXml {
+    #[serde(default)]
+    character: Vec<CharacterXml>,
+}
+
+#[derive(Debug, Deserialize)]
+struct CharacterXml {
+    #[serde(rename = "@type")]
+    type_field: Option<String>,
+    name: String,
+    picture: Option<String>,
+    seiyuu: Option<SeiyuuXml>,
+}
+
+#[derive(Debug, Deserialize)]
+struct SeiyuuXml {
+    #[serde(rename = "@picture")]
+    picture: Option<String>,
+    #[serde(rename = "$text")]
+    name: String,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct EpisodesXml {
+    #[serde(default)]
+    episode: Vec<EpisodeXml>,
+}
+
+#[derive(Debug, Deserialize)]
+struct EpisodeXml {
+    epno: EpnoXml,
+    length: Option<i32>,
+    airdate: Option<String>,
+    #[serde(default)]
+    title: Vec<TitleXml>,
+    summary: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct EpnoXml {
+    #[serde(rename = "@type")]
+    type_field: u8,
+    #[serde(rename = "$text")]
+    number: String,
+}
+
+// AniDB answers problems such as bans with an `<error>` document and a 200
+fn parse_anime(xml: &str) -> Result<AnimeXml> {
+    if is_error(xml) {
+        let message = xml
+            .split_once("<error")
+            .and_then(|(_, rest)| rest.split_once('>'))
+            .and_then(|(_, rest)| rest.split_once("</error>"))
+            .map_or("unknown", |(message, _)| message.trim());
+        return Err(Error::RustError(format!("AniDB error: {}", message)));
+    }
+    quick_xml::de::from_str(xml).map_err(|e| Error::RustError(format!("Invalid AniDB XML: {}", e)))
+}
+
+// AniDB languages to try for episode titles, best first
+fn title_languages(language: &Language) -> &'static [&'static str] {
+    match language {
+        Language::Ja => &["ja", "x-jat", "en"],
+        Language::En => &["en", "x-jat"],
+        Language::ZhHans => &["zh-Hans", "zh", "zh-Hant", "ja", "en"],
+        Language::ZhHant => &["zh-Hant", "zh", "zh-Hans", "ja", "en"],
+    }
+}
+
+// Descriptions link to other entries as `http://anidb.net/ch28 [Lafiel]`
+fn strip_links(text: &str) -> String {
+    let regex = ANIDB_LINK_REGEX.get_or_init(|| {
+        Regex::new(r"https?://anidb\.net/\S+ \[([^\]]+)\]").expect("Invalid AniDB Link Regex")
+    });
+    regex.replace_all(text, "$1").trim().to_string()
+}
+
+fn anime_to_unified(anime: AnimeXml, language: &Language, now: Timestamp) -> UnifiedMetadata {
+    let find = |lang: &str, kind: &str| {
+        anime
+            .titles
+            .title
+            .iter()
+            .find(|t| t.lang == lang && t.type_field.as_deref() == Some(kind))
+            .map(|t| t.text.clone())
+    };
+    let mut title = UniversalTitle {
+        romaji: find("x-jat", "main"),
+        english: find("en", "official"),
+        native: find("ja", "official"),
+        season: None,
+        alternatives: Vec::new(),
+    };
+    for t in &anime.titles.title {
+        title.add_alternative(AlternativeTitle {
+            title: t.text.clone(),
+            language: Some(t.lang.clone()),
+            region: None,
+        });
+    }
+
+    let (studios, staff): (Vec<CreatorXml>, Vec<CreatorXml>) = anime
+        .creators
+        .name
+        .into_iter()
+        .partition(|c| c.type_field == CREATOR_STUDIO);
+
+    let mut tags: Vec<TagXml> = anime
+        .tags
+        .tag
+        .into_iter()
+        .filter(|t| t.weight > 0 && !t.local_spoiler && !t.global_spoiler)
+        .collect();
+    tags.sort_by_key(|t| std::cmp::Reverse(t.weight));
+
+    let characters = anime
+        .characters
+        .character
+        .into_iter()
+        .map(|c| {
+            let image = |p: String| format!("{}/{}", config::ANIDB_IMAGE_BASE, p);
+            let (voice_actor, voice_actor_image) = match c.seiyuu {
+                Some(s) => (Some(s.name), s.picture.map(image)),
+                None => (None, None),
+            };
+            UniversalCharacter {
+                name: c.name,
+                voice_actor,
+                role: c.type_field,
+                image: c.picture.map(image),
+                voice_actor_image,
+                voice_actor_language: None,
+            }
+        })
+        .collect();
+
+    let preferred = title_languages(language);
+    let mut episodes_list: Vec<UniversalEpisode> = anime
+        .episodes
+        .episode
+        .into_iter()
+        .filter(|e| e.epno.type_field == EPISODE_REGULAR)
+        .filter_map(|e| {
+            let number = e.epno.number.trim().parse().ok()?;
+            let title = preferred
+                .iter()
+                .find_map(|lang| e.title.iter().find(|t| t.lang == *lang))
+                .or_else(|| e.title.first())
+                .map(|t| t.text.clone());
+            Some(UniversalEpisode {
+                number,
+                title,
+                air_date: e.airdate,
+                overview: e.summary.map(|s| strip_links(&s)).filter(|s| !s.is_empty()),
+                runtime: e.length,
+                thumbnail: None,
+                titles: e
+                    .title
+                    .into_iter()
+                    .map(|t| AlternativeTitle {
+                        title: t.text,
+                        language: Some(t.lang),
+                        region: None,
+                    })
+                    .collect(),
+            })
+        })
+        .collect();
+    episodes_list.sort_by_key(|e| e.number);
+
+    let description = anime
+        .description
+        .map(|d| strip_links(&d))
+        .filter(|d| !d.is_empty());
+
+    UnifiedMetadata {
+        id: anime.id.to_string(),
+        title,
+        cover_image: UniversalCoverImage {
+            large: anime
+                .picture
+                .map(|p| format!("{}/{}", config::ANIDB_IMAGE_BASE, p)),
+            extra_large: None,
+        },
+        average_score: anime
+            .ratings
+            .and_then(|r| r.permanent)
+            .map(|r| (r.value * 10.0).round() as i32),
+        episodes: anime.episodecount.filter(|c| *c > 0),
+        genres: tags.into_iter().take(GENRE_LIMIT).map(|t| t.name).collect(),
+        description_language: description.as_ref().map(|_| "en".to_string()),
+        description,
+        studios: studios.into_iter().map(|c| c.name).collect(),
+        characters,
+        staff: staff
+            .into_iter()
+            .map(|c| UniversalStaff {
+                name: c.name,
+                role: c.type_field,
+                department: None,
+                image: None,
+            })
+            .collect(),
+        runtime: episodes_list.iter().find_map(|e| e.runtime),
+        episodes_list,
+        // Movies often have no end date, so they're done once released
+        is_finished: anime
+            .enddate
+            .or(anime
+                .startdate
+                .filter(|_| anime.type_field.as_deref() == Some("Movie")))
+            .and_then(|d| Timestamp::parse(&d))
+            .is_some_and(|end| end <= now),
+        total_seasons: None,
+        current_season: None,
+        content_rating: None,
+        next_episode: None,
+        match_confidence: None,
+        external_ids: Default::default(),
+        external_links: Vec::new(),
+        streaming_episodes: Vec::new(),
+        sources: Default::default(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kv::MemoryStore;
+    use futures::executor::block_on;
+    use std::cell::RefCell;
+
+    const ANIME_XML: &str = include_str!("fixtures/anidb_anime.xml");
+
+    const TITLES_DUMP: &str = "\
+# created: Sat Oct 18 00:00:01 2026
+# <aid>|<type>|<language>|<title>
+# type: 1=primary title (one per anime), 2=synonyms (multiple per anime), 3=shorttitles (multiple per anime), 4=official title (one per language)
+1|4|en|Crest of the Stars
+1|1|x-jat|Seikai no Monshou
+1|4|ja|星界の紋章
+2|1|x-jat|Seikai no Senki
+2|4|ja|星界の戦旗
+17617|3|x-jat|Frieren
+17617|1|x-jat|Sousou no Frieren
+17617|4|ja|葬送のフリーレン
+17617|4|en|Frieren: Beyond Journey`s End
+";
+
+    /// Serves the fixture, recording which anime were requested.
+    #[derive(Default)]
+    struct MockApi(RefCell<Vec<u32>>);
+
+    impl AnidbApi for MockApi {
+        async fn anime_xml(&self, aid: u32) -> Result<Option<String>> {
+            self.0.borrow_mut().push(aid);
+            Ok((aid == 1).then(|| ANIME_XML.to_string()))
+        }
+    }
+
+    fn indexed() -> MemoryStore {
+        let store = MemoryStore::default();
+        block_on(index_titles(&store, TITLES_DUMP)).unwrap();
+        store
+    }
+
+    #[test]
+    fn test_anime_to_unified() {
+        let anime = parse_anime(ANIME_XML).unwrap();
+        let now = Timestamp::parse("2026-10-18").unwrap();
+        let unified = anime_to_unified(anime, &Language::En, now);
+
+        assert_eq!(unified.id, "1");
+        assert_eq!(unified.title.romaji.as_deref(), Some("Seikai no Monshou"));
+        assert_eq!(unified.title.english.as_deref(), Some("Crest of the Stars"));
+        assert_eq!(unified.title.native.as_deref(), Some("星界の紋章"));
+        assert_eq!(unified.title.alternatives[0].title, "星界之纹章");
+        assert_eq!(
+            unified.title.alternatives[0].language.as_deref(),
+            Some("zh-Hans")
+        );
+        assert_eq!(
+            unified.cover_image.large.as_deref(),
+            Some("https://cdn-eu.anidb.net/images/main/440.jpg")
+        );
+        assert_eq!(unified.average_score, Some(82));
+        assert_eq!(unified.episodes, Some(13));
+        assert_eq!(unified.genres, vec!["space travel", "military"]);
+        assert_eq!(
+            unified.description.as_deref(),
+            Some("Jinto Lin meets Lafiel, princess of the Abh Empire.")
+        );
+        assert_eq!(unified.studios, vec!["Sunrise"]);
+        assert_eq!(unified.staff[0].role, "Direction");
+        assert_eq!(
+            unified.characters[0].name,
+            "Abriel Nei Debrusc Borl Paryun Lafiel"
+        );
+        assert_eq!(
+            unified.characters[0].voice_actor.as_deref(),
+            Some("Kawasumi Ayako")
+        );
+        assert_eq!(
+            unified.characters[0].voice_actor_image.as_deref(),
+            Some("https://cdn-eu.anidb.net/images/main/184301.jpg")
+        );
+        assert_eq!(unified.runtime, Some(25));
+        assert!(unified.is_finished);
+
+        // Regular episodes only, in order, titled in the requested language
+        let numbers: Vec<_> = unified.episodes_list.iter().map(|e| e.number).collect();
+        assert_eq!(numbers, vec![1, 2]);
+        let first = &unified.episodes_list[0];
+        assert_eq!(first.title.as_deref(), Some("Invasion"));
+        assert_eq!(first.air_date.as_deref(), Some("1999-01-02"));
+        let languages: Vec<_> = first
+            .titles
+            .iter()
+            .map(|t| t.language.as_deref().unwrap())
+            .collect();
+        assert_eq!(languages, vec!["ja", "en", "x-jat"]);
+
+        let anime = parse_anime(ANIME_XML).unwrap();
+        let ja = anime_to_unified(anime, &Language::Ja, now);
+        assert_eq!(ja.episodes_list[0].title.as_deref(), Some("侵略"));
+        // zh falls back to Japanese when AniDB has no Chinese title
+        let anime = parse_anime(ANIME_XML).unwrap();
+        let zh = anime_to_unified(anime, &Language::ZhHant, now);
+        assert_eq!(zh.episodes_list[1].title.as_deref(), Some("帝国"));
+    }
+
+    #[test]
+    fn test_parse_anime_error() {
+        let banned =
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<error code=\"500\">banned</error>";
+        match parse_anime(banned) {
+            Err(Error::RustError(message)) => assert_eq!(message, "AniDB error: banned"),
+            other => panic!("unexpected {:?}", other.map(|a| a.id)),
+        }
+        assert!(parse_anime("<anime>").is_err());
+    }
+
+    #[test]
+    fn test_is_error() {
+        assert!(is_error(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<error code=\"500\">banned</error>"
+        ));
+        assert!(is_error("<error>Client Values Missing or Invalid</error>"));
+        assert!(!is_error(ANIME_XML));
+    }
+
+    #[test]
+    fn test_search_titles() {
+        let store = indexed();
+        let query = MatchQuery::new(["Seikai no Senki", "星界の紋章", "星界"]);
+        let candidates = block_on(search_titles(&store, &query, 10)).unwrap();
+
+        // Titles match whole, in the order of the query's
+        let ids: Vec<_> = candidates.iter().map(|c| c.id.as_str()).collect();
+        assert_eq!(ids, vec!["2", "1"]);
+        assert_eq!(candidates[0].titles, vec!["Seikai no Senki", "星界の戦旗"]);
+        assert_eq!(
+            candidates[1].titles,
+            vec!["Seikai no Monshou", "Crest of the Stars", "星界の紋章"]
+        );
+        assert_eq!(block_on(search_titles(&store, &query, 1)).unwrap().len(), 1);
+        let missing = MatchQuery::new(["ダンジョン飯"]);
+        assert!(
+            block_on(search_titles(&store, &missing, 10))
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn test_refresh_titles() {
+        let store = MemoryStore::default();
+        assert_eq!(block_on(refresh_titles(&store)).unwrap(), None);
+
+        store.0.borrow_mut().insert(
+            config::ANIDB_TITLES_KEY.to_string(),
+            TITLES_DUMP.to_string(),
+        );
+        assert_eq!(block_on(refresh_titles(&store)).unwrap(), Some(3));
+        // The upload is indexed once, then dropped
+        assert!(!store.0.borrow().contains_key(config::ANIDB_TITLES_KEY));
+        assert_eq!(block_on(refresh_titles(&store)).unwrap(), None);
+
+        let query = MatchQuery::new(["sousou no frieren"]);
+        let candidates = block_on(search_titles(&store, &query, 10)).unwrap();
+        assert_eq!(candidates[0].id, "17617");
+        assert_eq!(candidates[0].titles[0], "Sousou no Frieren");
+    }
+
+    #[test]
+    fn test_provider_fetch() {
+        let now = Timestamp::parse("2026-10-18").unwrap();
+        let provider = AnidbProvider::new(Some(indexed()), MockApi::default(), Language::En, now);
+        let query = MatchQuery::new(["Crest of the Stars"]);
+
+        // Searching reads the index alone; anime are fetched by ID
+        let candidates = block_on(provider.search(&query)).unwrap();
+        assert_eq!(candidates[0].id, "1");
+        assert!(provider.api.0.borrow().is_empty());
+        let unified = block_on(provider.fetch(Some("1"), &query)).unwrap();
+        assert_eq!(unified.id, "1");
+        assert!(block_on(provider.fetch(Some("2"), &query)).is_err());
+        assert_eq!(*provider.api.0.borrow(), vec![1, 2]);
+
+        let without_index =
+            AnidbProvider::<MemoryStore, _>::new(None, MockApi::default(), Language::En, now);
+        assert!(block_on(without_index.search(&query)).is_err());
+    }
+}
@@ -1,57 +1,472 @@
 use super::MetadataProvider;
-use crate::model;
+use super::credits::{CreditsPage, CreditsSource};
+use super::relations::{RelationGraph, RelationNode, RelationSource, RelationType};
+use super::scoring::{Candidate, MatchQuery};
+use crate::{config, model, utils};
+use regex::Regex;
+use serde_derive::Deserialize;
+use std::collections::BTreeMap;
 use std::sync::OnceLock;
 use worker::*;
 
 pub struct AnilistProvider;
 
 static ANILIST_CLIENT: OnceLock<rust_anilist::Client> = OnceLock::new();
+static EPISODE_NUMBER_REGEX: OnceLock<Regex> = OnceLock::new();
+
+const SEARCH_QUERY: &str = "
+query ($search: String, $perPage: Int) {
+  Page(perPage: $perPage) {
+    media(search: $search, type: ANIME) {
+      id
+      title { romaji english native }
+      synonyms
+      format
+      episodes
+      seasonYear
+      startDate { year }
+      coverImage { large }
+    }
+  }
+}";
+
+// A page of the cast, each character with their voice actors, and crew
+const CREDITS_QUERY: &str = "
+query ($id: Int, $page: Int, $perPage: Int) {
+  Media(id: $id, type: ANIME) {
+    characters(page: $page, perPage: $perPage, sort: [ROLE, RELEVANCE, ID]) {
+      pageInfo { total hasNextPage }
+      edges {
+        role
+        node { name { full } image { large } }
+        voiceActors { name { full } image { large } languageV2 }
+      }
+    }
+    staff(page: $page, perPage: $perPage, sort: [RELEVANCE, ID]) {
+      pageInfo { total hasNextPage }
+      edges {
+        role
+        node { name { full } image { large } }
+      }
+    }
+  }
+}";
+
+// The works related to one, each summarized as the root is
+const RELATIONS_QUERY: &str = "
+fragment summary on Media {
+  id
+  type
+  format
+  episodes
+  seasonYear
+  startDate { year }
+  title { romaji english native }
+  coverImage { large extraLarge }
+}
+query ($id: Int) {
+  Media(id: $id) {
+    ...summary
+    relations {
+      edges {
+        relationType(version: 2)
+        node { ...summary }
+      }
+    }
+  }
+}";
+
+#[derive(Debug, Deserialize)]
+struct GraphQlResponse<T> {
+    data: T,
+}
+
+#[derive(Debug, Deserialize)]
+struct SearchPage {
+    #[serde(rename = "Page")]
+    page: SearchMediaList,
+}
+
+#[derive(Debug, Deserialize)]
+struct SearchMediaList {
+    media: Vec<SearchMedia>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SearchMedia {
+    id: i64,
+    title: SearchTitle,
+    #[serde(default)]
+    synonyms: Vec<String>,
+    format: Option<String>,
+    episodes: Option<i32>,
+    season_year: Option<i32>,
+    start_date: Option<SearchDate>,
+    cover_image: Option<SearchCover>,
+}
+
+#[derive(Debug, Deserialize)]
+struct SearchTitle {
+    romaji: Option<String>,
+    english: Option<String>,
+    native: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct SearchDate {
+    year: Option<i32>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SearchCover {
+    large: Option<String>,
+    extra_large: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct CreditsData {
+    #[serde(rename = "Media")]
+    media: CreditsMedia,
+}
+
+#[derive(Debug, Deserialize)]
+struct CreditsMedia {
+    characters: Option<Connection<CharacterEdge>>,
+    staff: Option<Connection<StaffEdge>>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Connection<T> {
+    page_info: Option<PageInfo>,
+    edges: Vec<T>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PageInfo {
+    total: Option<usize>,
+    has_next_page: Option<bool>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CharacterEdge {
+    role: Option<String>,
+    node: CreditsPerson,
+    #[serde(default)]
+    voice_actors: Vec<CreditsPerson>,
+}
+
+#[derive(Debug, Deserialize)]
+struct StaffEdge {
+    role: Option<String>,
+    node: CreditsPerson,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CreditsPerson {
+    name: CreditsName,
+    image: Option<CreditsImage>,
+    language_v2: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct CreditsName {
+    full: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct CreditsImage {
+    large: Option<String>,
+}
+
+impl CreditsPerson {
+    fn name(&self) -> String {
+        self.name.full.clone().unwrap_or_default()
+    }
+
+    fn image(&self) -> Option<String> {
+        self.image.as_ref()?.large.clone()
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct RelationsData {
+    #[serde(rename = "Media")]
+    media: RelationsMedia,
+}
+
+#[derive(Debug, Deserialize)]
+struct RelationsMedia {
+    #[serde(flatten)]
+    summary: RelationSummary,
+    relations: Option<RelationsConnection>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RelationSummary {
+    id: i64,
+    #[serde(rename = "type")]
+    media_type: Option<String>,
+    format: Option<String>,
+    episodes: Option<i32>,
+    season_year: Option<i32>,
+    start_date: Option<SearchDate>,
+    title: SearchTitle,
+    cover_image: Option<SearchCover>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RelationsConnection {
+    edges: Vec<RelationsEdge>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RelationsEdge {
+    relation_type: Option<RelationType>,
+    node: RelationSummary,
+}
 
 impl MetadataProvider for AnilistProvider {
-    async fn fetch(
-        &self,
-        id: Option<&str>,
-        title: Option<&str>,
-        _year: Option<i32>,
-    ) -> Result<model::UnifiedMetadata> {
+    // rust-anilist's search only exposes titles, so query the API directly
+    async fn search(&self, query: &MatchQuery) -> Result<Vec<Candidate>> {
+        let title = query
+            .primary_title()
+            .ok_or_else(|| Error::RustError("Title required".into()))?;
+        let body = serde_json::json!({
+            "query": SEARCH_QUERY,
+            "variables": { "search": title, "perPage": config::METADATA_SEARCH_LIMIT },
+        });
+
+        let response: GraphQlResponse<SearchPage> =
+            utils::post_json(config::ANILIST_GRAPHQL_URL, &body).await?;
+        Ok(response
+            .data
+            .page
+            .media
+            .into_iter()
+            .map(search_candidate)
+            .collect())
+    }
+
+    async fn fetch_by_id(&self, id: &str) -> Result<model::UnifiedMetadata> {
         let client = ANILIST_CLIENT.get_or_init(rust_anilist::Client::default);
 
-        let anime = if let Some(i) = id {
-            // AniList ID must be an integer
-            let anime_id = i
-                .parse::<i64>()
-                .map_err(|e| Error::RustError(format!("Invalid AniList ID: {}", e)))?;
-            client
-                .get_anime(anime_id)
-                .await
-                .map_err(|e| Error::RustError(format!("AniList API error (get_anime): {}", e)))?
-        } else if let Some(t) = title {
-            let results = client.search_anime(t, 1, 1).await;
-
-            // rust-anilist search_anime returns Option<Vec<Anime>>
-            // and it might return None or an empty vector.
-            match results.and_then(|v| v.into_iter().next()) {
-                Some(anime) => anime,
-                None => return Err(Error::RustError("AniList: Not Found".into())),
-            }
-        } else {
-            return Err(Error::RustError(
-                "AniList provider requires ID or Title".into(),
-            ));
-        };
+        // AniList ID must be an integer
+        let anime_id = id
+            .parse::<i64>()
+            .map_err(|e| Error::RustError(format!("Invalid AniList ID: {}", e)))?;
+        // rust-anilist leaves out staff roles, so the first page of credits
+        // supplies the staff
+        let (anime, credits) = futures::join!(
+            client.get_anime(anime_id),
+            self.credits(id, 1, config::CREDITS_PER_PAGE_DEFAULT)
+        );
+        let anime =
+            anime.map_err(|e| Error::RustError(format!("AniList API error (get_anime): {}", e)))?;
 
-        Ok(anilist_to_unified(anime))
+        let mut unified = anilist_to_unified(anime);
+        match credits {
+            Ok(credits) if !credits.staff.is_empty() => unified.staff = credits.staff,
+            Ok(_) => {}
+            Err(e) => console_log!("AniList credits failed {:?}", e),
+        }
+        Ok(unified)
+    }
+}
+
+impl RelationSource for AnilistProvider {
+    async fn related(&self, id: &str) -> Result<RelationGraph> {
+        let anime_id = id
+            .parse::<i64>()
+            .map_err(|e| Error::RustError(format!("Invalid AniList ID: {}", e)))?;
+        let body = serde_json::json!({
+            "query": RELATIONS_QUERY,
+            "variables": { "id": anime_id },
+        });
+
+        let response: GraphQlResponse<RelationsData> =
+            utils::post_json(config::ANILIST_GRAPHQL_URL, &body).await?;
+        Ok(relations_graph(response.data.media))
+    }
+}
+
+fn relations_graph(media: RelationsMedia) -> RelationGraph {
+    let related = media
+        .relations
+        .map(|r| r.edges)
+        .unwrap_or_default()
+        .into_iter()
+        .map(|edge| {
+            (
+                edge.relation_type.unwrap_or(RelationType::Other),
+                relation_node(edge.node),
+            )
+        })
+        .collect();
+    RelationGraph::around(relation_node(media.summary), related)
+}
+
+fn relation_node(media: RelationSummary) -> RelationNode {
+    let cover = media.cover_image;
+    RelationNode {
+        media_type: media.media_type.map(|t| t.to_lowercase()),
+        format: media.format,
+        title: model::UniversalTitle {
+            romaji: media.title.romaji,
+            english: media.title.english,
+            native: media.title.native,
+            ..Default::default()
+        },
+        cover_image: model::UniversalCoverImage {
+            large: cover.as_ref().and_then(|c| c.large.clone()),
+            extra_large: cover.and_then(|c| c.extra_large),
+        },
+        year: media
+            .season_year
+            .or_else(|| media.start_date.and_then(|d| d.year)),
+        episodes: media.episodes,
+        ..RelationNode::new("anilist", media.id.to_string())
+    }
+}
+
+impl CreditsSource for AnilistProvider {
+    async fn credits(&self, id: &str, page: usize, per_page: usize) -> Result<CreditsPage> {
+        let anime_id = id
+            .parse::<i64>()
+            .map_err(|e| Error::RustError(format!("Invalid AniList ID: {}", e)))?;
+        let body = serde_json::json!({
+            "query": CREDITS_QUERY,
+            "variables": { "id": anime_id, "page": page, "perPage": per_page },
+        });
+
+        let response: GraphQlResponse<CreditsData> =
+            utils::post_json(config::ANILIST_GRAPHQL_URL, &body).await?;
+        Ok(credits_page(response.data.media, page, per_page))
+    }
+}
+
+fn credits_page(media: CreditsMedia, page: usize, per_page: usize) -> CreditsPage {
+    // Totals can't be less than what was returned up to this page
+    let total = |info: Option<&PageInfo>, len: usize| {
+        let seen = page.saturating_sub(1) * per_page + len;
+        info.and_then(|i| i.total).unwrap_or(seen).max(seen)
+    };
+    let more = |info: Option<&PageInfo>| info.and_then(|i| i.has_next_page).unwrap_or(false);
+
+    let (characters, character_info) = match media.characters {
+        Some(c) => (c.edges, c.page_info),
+        None => (Vec::new(), None),
+    };
+    let (staff, staff_info) = match media.staff {
+        Some(s) => (s.edges, s.page_info),
+        None => (Vec::new(), None),
+    };
+
+    CreditsPage {
+        page,
+        per_page,
+        total_characters: total(character_info.as_ref(), characters.len()),
+        total_staff: total(staff_info.as_ref(), staff.len()),
+        has_next_page: more(character_info.as_ref()) || more(staff_info.as_ref()),
+        characters: characters.into_iter().map(character_to_unified).collect(),
+        staff: staff.into_iter().map(staff_to_unified).collect(),
+    }
+}
+
+fn character_to_unified(edge: CharacterEdge) -> model::UniversalCharacter {
+    // Dubs are listed too; the Japanese cast comes first where there is one
+    let voice_actor = edge
+        .voice_actors
+        .iter()
+        .find(|va| va.language_v2.as_deref() == Some("Japanese"))
+        .or_else(|| edge.voice_actors.first());
+
+    model::UniversalCharacter {
+        name: edge.node.name(),
+        voice_actor: voice_actor.map(CreditsPerson::name),
+        role: edge.role.as_deref().map(role_name),
+        image: edge.node.image(),
+        voice_actor_image: voice_actor.and_then(CreditsPerson::image),
+        voice_actor_language: voice_actor.and_then(|va| va.language_v2.clone()),
+    }
+}
+
+fn staff_to_unified(edge: StaffEdge) -> model::UniversalStaff {
+    model::UniversalStaff {
+        name: edge.node.name(),
+        role: edge.role.unwrap_or_default(),
+        department: None,
+        image: edge.node.image(),
+    }
+}
+
+// `MAIN` reads `Main`, as rust-anilist displays its roles
+fn role_name(role: &str) -> String {
+    let mut chars = role.chars();
+    chars
+        .next()
+        .map(|first| first.to_string() + &chars.as_str().to_lowercase())
+        .unwrap_or_default()
+}
+
+// Streaming episode titles read like "Episode 3 - Killing Magic"
+fn episode_number(title: &str) -> Option<i32> {
+    let re = EPISODE_NUMBER_REGEX.get_or_init(|| {
+        Regex::new(r"(?i)^\s*episode\s+(\d+)").expect("Invalid Episode Number Regex")
+    });
+    re.captures(title)?[1].parse().ok()
+}
+
+fn search_candidate(media: SearchMedia) -> Candidate {
+    let type_field = match media.format.as_deref() {
+        Some("TV" | "TV_SHORT") => Some(model::ItemType::Tv),
+        Some("MOVIE") => Some(model::ItemType::Movie),
+        Some("OVA" | "SPECIAL") => Some(model::ItemType::Ova),
+        Some("ONA") => Some(model::ItemType::Web),
+        _ => None,
+    };
+    let title = media.title;
+
+    Candidate {
+        id: media.id.to_string(),
+        titles: [title.native, title.romaji, title.english]
+            .into_iter()
+            .flatten()
+            .chain(media.synonyms)
+            .collect(),
+        year: media
+            .season_year
+            .or_else(|| media.start_date.and_then(|d| d.year)),
+        type_field,
+        episodes: media.episodes,
+        poster: media.cover_image.and_then(|c| c.large),
     }
 }
 
 pub fn anilist_to_unified(media: rust_anilist::models::Anime) -> model::UnifiedMetadata {
     use model::*;
 
-    let title = UniversalTitle {
+    let mut title = UniversalTitle {
         romaji: (!media.title.romaji().is_empty()).then(|| media.title.romaji().to_string()),
         english: (!media.title.english().is_empty()).then(|| media.title.english().to_string()),
         native: (!media.title.native().is_empty()).then(|| media.title.native().to_string()),
+        season: None,
+        alternatives: Vec::new(),
     };
+    // AniList synonyms carry no language
+    for synonym in media.synonyms.unwrap_or_default() {
+        title.add_alternative(AlternativeTitle {
+            title: synonym,
+            ..Default::default()
+        });
+    }
 
     let cover_image = UniversalCoverImage {
         large: media.cover.large,
@@ -82,10 +497,14 @@ pub fn anilist_to_unified(media: rust_anilist::models::Anime) -> model::UnifiedM
                 name: c.name.full.unwrap_or_default(),
                 voice_actor: voice_actor.flatten(),
                 role: c.role.map(|r| r.to_string()),
+                image: None,
+                voice_actor_image: None,
+                voice_actor_language: None,
             }
         })
         .collect();
 
+    // Without roles; `fetch_by_id` replaces these with the credits' staff
     let staff = media
         .staff
         .unwrap_or_default()
@@ -94,8 +513,43 @@ pub fn anilist_to_unified(media: rust_anilist::models::Anime) -> model::UnifiedM
             name: s.name.full.unwrap_or_default(),
             role: "".to_string(),
             department: None,
+            image: None,
+        })
+        .collect();
+
+    let mut external_ids = BTreeMap::from([("anilist".to_string(), media.id.to_string())]);
+    if let Some(id_mal) = media.id_mal {
+        external_ids.insert("mal".to_string(), id_mal.to_string());
+    }
+
+    let external_links = media
+        .external_links
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|link| !link.url.is_empty())
+        .map(|link| ExternalLink {
+            site: link.site,
+            url: link.url,
+            link_type: link.link_type.map(|t| t.to_string().to_uppercase()),
+            language: link.language,
+        })
+        .collect();
+
+    let mut streaming_episodes: Vec<StreamingEpisode> = media
+        .streaming_episodes
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|episode| !episode.url.is_empty())
+        .map(|episode| StreamingEpisode {
+            number: episode.title.as_deref().and_then(episode_number),
+            title: episode.title,
+            thumbnail: episode.thumbnail,
+            url: episode.url,
+            site: episode.site,
         })
         .collect();
+    // AniList lists them newest first; unnumbered ones go last
+    streaming_episodes.sort_by_key(|e| (e.number.is_none(), e.number));
 
     let description = if media.description.is_empty() {
         None
@@ -110,6 +564,7 @@ pub fn anilist_to_unified(media: rust_anilist::models::Anime) -> model::UnifiedM
         average_score: media.average_score.map(|s| s as i32),
         episodes: media.episodes.map(|e| e as i32),
         genres,
+        description_language: description.as_ref().map(|_| "en".to_string()),
         description,
         studios,
         characters,
@@ -123,6 +578,12 @@ pub fn anilist_to_unified(media: rust_anilist::models::Anime) -> model::UnifiedM
         current_season: None,
         runtime: media.duration.map(|d| d as i32),
         content_rating: None,
+        next_episode: None,
+        match_confidence: None,
+        external_ids,
+        external_links,
+        streaming_episodes,
+        sources: Default::default(),
     }
 }
 
@@ -153,6 +614,7 @@ mod tests {
             "format": "TV",
             "status": "FINISHED",
             "description": "This is a test description.",
+            "synonyms": ["TA", "Test Anime"],
             "coverImage": {
                 "large": "https://example.com/large.jpg",
                 "extraLarge": "https://example.com/xlarge.jpg"
@@ -211,6 +673,13 @@ mod tests {
             unified.title.english,
             Some("Test Anime English".to_string())
         );
+        assert_eq!(
+            unified.title.alternatives,
+            vec![model::AlternativeTitle {
+                title: "TA".to_string(),
+                ..Default::default()
+            }]
+        );
         assert_eq!(unified.title.native, Some("テストアニメ".to_string()));
         assert_eq!(
             unified.cover_image.large,
@@ -282,4 +751,201 @@ mod tests {
         assert!(!unified.is_finished); // RELEASING -> false
         assert_eq!(unified.runtime, None);
     }
+
+    #[test]
+    fn test_anilist_to_unified_links() {
+        let anime = create_anime_from_json(json!({
+            "id": 154587,
+            "idMal": 52991,
+            "title": { "romaji": "Sousou no Frieren" },
+            "status": "FINISHED",
+            "description": "",
+            "externalLinks": [
+                { "url": "https://frieren-anime.jp/", "site": "Official Site", "type": "INFO", "language": "Japanese" },
+                { "url": "https://www.crunchyroll.com/series/GG5H5XQX4", "site": "Crunchyroll", "type": "STREAMING", "language": null },
+                { "url": "", "site": "Broken", "type": "INFO", "language": null }
+            ],
+            "streamingEpisodes": [
+                { "title": "Episode 2 - It Didn't Have to Be Magic...", "thumbnail": "https://example.com/2.jpg", "url": "https://www.crunchyroll.com/watch/2", "site": "Crunchyroll" },
+                { "title": "Recap", "thumbnail": null, "url": "https://www.crunchyroll.com/watch/r", "site": "Crunchyroll" },
+                { "title": "Episode 1 - The Journey's End", "thumbnail": null, "url": "https://www.crunchyroll.com/watch/1", "site": "Crunchyroll" }
+            ]
+        }));
+        let unified = anilist_to_unified(anime);
+
+        assert_eq!(
+            unified.external_ids,
+            BTreeMap::from([
+                ("anilist".into(), "154587".into()),
+                ("mal".into(), "52991".into())
+            ])
+        );
+        assert_eq!(unified.external_links.len(), 2);
+        assert_eq!(unified.external_links[1].site, "Crunchyroll");
+        assert_eq!(
+            unified.external_links[1].link_type.as_deref(),
+            Some("STREAMING")
+        );
+        assert_eq!(
+            unified.external_links[0].language.as_deref(),
+            Some("Japanese")
+        );
+        let numbers: Vec<_> = unified
+            .streaming_episodes
+            .iter()
+            .map(|e| e.number)
+            .collect();
+        assert_eq!(numbers, vec![Some(1), Some(2), None]);
+        assert_eq!(
+            unified.streaming_episodes[0].url,
+            "https://www.crunchyroll.com/watch/1"
+        );
+    }
+
+    #[test]
+    fn test_credits_page() {
+        let response: GraphQlResponse<CreditsData> = serde_json::from_value(json!({
+            "data": { "Media": {
+                "characters": {
+                    "pageInfo": { "total": 5, "hasNextPage": true },
+                    "edges": [{
+                        "role": "MAIN",
+                        "node": { "name": { "full": "Frieren" }, "image": { "large": "https://example.com/c.jpg" } },
+                        "voiceActors": [
+                            { "name": { "full": "Mallorie Rodak" }, "image": { "large": "https://example.com/en.jpg" }, "languageV2": "English" },
+                            { "name": { "full": "Atsumi Tanezaki" }, "image": { "large": "https://example.com/ja.jpg" }, "languageV2": "Japanese" }
+                        ]
+                    }, {
+                        "role": "BACKGROUND",
+                        "node": { "name": { "full": "Flamme" }, "image": null },
+                        "voiceActors": []
+                    }]
+                },
+                "staff": {
+                    "pageInfo": { "total": 0, "hasNextPage": false },
+                    "edges": [
+                        { "role": "Director", "node": { "name": { "full": "Keiichirou Saitou" }, "image": null } }
+                    ]
+                }
+            }}
+        }))
+        .unwrap();
+
+        let page = credits_page(response.data.media, 2, 2);
+        assert_eq!(page.total_characters, 5);
+        // A total lower than what was returned is not believed
+        assert_eq!(page.total_staff, 3);
+        assert!(page.has_next_page);
+
+        let frieren = &page.characters[0];
+        assert_eq!(frieren.role.as_deref(), Some("Main"));
+        assert_eq!(frieren.image.as_deref(), Some("https://example.com/c.jpg"));
+        assert_eq!(frieren.voice_actor.as_deref(), Some("Atsumi Tanezaki"));
+        assert_eq!(
+            frieren.voice_actor_image.as_deref(),
+            Some("https://example.com/ja.jpg")
+        );
+        assert_eq!(frieren.voice_actor_language.as_deref(), Some("Japanese"));
+        assert_eq!(page.characters[1].role.as_deref(), Some("Background"));
+        assert_eq!(page.characters[1].voice_actor, None);
+        assert_eq!(page.staff[0].role, "Director");
+    }
+
+    #[test]
+    fn test_relations_graph() {
+        let response: GraphQlResponse<RelationsData> = serde_json::from_value(json!({
+            "data": { "Media": {
+                "id": 21,
+                "type": "ANIME",
+                "format": "TV",
+                "episodes": null,
+                "seasonYear": 1999,
+                "startDate": { "year": 1999 },
+                "title": { "romaji": "ONE PIECE", "english": "ONE PIECE", "native": "ONE PIECE" },
+                "coverImage": { "large": "https://example.com/21.jpg", "extraLarge": null },
+                "relations": { "edges": [
+                    { "relationType": "SOURCE", "node": {
+                        "id": 30013, "type": "MANGA", "format": "MANGA", "episodes": null,
+                        "seasonYear": null, "startDate": { "year": 1997 },
+                        "title": { "romaji": "ONE PIECE", "english": null, "native": "ONE PIECE" },
+                        "coverImage": null
+                    }},
+                    { "relationType": "SIDE_STORY", "node": {
+                        "id": 459, "type": "ANIME", "format": "MOVIE", "episodes": 1,
+                        "seasonYear": 2000, "startDate": { "year": 2000 },
+                        "title": { "romaji": "ONE PIECE (Movie)", "english": null, "native": null },
+                        "coverImage": { "large": null, "extraLarge": null }
+                    }},
+                    { "relationType": "TRANSLATION", "node": {
+                        "id": 1, "type": "MANGA", "format": "MANGA", "episodes": null,
+                        "seasonYear": null, "startDate": null,
+                        "title": { "romaji": null, "english": null, "native": null },
+                        "coverImage": null
+                    }}
+                ]}
+            }}
+        }))
+        .unwrap();
+
+        let graph = relations_graph(response.data.media);
+        assert_eq!(graph.root, "anilist:21");
+        assert_eq!(graph.nodes.len(), 4);
+        let root = graph.nodes.iter().find(|n| n.id == graph.root).unwrap();
+        assert_eq!(root.media_type.as_deref(), Some("anime"));
+        assert_eq!(root.year, Some(1999));
+        assert_eq!(
+            root.cover_image.large.as_deref(),
+            Some("https://example.com/21.jpg")
+        );
+
+        let relations: Vec<_> = graph
+            .edges
+            .iter()
+            .map(|e| (e.relation, e.to.as_str()))
+            .collect();
+        assert_eq!(
+            relations,
+            vec![
+                (RelationType::Source, "anilist:30013"),
+                (RelationType::SideStory, "anilist:459"),
+                (RelationType::Other, "anilist:1"),
+            ]
+        );
+        let movie = graph.nodes.iter().find(|n| n.id == "anilist:459").unwrap();
+        assert_eq!(movie.provider_id, "459");
+        assert_eq!(movie.format.as_deref(), Some("MOVIE"));
+    }
+
+    #[test]
+    fn test_search_candidate() {
+        let response: GraphQlResponse<SearchPage> = serde_json::from_value(json!({
+            "data": { "Page": { "media": [{
+                "id": 154587,
+                "title": { "romaji": "Sousou no Frieren", "english": "Frieren: Beyond Journey's End", "native": "葬送のフリーレン" },
+                "synonyms": ["Frieren at the Funeral"],
+                "format": "TV",
+                "episodes": 28,
+                "seasonYear": null,
+                "startDate": { "year": 2023 },
+                "coverImage": { "large": "https://example.com/large.jpg" }
+            }]}}
+        }))
+        .unwrap();
+
+        let media = response.data.page.media.into_iter().next().unwrap();
+        let candidate = search_candidate(media);
+        assert_eq!(candidate.id, "154587");
+        assert_eq!(
+            candidate.titles,
+            vec![
+                "葬送のフリーレン",
+                "Sousou no Frieren",
+                "Frieren: Beyond Journey's End",
+                "Frieren at the Funeral"
+            ]
+        );
+        assert_eq!(candidate.year, Some(2023));
+        assert_eq!(candidate.type_field, Some(model::ItemType::Tv));
+        assert_eq!(candidate.episodes, Some(28));
+    }
 }
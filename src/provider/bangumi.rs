@@ -0,0 +1,590 @@
+use super::MetadataProvider;
+use super::scoring::{Candidate, MatchQuery};
+use crate::datetime::Timestamp;
+use crate::model::{
+    AlternativeTitle, ItemType, Language, UnifiedMetadata, UniversalCharacter, UniversalCoverImage,
+    UniversalEpisode, UniversalStaff, UniversalTitle,
+};
+use crate::{config, utils};
+use serde_derive::Deserialize;
+use worker::*;
+
+// bgm.tv subject type for anime
+const SUBJECT_TYPE_ANIME: i32 = 2;
+// Episode type for the main story, as opposed to specials, OPs and EDs
+const EPISODE_TYPE_MAIN: i32 = 0;
+const EPISODES_PER_PAGE: usize = 100;
+// Person relation bgm.tv uses for the animation studio
+const RELATION_STUDIO: &str = "动画制作";
+
+pub struct BangumiProvider {
+    language: Language,
+}
+
+#[derive(Debug, Deserialize)]
+struct Subject {
+    id: i64,
+    name: String,
+    #[serde(default)]
+    name_cn: String,
+    #[serde(default)]
+    summary: String,
+    date: Option<String>,
+    #[serde(default)]
+    platform: String,
+    images: Option<SubjectImages>,
+    #[serde(default)]
+    infobox: Vec<InfoboxItem>,
+    #[serde(default)]
+    eps: i32,
+    #[serde(default)]
+    total_episodes: i32,
+    rating: Option<Rating>,
+    #[serde(default)]
+    meta_tags: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct SubjectImages {
+    large: Option<String>,
+    common: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct InfoboxItem {
+    key: String,
+    value: InfoboxValue,
+}
+
+// Infobox values are either a string or a list of `{ "k"?, "v" }` entries
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum InfoboxValue {
+    Text(String),
+    List(Vec<InfoboxEntry>),
+}
+
+#[derive(Debug, Deserialize)]
+struct InfoboxEntry {
+    v: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct Rating {
+    score: f64,
+}
+
+#[derive(Debug, Deserialize)]
+struct SearchResponse {
+    data: Vec<Subject>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Character {
+    name: String,
+    #[serde(default)]
+    relation: String,
+    #[serde(default)]
+    actors: Vec<Person>,
+    images: Option<PortraitImages>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Person {
+    name: String,
+    #[serde(default)]
+    relation: String,
+    images: Option<PortraitImages>,
+}
+
+// Missing images are empty strings
+#[derive(Debug, Deserialize)]
+struct PortraitImages {
+    #[serde(default)]
+    medium: String,
+}
+
+fn portrait(images: Option<PortraitImages>) -> Option<String> {
+    non_empty(images?.medium)
+}
+
+#[derive(Debug, Deserialize)]
+struct EpisodePage {
+    data: Vec<Episode>,
+    total: usize,
+}
+
+#[derive(Debug, Deserialize)]
+struct Episode {
+    ep: Option<f64>,
+    sort: f64,
+    #[serde(default)]
+    name: String,
+    #[serde(default)]
+    name_cn: String,
+    #[serde(default)]
+    airdate: String,
+    #[serde(default)]
+    desc: String,
+    duration_seconds: Option<i32>,
+}
+
+impl BangumiProvider {
+    pub fn new(language: Language) -> Self {
+        Self { language }
+    }
+}
+
+// bgm.tv rejects requests without a descriptive User-Agent
+async fn get<T: for<'de> serde::Deserialize<'de>>(path: &str) -> Result<Option<T>> {
+    let url = format!("{}{}", config::BANGUMI_API_BASE, path);
+    utils::fetch_json_with_headers(&url, &[("User-Agent", config::BANGUMI_USER_AGENT)]).await
+}
+
+impl MetadataProvider for BangumiProvider {
+    async fn search(&self, query: &MatchQuery) -> Result<Vec<Candidate>> {
+        let title = query
+            .primary_title()
+            .ok_or_else(|| Error::RustError("Title required".into()))?;
+        let url = format!(
+            "{}/v0/search/subjects?limit={}",
+            config::BANGUMI_API_BASE,
+            config::METADATA_SEARCH_LIMIT
+        );
+        let body = serde_json::json!({
+            "keyword": title,
+            "filter": { "type": [SUBJECT_TYPE_ANIME] },
+        });
+
+        let response: SearchResponse = utils::post_json_with_headers(
+            &url,
+            &body,
+            &[("User-Agent", config::BANGUMI_USER_AGENT)],
+        )
+        .await?;
+        Ok(response.data.iter().map(subject_candidate).collect())
+    }
+
+    async fn fetch_by_id(&self, id: &str) -> Result<UnifiedMetadata> {
+        let id = id
+            .parse::<i64>()
+            .map_err(|e| Error::RustError(format!("Invalid Bangumi ID: {}", e)))?;
+
+        let subject_path = format!("/v0/subjects/{}", id);
+        let characters_path = format!("{}/characters", subject_path);
+        let persons_path = format!("{}/persons", subject_path);
+        let (subject, characters, persons, episodes) = futures::join!(
+            get::<Subject>(&subject_path),
+            get::<Vec<Character>>(&characters_path),
+            get::<Vec<Person>>(&persons_path),
+            fetch_episodes(id),
+        );
+        let subject = subject?.ok_or_else(|| Error::RustError("Bangumi data not found".into()))?;
+        let (episodes, total) = episodes?;
+
+        Ok(subject_to_unified(
+            subject,
+            characters?.unwrap_or_default(),
+            persons?.unwrap_or_default(),
+            Episodes { episodes, total },
+            &self.language,
+            Timestamp::now(),
+        ))
+    }
+}
+
+// Main episodes in pages of EPISODES_PER_PAGE, up to the configured number
+// of pages, with the total bgm.tv reports
+async fn fetch_episodes(id: i64) -> Result<(Vec<Episode>, usize)> {
+    let mut episodes = Vec::new();
+    let mut total = 0;
+
+    for page in 0..config::BANGUMI_EPISODE_PAGES {
+        let path = format!(
+            "/v0/episodes?subject_id={}&type={}&limit={}&offset={}",
+            id,
+            EPISODE_TYPE_MAIN,
+            EPISODES_PER_PAGE,
+            page * EPISODES_PER_PAGE
+        );
+        let Some(response) = get::<EpisodePage>(&path).await? else {
+            break;
+        };
+        total = response.total;
+        episodes.extend(response.data);
+        if episodes.len() >= total {
+            break;
+        }
+    }
+    Ok((episodes, total))
+}
+
+struct Episodes {
+    episodes: Vec<Episode>,
+    /// How many main episodes bgm.tv has, which may be more than were fetched.
+    total: usize,
+}
+
+fn item_type(platform: &str) -> Option<ItemType> {
+    match platform {
+        "TV" => Some(ItemType::Tv),
+        "WEB" => Some(ItemType::Web),
+        "OVA" | "OAD" => Some(ItemType::Ova),
+        "剧场版" => Some(ItemType::Movie),
+        _ => None,
+    }
+}
+
+fn infobox_values<'a>(subject: &'a Subject, key: &'a str) -> impl Iterator<Item = &'a str> {
+    subject
+        .infobox
+        .iter()
+        .filter(move |i| i.key == key)
+        .flat_map(|i| match &i.value {
+            InfoboxValue::Text(text) => vec![text.as_str()],
+            InfoboxValue::List(entries) => entries.iter().map(|e| e.v.as_str()).collect(),
+        })
+        .map(str::trim)
+        .filter(|v| !v.is_empty())
+}
+
+// Summaries are mostly Chinese, but some are copied from Japanese sites
+fn summary_language(summary: &str) -> &'static str {
+    let has_kana = summary
+        .chars()
+        .any(|c| matches!(c, '\u{3041}'..='\u{3096}' | '\u{30A1}'..='\u{30FA}'));
+    if has_kana { "ja" } else { "zh-Hans" }
+}
+
+fn non_empty(s: String) -> Option<String> {
+    let trimmed = s.trim();
+    (!trimmed.is_empty()).then(|| trimmed.to_string())
+}
+
+fn subject_candidate(subject: &Subject) -> Candidate {
+    let mut titles = vec![subject.name.clone()];
+    if !subject.name_cn.is_empty() && subject.name_cn != subject.name {
+        titles.push(subject.name_cn.clone());
+    }
+    titles.extend(infobox_values(subject, "别名").map(str::to_string));
+
+    let total = subject.total_episodes.max(subject.eps);
+    Candidate {
+        id: subject.id.to_string(),
+        titles,
+        year: subject
+            .date
+            .as_deref()
+            .and_then(|d| d.get(0..4)?.parse().ok()),
+        type_field: item_type(&subject.platform),
+        episodes: (total > 0).then_some(total),
+        poster: subject.images.as_ref().and_then(|i| i.large.clone()),
+    }
+}
+
+fn subject_to_unified(
+    subject: Subject,
+    characters: Vec<Character>,
+    persons: Vec<Person>,
+    episodes: Episodes,
+    language: &Language,
+    now: Timestamp,
+) -> UnifiedMetadata {
+    let mut title = UniversalTitle {
+        romaji: None,
+        english: infobox_values(&subject, "英文名")
+            .next()
+            .map(str::to_string),
+        native: non_empty(subject.name.clone()),
+        season: None,
+        alternatives: Vec::new(),
+    };
+    if let Some(name_cn) = non_empty(subject.name_cn.clone()) {
+        title.add_alternative(AlternativeTitle {
+            title: name_cn,
+            language: Some("zh".into()),
+            region: Some("CN".into()),
+        });
+    }
+    let aliases: Vec<String> = infobox_values(&subject, "别名")
+        .map(str::to_string)
+        .collect();
+    for alias in aliases {
+        title.add_alternative(AlternativeTitle {
+            title: alias,
+            ..Default::default()
+        });
+    }
+
+    let characters = characters
+        .into_iter()
+        .map(|c| {
+            let (voice_actor, voice_actor_image) = match c.actors.into_iter().next() {
+                Some(actor) => (Some(actor.name), portrait(actor.images)),
+                None => (None, None),
+            };
+            UniversalCharacter {
+                name: c.name,
+                voice_actor,
+                role: non_empty(c.relation),
+                image: portrait(c.images),
+                voice_actor_image,
+                voice_actor_language: None,
+            }
+        })
+        .collect();
+
+    let (studios, staff): (Vec<Person>, Vec<Person>) = persons
+        .into_iter()
+        .partition(|p| p.relation == RELATION_STUDIO);
+
+    // Movies have no episodes, so they're done once released
+    let aired = |date: &str| Timestamp::parse(date).is_some_and(|t| t <= now);
+    let is_finished = match episodes.episodes.last() {
+        Some(last) => episodes.episodes.len() >= episodes.total && aired(&last.airdate),
+        None => subject.date.as_deref().is_some_and(aired),
+    };
+
+    let chinese = matches!(language, Language::ZhHans | Language::ZhHant);
+    let runtime = episodes
+        .episodes
+        .iter()
+        .find_map(|e| e.duration_seconds.filter(|d| *d > 0))
+        .map(|d| d / 60);
+    let episodes_list = episodes
+        .episodes
+        .into_iter()
+        .map(|e| {
+            let (preferred, fallback) = if chinese {
+                (e.name_cn, e.name)
+            } else {
+                (e.name, e.name_cn)
+            };
+            UniversalEpisode {
+                number: e.ep.unwrap_or(e.sort) as i32,
+                title: non_empty(preferred).or_else(|| non_empty(fallback)),
+                air_date: non_empty(e.airdate),
+                overview: non_empty(e.desc),
+                runtime: e.duration_seconds.filter(|d| *d > 0).map(|d| d / 60),
+                thumbnail: None,
+                titles: Vec::new(),
+            }
+        })
+        .collect();
+
+    let description = non_empty(subject.summary);
+    let total_episodes = subject.total_episodes.max(subject.eps);
+
+    UnifiedMetadata {
+        id: subject.id.to_string(),
+        title,
+        cover_image: UniversalCoverImage {
+            large: subject.images.as_ref().and_then(|i| i.common.clone()),
+            extra_large: subject.images.and_then(|i| i.large),
+        },
+        average_score: subject
+            .rating
+            .map(|r| (r.score * 10.0).round() as i32)
+            .filter(|s| *s > 0),
+        episodes: (total_episodes > 0).then_some(total_episodes),
+        genres: subject.meta_tags,
+        description_language: description
+            .as_deref()
+            .map(|d| summary_language(d).to_string()),
+        description,
+        studios: studios.into_iter().map(|p| p.name).collect(),
+        characters,
+        staff: staff
+            .into_iter()
+            .map(|p| UniversalStaff {
+                name: p.name,
+                role: p.relation,
+                department: None,
+                image: portrait(p.images),
+            })
+            .collect(),
+        episodes_list,
+        is_finished,
+        total_seasons: None,
+        current_season: None,
+        runtime,
+        content_rating: None,
+        next_episode: None,
+        match_confidence: None,
+        external_ids: Default::default(),
+        external_links: Vec::new(),
+        streaming_episodes: Vec::new(),
+        sources: Default::default(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn subject() -> Subject {
+        serde_json::from_value(json!({
+            "id": 400602,
+            "type": 2,
+            "name": "葬送のフリーレン",
+            "name_cn": "葬送的芙莉莲",
+            "summary": "勇者辛美尔一行人击败了魔王。",
+            "date": "2023-09-29",
+            "platform": "TV",
+            "images": {
+                "large": "https://lain.bgm.tv/pic/cover/l/13/c5/400602_ZI8Y9.jpg",
+                "common": "https://lain.bgm.tv/pic/cover/c/13/c5/400602_ZI8Y9.jpg"
+            },
+            "infobox": [
+                { "key": "中文名", "value": "葬送的芙莉莲" },
+                { "key": "别名", "value": [{ "v": "Frieren" }, { "v": "葬送的芙莉蓮" }] },
+                { "key": "英文名", "value": "Frieren: Beyond Journey's End" }
+            ],
+            "eps": 28,
+            "total_episodes": 28,
+            "rating": { "rank": 1, "total": 60000, "score": 9.1 },
+            "meta_tags": ["TV", "奇幻"]
+        }))
+        .unwrap()
+    }
+
+    fn episodes(total: usize) -> Episodes {
+        let page: EpisodePage = serde_json::from_value(json!({
+            "data": [
+                { "ep": 1, "sort": 1, "name": "冒険の終わり", "name_cn": "冒险的结束",
+                  "airdate": "2023-09-29", "desc": "", "duration_seconds": 1440 },
+                { "ep": 2, "sort": 2, "name": "別に魔法じゃなくたって…", "name_cn": "",
+                  "airdate": "2023-09-29", "desc": "", "duration_seconds": 1440 }
+            ],
+            "total": total
+        }))
+        .unwrap();
+        Episodes {
+            episodes: page.data,
+            total: page.total,
+        }
+    }
+
+    #[test]
+    fn test_subject_to_unified() {
+        let characters: Vec<Character> = serde_json::from_value(json!([
+            { "id": 1, "name": "フリーレン", "relation": "主角",
+              "images": { "large": "https://lain.bgm.tv/l/1.jpg", "medium": "https://lain.bgm.tv/m/1.jpg" },
+              "actors": [{ "id": 2, "name": "種﨑敦美", "images": { "medium": "" } }] }
+        ]))
+        .unwrap();
+        let persons: Vec<Person> = serde_json::from_value(json!([
+            { "id": 1, "name": "斎藤圭一郎", "relation": "导演", "career": ["artist"] },
+            { "id": 2, "name": "MADHOUSE", "relation": "动画制作", "career": [] }
+        ]))
+        .unwrap();
+        let now = Timestamp::parse("2024-04-01").unwrap();
+
+        let unified = subject_to_unified(
+            subject(),
+            characters,
+            persons,
+            episodes(2),
+            &Language::ZhHans,
+            now,
+        );
+
+        assert_eq!(unified.id, "400602");
+        assert_eq!(unified.title.native.as_deref(), Some("葬送のフリーレン"));
+        assert_eq!(
+            unified.title.english.as_deref(),
+            Some("Frieren: Beyond Journey's End")
+        );
+        let alternatives: Vec<_> = unified
+            .title
+            .alternatives
+            .iter()
+            .map(|a| a.title.as_str())
+            .collect();
+        assert_eq!(
+            alternatives,
+            vec!["葬送的芙莉莲", "Frieren", "葬送的芙莉蓮"]
+        );
+        assert_eq!(
+            unified.title.alternatives[0].language.as_deref(),
+            Some("zh")
+        );
+        assert_eq!(unified.average_score, Some(91));
+        assert_eq!(unified.episodes, Some(28));
+        assert_eq!(unified.genres, vec!["TV", "奇幻"]);
+        assert_eq!(unified.description_language.as_deref(), Some("zh-Hans"));
+        assert_eq!(unified.studios, vec!["MADHOUSE"]);
+        assert_eq!(unified.staff[0].role, "导演");
+        assert_eq!(
+            unified.characters[0].voice_actor.as_deref(),
+            Some("種﨑敦美")
+        );
+        assert_eq!(unified.characters[0].role.as_deref(), Some("主角"));
+        assert_eq!(
+            unified.characters[0].image.as_deref(),
+            Some("https://lain.bgm.tv/m/1.jpg")
+        );
+        assert_eq!(unified.characters[0].voice_actor_image, None);
+        assert_eq!(unified.runtime, Some(24));
+        assert!(unified.is_finished);
+
+        // Chinese episode titles fall back to the original
+        let titles: Vec<_> = unified
+            .episodes_list
+            .iter()
+            .map(|e| e.title.as_deref())
+            .collect();
+        assert_eq!(
+            titles,
+            vec![Some("冒险的结束"), Some("別に魔法じゃなくたって…")]
+        );
+        assert_eq!(unified.episodes_list[1].overview, None);
+    }
+
+    #[test]
+    fn test_subject_to_unified_ongoing() {
+        let now = Timestamp::parse("2024-04-01").unwrap();
+        // More episodes to come than were listed
+        let unified =
+            subject_to_unified(subject(), vec![], vec![], episodes(28), &Language::Ja, now);
+        assert!(!unified.is_finished);
+        assert_eq!(
+            unified.episodes_list[0].title.as_deref(),
+            Some("冒険の終わり")
+        );
+
+        // A movie without episodes is finished once released
+        let before = Timestamp::parse("2023-01-01").unwrap();
+        let none = || Episodes {
+            episodes: vec![],
+            total: 0,
+        };
+        assert!(
+            subject_to_unified(subject(), vec![], vec![], none(), &Language::Ja, now).is_finished
+        );
+        assert!(
+            !subject_to_unified(subject(), vec![], vec![], none(), &Language::Ja, before)
+                .is_finished
+        );
+    }
+
+    #[test]
+    fn test_subject_candidate() {
+        let candidate = subject_candidate(&subject());
+        assert_eq!(candidate.id, "400602");
+        assert_eq!(
+            candidate.titles,
+            vec![
+                "葬送のフリーレン",
+                "葬送的芙莉莲",
+                "Frieren",
+                "葬送的芙莉蓮"
+            ]
+        );
+        assert_eq!(candidate.year, Some(2023));
+        assert_eq!(candidate.type_field, Some(ItemType::Tv));
+        assert_eq!(candidate.episodes, Some(28));
+        assert_eq!(summary_language("冒険が終わった"), "ja");
+    }
+}
@@ -0,0 +1,85 @@
+use crate::model::{UniversalCharacter, UniversalStaff};
+use serde_derive::Serialize;
+use worker::Result;
+
+/// One page of a show's full cast and crew. Characters and staff are paged
+/// together, so page 2 holds the second `perPage` of each.
+#[derive(Debug, Default, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreditsPage {
+    pub page: usize,
+    pub per_page: usize,
+    pub total_characters: usize,
+    pub total_staff: usize,
+    /// Whether either list goes on past this page.
+    pub has_next_page: bool,
+    pub characters: Vec<UniversalCharacter>,
+    pub staff: Vec<UniversalStaff>,
+}
+
+impl CreditsPage {
+    /// Page `page`, counted from 1, of lists the provider returned whole.
+    pub fn slice(
+        characters: Vec<UniversalCharacter>,
+        staff: Vec<UniversalStaff>,
+        page: usize,
+        per_page: usize,
+    ) -> Self {
+        let skip = page.saturating_sub(1).saturating_mul(per_page);
+        let end = skip.saturating_add(per_page);
+        Self {
+            page,
+            per_page,
+            total_characters: characters.len(),
+            total_staff: staff.len(),
+            has_next_page: characters.len() > end || staff.len() > end,
+            characters: characters.into_iter().skip(skip).take(per_page).collect(),
+            staff: staff.into_iter().skip(skip).take(per_page).collect(),
+        }
+    }
+}
+
+/// A provider with an API for cast and crew beyond what its metadata
+/// lookup returns.
+pub trait CreditsSource {
+    async fn credits(&self, id: &str, page: usize, per_page: usize) -> Result<CreditsPage>;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn staff(count: usize) -> Vec<UniversalStaff> {
+        (0..count)
+            .map(|i| UniversalStaff {
+                name: format!("Staff {}", i),
+                ..Default::default()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_slice() {
+        let characters = vec![UniversalCharacter {
+            name: "Frieren".into(),
+            ..Default::default()
+        }];
+
+        let first = CreditsPage::slice(characters.clone(), staff(25), 1, 10);
+        assert_eq!(first.characters.len(), 1);
+        assert_eq!(first.staff.len(), 10);
+        assert_eq!(first.total_characters, 1);
+        assert_eq!(first.total_staff, 25);
+        assert!(first.has_next_page);
+
+        let last = CreditsPage::slice(characters.clone(), staff(25), 3, 10);
+        assert!(last.characters.is_empty());
+        assert_eq!(last.staff[0].name, "Staff 20");
+        assert_eq!(last.staff.len(), 5);
+        assert!(!last.has_next_page);
+
+        let past = CreditsPage::slice(characters, staff(25), 9, 10);
+        assert!(past.staff.is_empty());
+        assert!(!past.has_next_page);
+    }
+}
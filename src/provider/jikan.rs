@@ -1,9 +1,12 @@
 use crate::model::{
-    Item, ItemType, Language, Site, TitleTranslate, UnifiedMetadata, UniversalCoverImage,
-    UniversalTitle,
+    AlternativeTitle, Item, ItemType, Language, Site, TitleTranslate, UnifiedMetadata,
+    UniversalCharacter, UniversalCoverImage, UniversalStaff, UniversalTitle,
 };
 use crate::provider::MetadataProvider;
-use crate::utils;
+use crate::provider::credits::{CreditsPage, CreditsSource};
+use crate::provider::relations::{RelationGraph, RelationNode, RelationSource, RelationType};
+use crate::provider::scoring::{Candidate, MatchQuery};
+use crate::{config, utils};
 use regex::Regex;
 use serde_derive::Deserialize;
 use std::collections::{HashMap, HashSet};
@@ -25,6 +28,8 @@ struct JikanAnime {
     title: String,
     title_english: Option<String>,
     title_japanese: Option<String>,
+    #[serde(default)]
+    titles: Vec<JikanTitle>,
     #[serde(rename = "type")]
     type_field: Option<String>,
     episodes: Option<i32>,
@@ -36,11 +41,20 @@ struct JikanAnime {
     background: Option<String>,
     #[allow(dead_code)]
     season: Option<String>,
-    #[allow(dead_code)]
     year: Option<i32>,
     broadcast: Option<JikanBroadcast>,
     studios: Vec<JikanEntity>,
     genres: Vec<JikanEntity>,
+    /// Only in `/anime/{id}/full`.
+    #[serde(default)]
+    relations: Vec<JikanRelation>,
+}
+
+#[derive(Debug, Deserialize)]
+struct JikanTitle {
+    #[serde(rename = "type")]
+    type_field: String,
+    title: String,
 }
 
 #[derive(Debug, Deserialize)]
@@ -65,16 +79,81 @@ struct JikanEntity {
     name: String,
 }
 
+// An entry of `/anime/{id}/characters`
+#[derive(Debug, Deserialize)]
+struct JikanCharacterRole {
+    character: JikanPerson,
+    role: Option<String>,
+    #[serde(default)]
+    voice_actors: Vec<JikanVoiceActor>,
+}
+
+#[derive(Debug, Deserialize)]
+struct JikanVoiceActor {
+    person: JikanPerson,
+    language: Option<String>,
+}
+
+// An entry of `/anime/{id}/staff`
+#[derive(Debug, Deserialize)]
+struct JikanStaff {
+    person: JikanPerson,
+    #[serde(default)]
+    positions: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct JikanPerson {
+    name: String,
+    #[serde(default)]
+    images: HashMap<String, JikanImage>,
+}
+
+impl JikanPerson {
+    fn image(&self) -> Option<String> {
+        self.images.get("jpg")?.image_url.clone()
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct JikanRelation {
+    relation: String,
+    entry: Vec<JikanRelationEntry>,
+}
+
+#[derive(Debug, Deserialize)]
+struct JikanRelationEntry {
+    mal_id: i32,
+    /// `anime` or `manga`.
+    #[serde(rename = "type")]
+    type_field: String,
+    name: String,
+}
+
 pub struct JikanProvider;
 
 impl MetadataProvider for JikanProvider {
-    async fn fetch(
-        &self,
-        id: Option<&str>,
-        _title: Option<&str>,
-        _year: Option<i32>,
-    ) -> Result<UnifiedMetadata> {
-        let mal_id = id.ok_or_else(|| Error::RustError("MAL ID required".into()))?;
+    async fn search(&self, query: &MatchQuery) -> Result<Vec<Candidate>> {
+        let title = query
+            .primary_title()
+            .ok_or_else(|| Error::RustError("Title required".into()))?;
+        let url = Url::parse_with_params(
+            "https://api.jikan.moe/v4/anime",
+            &[
+                ("q", title.to_string()),
+                ("limit", config::METADATA_SEARCH_LIMIT.to_string()),
+            ],
+        )
+        .map_err(|e| Error::RustError(format!("Invalid Jikan search URL: {}", e)))?;
+
+        let response: Option<JikanResponse<Vec<JikanAnime>>> =
+            utils::fetch_json(url.as_str()).await?;
+        Ok(response
+            .map(|r| r.data.iter().map(jikan_candidate).collect())
+            .unwrap_or_default())
+    }
+
+    async fn fetch_by_id(&self, mal_id: &str) -> Result<UnifiedMetadata> {
         let url = format!("https://api.jikan.moe/v4/anime/{}/full", mal_id);
 
         let response: JikanResponse<JikanAnime> = utils::fetch_json(&url)
@@ -86,6 +165,111 @@ impl MetadataProvider for JikanProvider {
     }
 }
 
+impl RelationSource for JikanProvider {
+    async fn related(&self, mal_id: &str) -> Result<RelationGraph> {
+        let url = format!("https://api.jikan.moe/v4/anime/{}/full", mal_id);
+
+        let response: JikanResponse<JikanAnime> = utils::fetch_json(&url)
+            .await?
+            .ok_or_else(|| Error::RustError("Jikan data not found".into()))?;
+        Ok(relations_graph(response.data))
+    }
+}
+
+// Jikan returns the whole cast and crew at once
+impl CreditsSource for JikanProvider {
+    async fn credits(&self, mal_id: &str, page: usize, per_page: usize) -> Result<CreditsPage> {
+        let characters_url = format!("https://api.jikan.moe/v4/anime/{}/characters", mal_id);
+        let staff_url = format!("https://api.jikan.moe/v4/anime/{}/staff", mal_id);
+
+        let (characters, staff) = futures::join!(
+            utils::fetch_json::<JikanResponse<Vec<JikanCharacterRole>>>(&characters_url),
+            utils::fetch_json::<JikanResponse<Vec<JikanStaff>>>(&staff_url)
+        );
+        let characters = characters?.map(|r| r.data).unwrap_or_default();
+        let staff = staff?.map(|r| r.data).unwrap_or_default();
+        Ok(CreditsPage::slice(
+            characters.into_iter().map(character_to_unified).collect(),
+            staff.into_iter().map(staff_to_unified).collect(),
+            page,
+            per_page,
+        ))
+    }
+}
+
+fn character_to_unified(role: JikanCharacterRole) -> UniversalCharacter {
+    // Dubs are listed too; the Japanese cast comes first where there is one
+    let voice_actor = role
+        .voice_actors
+        .iter()
+        .find(|va| va.language.as_deref() == Some("Japanese"))
+        .or_else(|| role.voice_actors.first());
+
+    UniversalCharacter {
+        name: role.character.name.clone(),
+        voice_actor: voice_actor.map(|va| va.person.name.clone()),
+        role: role.role,
+        image: role.character.image(),
+        voice_actor_image: voice_actor.and_then(|va| va.person.image()),
+        voice_actor_language: voice_actor.and_then(|va| va.language.clone()),
+    }
+}
+
+fn staff_to_unified(staff: JikanStaff) -> UniversalStaff {
+    UniversalStaff {
+        role: staff.positions.join(", "),
+        image: staff.person.image(),
+        name: staff.person.name,
+        department: None,
+    }
+}
+
+fn relations_graph(anime: JikanAnime) -> RelationGraph {
+    let image = anime.images.get("jpg").or_else(|| anime.images.get("webp"));
+    let root = RelationNode {
+        media_type: Some("anime".to_string()),
+        format: anime.type_field,
+        title: UniversalTitle {
+            romaji: Some(anime.title),
+            english: anime.title_english,
+            native: anime.title_japanese,
+            ..Default::default()
+        },
+        cover_image: UniversalCoverImage {
+            large: image.and_then(|i| i.large_image_url.clone()),
+            extra_large: image.and_then(|i| i.image_url.clone()),
+        },
+        year: anime.year.or_else(|| {
+            anime
+                .aired
+                .from
+                .as_deref()
+                .and_then(|d| d.get(0..4)?.parse().ok())
+        }),
+        episodes: anime.episodes,
+        ..RelationNode::new("mal", anime.mal_id.to_string())
+    };
+
+    let related = anime
+        .relations
+        .into_iter()
+        .flat_map(|r| {
+            let relation = RelationType::from_jikan(&r.relation);
+            r.entry.into_iter().map(move |entry| {
+                let mut node = RelationNode::new("mal", entry.mal_id.to_string());
+                // MAL numbers anime and manga separately
+                if entry.type_field != "anime" {
+                    node.id = format!("mal:{}:{}", entry.type_field, entry.mal_id);
+                }
+                node.media_type = Some(entry.type_field);
+                node.title.romaji = Some(entry.name);
+                (relation, node)
+            })
+        })
+        .collect();
+    RelationGraph::around(root, related)
+}
+
 pub async fn fetch_season(year: i32, season: &str) -> Result<Vec<Item>> {
     let url = format!("https://api.jikan.moe/v4/seasons/{}/{}", year, season);
     let response: Option<JikanResponse<Vec<JikanAnime>>> = utils::fetch_json(&url).await?;
@@ -104,14 +288,46 @@ pub async fn fetch_season(year: i32, season: &str) -> Result<Vec<Item>> {
     Ok(items)
 }
 
+fn item_type(type_field: Option<&str>) -> Option<ItemType> {
+    match type_field? {
+        "TV" => Some(ItemType::Tv),
+        "Movie" => Some(ItemType::Movie),
+        "OVA" => Some(ItemType::Ova),
+        "ONA" => Some(ItemType::Web),
+        _ => None,
+    }
+}
+
+fn jikan_candidate(anime: &JikanAnime) -> Candidate {
+    let mut titles = vec![anime.title.clone()];
+    titles.extend(
+        anime
+            .title_japanese
+            .iter()
+            .chain(&anime.title_english)
+            .cloned(),
+    );
+    titles.extend(anime.titles.iter().map(|t| t.title.clone()));
+    let image = anime.images.get("jpg").or_else(|| anime.images.get("webp"));
+
+    Candidate {
+        id: anime.mal_id.to_string(),
+        titles,
+        year: anime.year.or_else(|| {
+            anime
+                .aired
+                .from
+                .as_deref()
+                .and_then(|d| d.get(0..4)?.parse().ok())
+        }),
+        type_field: item_type(anime.type_field.as_deref()),
+        episodes: anime.episodes,
+        poster: image.and_then(|i| i.large_image_url.clone()),
+    }
+}
+
 fn convert_to_item(anime: JikanAnime) -> Item {
-    let type_field = match anime.type_field.as_deref() {
-        Some("TV") => ItemType::Tv,
-        Some("Movie") => ItemType::Movie,
-        Some("OVA") => ItemType::Ova,
-        Some("ONA") => ItemType::Web,
-        _ => ItemType::Tv,
-    };
+    let type_field = item_type(anime.type_field.as_deref()).unwrap_or_default();
 
     let sites = vec![Site {
         site: "mal".to_string(),
@@ -120,42 +336,36 @@ fn convert_to_item(anime: JikanAnime) -> Item {
         ..Default::default()
     }];
 
-    // Prefer Japanese title as main title
-    let (title, title_translate) = if let Some(ja_title) = &anime.title_japanese {
-        if !ja_title.is_empty() {
-            // Main title is Japanese
-            let mut en = anime.title_english.map(|t| vec![t]).unwrap_or_default();
-            // If the default 'title' is different from Japanese title, add it to English/Romaji fallback
-            // Jikan's 'title' is usually Romaji.
-            if &anime.title != ja_title {
-                en.push(anime.title);
-            }
-            (
-                ja_title.clone(),
-                TitleTranslate {
-                    en: if en.is_empty() { None } else { Some(en) },
-                    ja: Some(vec![ja_title.clone()]),
-                    ..Default::default()
-                },
-            )
-        } else {
-            // Fallback to default title
-            (
-                anime.title.clone(),
-                TitleTranslate {
-                    en: anime.title_english.map(|t| vec![t]),
-                    ..Default::default()
-                },
-            )
-        }
-    } else {
-        (
-            anime.title.clone(),
-            TitleTranslate {
-                en: anime.title_english.map(|t| vec![t]),
-                ..Default::default()
-            },
-        )
+    // Titles are sorted by their type rather than assumed Japanese. "Default"
+    // is MAL's romanization, which goes with the English titles.
+    let mut ja: Vec<String> = anime.title_japanese.into_iter().collect();
+    let mut en: Vec<String> = anime.title_english.into_iter().collect();
+    for t in anime.titles {
+        let list = match t.type_field.as_str() {
+            "Japanese" => &mut ja,
+            "English" | "Default" => &mut en,
+            _ => continue,
+        };
+        list.push(t.title);
+    }
+
+    // Prefer a Japanese title as the main one, else the romanization
+    let (title, main_is_japanese) = match ja.iter().find(|t| !t.trim().is_empty()) {
+        Some(title) => (title.clone(), true),
+        None => (anime.title.clone(), false),
+    };
+    en.push(anime.title);
+    for list in [&mut ja, &mut en] {
+        let mut seen = HashSet::new();
+        list.retain(|t| !t.trim().is_empty() && seen.insert(t.clone()));
+    }
+    if !main_is_japanese {
+        en.retain(|t| *t != title);
+    }
+    let title_translate = TitleTranslate {
+        ja: Some(ja).filter(|t| !t.is_empty()),
+        en: Some(en).filter(|t| !t.is_empty()),
+        ..Default::default()
     };
 
     // Strip HTML tags from synopsis
@@ -168,6 +378,8 @@ fn convert_to_item(anime: JikanAnime) -> Item {
         title,
         title_translate,
         type_field,
+        // Seasonal anime on MAL are Japanese productions, whatever the main
+        // title is
         lang: Language::Ja,
         official_site: anime.url,
         begin: anime.aired.from,
@@ -175,6 +387,22 @@ fn convert_to_item(anime: JikanAnime) -> Item {
         comment,
         sites,
         broadcast: anime.broadcast.and_then(|b| b.string),
+        // Jikan's broadcast is prose like "Saturdays at 23:00 (JST)", not an ISO interval
+        broadcast_schedule: None,
+        next_episode: None,
+        display_title: None,
+    }
+}
+
+// Jikan tags titles by kind ("Default", "Synonym") or by language name
+fn jikan_title_language(type_field: &str) -> Option<&'static str> {
+    match type_field {
+        "Japanese" => Some("ja"),
+        "English" => Some("en"),
+        "German" => Some("de"),
+        "Spanish" => Some("es"),
+        "French" => Some("fr"),
+        _ => None,
     }
 }
 
@@ -187,13 +415,24 @@ fn convert_to_metadata(anime: JikanAnime) -> UnifiedMetadata {
         regex.replace_all(&s, "").to_string()
     });
 
+    let mut title = UniversalTitle {
+        romaji: Some(anime.title),
+        english: anime.title_english,
+        native: anime.title_japanese,
+        season: None,
+        alternatives: Vec::new(),
+    };
+    for t in anime.titles {
+        title.add_alternative(AlternativeTitle {
+            language: jikan_title_language(&t.type_field).map(Into::into),
+            title: t.title,
+            region: None,
+        });
+    }
+
     UnifiedMetadata {
         id: anime.mal_id.to_string(),
-        title: UniversalTitle {
-            romaji: Some(anime.title),
-            english: anime.title_english,
-            native: anime.title_japanese,
-        },
+        title,
         cover_image: UniversalCoverImage {
             large: image.and_then(|i| i.large_image_url.clone()),
             extra_large: image.and_then(|i| i.image_url.clone()), // Fallback
@@ -201,6 +440,7 @@ fn convert_to_metadata(anime: JikanAnime) -> UnifiedMetadata {
         average_score: anime.score.map(|s| (s * 10.0) as i32),
         episodes: anime.episodes,
         genres: anime.genres.into_iter().map(|g| g.name).collect(),
+        description_language: description.as_ref().map(|_| "en".to_string()),
         description,
         studios: anime.studios.into_iter().map(|s| s.name).collect(),
         characters: vec![],
@@ -211,5 +451,11 @@ fn convert_to_metadata(anime: JikanAnime) -> UnifiedMetadata {
         current_season: None,
         runtime: None,
         content_rating: None,
+        next_episode: None,
+        match_confidence: None,
+        external_ids: Default::default(),
+        external_links: Vec::new(),
+        streaming_episodes: Vec::new(),
+        sources: Default::default(),
     }
 }
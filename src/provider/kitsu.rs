@@ -0,0 +1,478 @@
+use super::MetadataProvider;
+use super::scoring::{Candidate, MatchQuery};
+use crate::model::{
+    AlternativeTitle, ItemType, UnifiedMetadata, UniversalCoverImage, UniversalEpisode,
+    UniversalTitle,
+};
+use crate::{config, utils};
+use serde_derive::Deserialize;
+use std::collections::{BTreeMap, HashMap};
+use worker::*;
+
+pub struct KitsuProvider;
+
+// JSON:API top level document
+#[derive(Debug, Deserialize)]
+struct Document<T> {
+    data: T,
+    #[serde(default)]
+    included: Vec<Included>,
+    #[serde(default)]
+    links: Links,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct Links {
+    next: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Resource<A> {
+    id: String,
+    attributes: A,
+}
+
+// Resources pulled in with `include`, told apart by `type`
+#[derive(Debug, Deserialize)]
+struct Included {
+    #[serde(rename = "type")]
+    type_field: String,
+    id: String,
+    #[serde(default)]
+    attributes: serde_json::Value,
+    #[serde(default)]
+    relationships: HashMap<String, Relationship>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Relationship {
+    #[serde(default)]
+    data: serde_json::Value,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct KitsuAnime {
+    canonical_title: Option<String>,
+    // Keyed like "en", "en_jp" (romaji) or "ja_jp"; BTreeMap for a stable order
+    #[serde(default)]
+    titles: BTreeMap<String, Option<String>>,
+    abbreviated_titles: Option<Vec<String>>,
+    synopsis: Option<String>,
+    /// A percentage, as a string like "82.01".
+    average_rating: Option<String>,
+    start_date: Option<String>,
+    status: Option<String>,
+    poster_image: Option<KitsuImage>,
+    episode_count: Option<i32>,
+    episode_length: Option<i32>,
+    subtype: Option<String>,
+    age_rating: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct KitsuImage {
+    large: Option<String>,
+    original: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct KitsuEpisode {
+    number: Option<i32>,
+    canonical_title: Option<String>,
+    synopsis: Option<String>,
+    airdate: Option<String>,
+    length: Option<i32>,
+    thumbnail: Option<KitsuImage>,
+}
+
+impl MetadataProvider for KitsuProvider {
+    async fn search(&self, query: &MatchQuery) -> Result<Vec<Candidate>> {
+        let title = query
+            .primary_title()
+            .ok_or_else(|| Error::RustError("Title required".into()))?;
+        let url = Url::parse_with_params(
+            &format!("{}/anime", config::KITSU_API_BASE),
+            &[
+                ("filter[text]", title.to_string()),
+                ("page[limit]", config::METADATA_SEARCH_LIMIT.to_string()),
+            ],
+        )
+        .map_err(|e| Error::RustError(format!("Invalid Kitsu search URL: {}", e)))?;
+
+        let response: Option<Document<Vec<Resource<KitsuAnime>>>> =
+            utils::fetch_json(url.as_str()).await?;
+        Ok(response
+            .map(|r| r.data.iter().map(kitsu_candidate).collect())
+            .unwrap_or_default())
+    }
+
+    async fn fetch_by_id(&self, id: &str) -> Result<UnifiedMetadata> {
+        // Kitsu IDs are numeric; anything else would change the path
+        if id.is_empty() || !id.bytes().all(|b| b.is_ascii_digit()) {
+            return Err(Error::RustError(format!("Invalid Kitsu ID: {}", id)));
+        }
+        let url = format!(
+            "{}/anime/{}?include=categories,animeProductions.producer",
+            config::KITSU_API_BASE,
+            id
+        );
+
+        let (anime, episodes) = futures::join!(
+            utils::fetch_json::<Document<Resource<KitsuAnime>>>(&url),
+            fetch_episodes(id)
+        );
+        let anime = anime?.ok_or_else(|| Error::RustError("Kitsu data not found".into()))?;
+        Ok(kitsu_to_unified(anime.data, &anime.included, episodes?))
+    }
+}
+
+// Episodes come 20 to a page; follow `next` up to the configured number of
+// pages to stay within the subrequest limit
+async fn fetch_episodes(id: &str) -> Result<Vec<Resource<KitsuEpisode>>> {
+    let mut episodes = Vec::new();
+    let mut next = Some(format!(
+        "{}/anime/{}/episodes?page[limit]=20&sort=number",
+        config::KITSU_API_BASE,
+        id
+    ));
+
+    for _ in 0..config::KITSU_EPISODE_PAGES {
+        let Some(url) = next.take() else { break };
+        let Some(page): Option<Document<Vec<Resource<KitsuEpisode>>>> =
+            utils::fetch_json(&url).await?
+        else {
+            break;
+        };
+        episodes.extend(page.data);
+        next = page.links.next;
+    }
+    Ok(episodes)
+}
+
+fn item_type(subtype: Option<&str>) -> Option<ItemType> {
+    match subtype? {
+        "TV" => Some(ItemType::Tv),
+        "movie" => Some(ItemType::Movie),
+        "OVA" | "special" => Some(ItemType::Ova),
+        "ONA" => Some(ItemType::Web),
+        _ => None,
+    }
+}
+
+fn non_empty(s: Option<String>) -> Option<String> {
+    s.filter(|s| !s.trim().is_empty())
+}
+
+fn kitsu_candidate(anime: &Resource<KitsuAnime>) -> Candidate {
+    let attributes = &anime.attributes;
+    let titles = attributes
+        .canonical_title
+        .iter()
+        .chain(attributes.titles.values().flatten())
+        .chain(attributes.abbreviated_titles.iter().flatten())
+        .filter(|t| !t.is_empty())
+        .fold(Vec::new(), |mut titles: Vec<String>, t| {
+            if !titles.contains(t) {
+                titles.push(t.clone());
+            }
+            titles
+        });
+
+    Candidate {
+        id: anime.id.clone(),
+        titles,
+        year: attributes
+            .start_date
+            .as_deref()
+            .and_then(|d| d.get(0..4)?.parse().ok()),
+        type_field: item_type(attributes.subtype.as_deref()),
+        episodes: attributes.episode_count,
+        poster: attributes
+            .poster_image
+            .as_ref()
+            .and_then(|i| i.large.clone()),
+    }
+}
+
+fn kitsu_to_unified(
+    anime: Resource<KitsuAnime>,
+    included: &[Included],
+    episodes: Vec<Resource<KitsuEpisode>>,
+) -> UnifiedMetadata {
+    let attributes = anime.attributes;
+    let mut titles = attributes.titles;
+    let mut take = |key: &str| non_empty(titles.remove(key).flatten());
+
+    let mut title = UniversalTitle {
+        romaji: take("en_jp"),
+        english: take("en").or_else(|| take("en_us")),
+        native: take("ja_jp"),
+        season: None,
+        alternatives: Vec::new(),
+    };
+    for (key, other) in titles {
+        let Some(other) = non_empty(other) else {
+            continue;
+        };
+        // Keys are a language and a region, e.g. "zh_cn"
+        let (language, region) = key.split_once('_').unwrap_or((&key, ""));
+        title.add_alternative(AlternativeTitle {
+            title: other,
+            language: Some(language.to_string()),
+            region: (!region.is_empty()).then(|| region.to_uppercase()),
+        });
+    }
+    for other in attributes
+        .canonical_title
+        .into_iter()
+        .chain(attributes.abbreviated_titles.unwrap_or_default())
+    {
+        title.add_alternative(AlternativeTitle {
+            title: other,
+            ..Default::default()
+        });
+    }
+
+    let attribute = |resource: &Included, name: &str| {
+        resource
+            .attributes
+            .get(name)
+            .and_then(|v| v.as_str())
+            .map(str::to_string)
+    };
+    let genres = included
+        .iter()
+        .filter(|i| i.type_field == "categories")
+        .filter_map(|i| attribute(i, "title"))
+        .collect();
+    let producer_name = |id: &str| {
+        included
+            .iter()
+            .find(|i| i.type_field == "producers" && i.id == id)
+            .and_then(|i| attribute(i, "name"))
+    };
+    let studios = included
+        .iter()
+        .filter(|i| i.type_field == "animeProductions")
+        .filter(|i| attribute(i, "role").as_deref() == Some("studio"))
+        .filter_map(|i| i.relationships.get("producer")?.data.get("id")?.as_str())
+        .filter_map(producer_name)
+        .collect();
+
+    let episodes_list = episodes
+        .into_iter()
+        .filter_map(|e| {
+            let e = e.attributes;
+            Some(UniversalEpisode {
+                number: e.number?,
+                title: non_empty(e.canonical_title),
+                air_date: e.airdate,
+                overview: non_empty(e.synopsis),
+                runtime: e.length,
+                thumbnail: e.thumbnail.and_then(|t| t.original),
+                titles: Vec::new(),
+            })
+        })
+        .collect();
+
+    let poster = attributes.poster_image;
+    let description = non_empty(attributes.synopsis);
+
+    UnifiedMetadata {
+        id: anime.id,
+        title,
+        cover_image: UniversalCoverImage {
+            large: poster.as_ref().and_then(|p| p.large.clone()),
+            extra_large: poster.and_then(|p| p.original),
+        },
+        average_score: attributes
+            .average_rating
+            .and_then(|r| r.parse::<f64>().ok())
+            .map(|r| r.round() as i32),
+        episodes: attributes.episode_count,
+        genres,
+        description_language: description.as_ref().map(|_| "en".to_string()),
+        description,
+        studios,
+        characters: vec![],
+        staff: vec![],
+        episodes_list,
+        is_finished: attributes.status.as_deref() == Some("finished"),
+        total_seasons: None,
+        current_season: None,
+        runtime: attributes.episode_length,
+        content_rating: attributes.age_rating,
+        next_episode: None,
+        match_confidence: None,
+        external_ids: Default::default(),
+        external_links: Vec::new(),
+        streaming_episodes: Vec::new(),
+        sources: Default::default(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn anime_document() -> Document<Resource<KitsuAnime>> {
+        serde_json::from_value(json!({
+            "data": {
+                "id": "46474",
+                "type": "anime",
+                "attributes": {
+                    "canonicalTitle": "Sousou no Frieren",
+                    "titles": {
+                        "en": "Frieren: Beyond Journey's End",
+                        "en_jp": "Sousou no Frieren",
+                        "ja_jp": "葬送のフリーレン",
+                        "zh_cn": "葬送的芙莉莲"
+                    },
+                    "abbreviatedTitles": ["Frieren at the Funeral"],
+                    "synopsis": "The adventure is over but life goes on.",
+                    "averageRating": "87.46",
+                    "startDate": "2023-09-29",
+                    "status": "finished",
+                    "posterImage": {
+                        "large": "https://media.kitsu.app/anime/46474/poster_image/large.jpg",
+                        "original": "https://media.kitsu.app/anime/46474/poster_image/original.jpg"
+                    },
+                    "episodeCount": 28,
+                    "episodeLength": 24,
+                    "subtype": "TV",
+                    "ageRating": "PG"
+                }
+            },
+            "included": [
+                { "id": "150", "type": "categories", "attributes": { "title": "Fantasy" } },
+                { "id": "1", "type": "animeProductions",
+                  "attributes": { "role": "studio" },
+                  "relationships": { "producer": { "data": { "type": "producers", "id": "7" } } } },
+                { "id": "2", "type": "animeProductions",
+                  "attributes": { "role": "producer" },
+                  "relationships": { "producer": { "data": { "type": "producers", "id": "8" } } } },
+                { "id": "7", "type": "producers", "attributes": { "name": "Madhouse" } },
+                { "id": "8", "type": "producers", "attributes": { "name": "Aniplex" } }
+            ]
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_kitsu_to_unified_full() {
+        let episodes: Document<Vec<Resource<KitsuEpisode>>> = serde_json::from_value(json!({
+            "data": [
+                { "id": "1", "type": "episodes", "attributes": {
+                    "number": 1,
+                    "canonicalTitle": "The Journey's End",
+                    "synopsis": "",
+                    "airdate": "2023-09-29",
+                    "length": 24,
+                    "thumbnail": { "original": "https://media.kitsu.app/episodes/thumbnails/1/original.jpg" }
+                } },
+                { "id": "2", "type": "episodes", "attributes": {
+                    "number": null, "canonicalTitle": "Recap", "airdate": null, "length": null, "thumbnail": null
+                } }
+            ],
+            "links": { "next": "https://kitsu.app/api/edge/anime/46474/episodes?page%5Boffset%5D=20" }
+        }))
+        .unwrap();
+        assert!(episodes.links.next.is_some());
+
+        let anime = anime_document();
+        let unified = kitsu_to_unified(anime.data, &anime.included, episodes.data);
+
+        assert_eq!(unified.id, "46474");
+        assert_eq!(unified.title.romaji.as_deref(), Some("Sousou no Frieren"));
+        assert_eq!(
+            unified.title.english.as_deref(),
+            Some("Frieren: Beyond Journey's End")
+        );
+        assert_eq!(unified.title.native.as_deref(), Some("葬送のフリーレン"));
+        assert_eq!(
+            unified.title.alternatives,
+            vec![
+                AlternativeTitle {
+                    title: "葬送的芙莉莲".into(),
+                    language: Some("zh".into()),
+                    region: Some("CN".into()),
+                },
+                AlternativeTitle {
+                    title: "Frieren at the Funeral".into(),
+                    ..Default::default()
+                },
+            ]
+        );
+        assert_eq!(unified.average_score, Some(87));
+        assert_eq!(unified.genres, vec!["Fantasy"]);
+        assert_eq!(unified.studios, vec!["Madhouse"]);
+        assert_eq!(unified.description_language.as_deref(), Some("en"));
+        assert!(unified.is_finished);
+        assert_eq!(unified.runtime, Some(24));
+        assert_eq!(unified.content_rating.as_deref(), Some("PG"));
+
+        // Episodes without a number are left out
+        assert_eq!(unified.episodes_list.len(), 1);
+        let episode = &unified.episodes_list[0];
+        assert_eq!(episode.title.as_deref(), Some("The Journey's End"));
+        assert_eq!(episode.air_date.as_deref(), Some("2023-09-29"));
+        assert_eq!(episode.overview, None);
+        assert_eq!(
+            episode.thumbnail.as_deref(),
+            Some("https://media.kitsu.app/episodes/thumbnails/1/original.jpg")
+        );
+    }
+
+    #[test]
+    fn test_kitsu_to_unified_minimal() {
+        let anime: Document<Resource<KitsuAnime>> = serde_json::from_value(json!({
+            "data": { "id": "1", "type": "anime", "attributes": {
+                "canonicalTitle": "Cowboy Bebop",
+                "titles": { "en_jp": "Cowboy Bebop", "ja_jp": null },
+                "abbreviatedTitles": null,
+                "synopsis": null,
+                "averageRating": null,
+                "startDate": null,
+                "status": "current",
+                "posterImage": null,
+                "episodeCount": null,
+                "episodeLength": null,
+                "subtype": "TV",
+                "ageRating": null
+            } }
+        }))
+        .unwrap();
+
+        let unified = kitsu_to_unified(anime.data, &anime.included, vec![]);
+        assert_eq!(unified.title.romaji.as_deref(), Some("Cowboy Bebop"));
+        assert_eq!(unified.title.native, None);
+        assert!(unified.title.alternatives.is_empty());
+        assert_eq!(unified.description, None);
+        assert_eq!(unified.cover_image.large, None);
+        assert!(unified.episodes_list.is_empty());
+        assert!(!unified.is_finished);
+    }
+
+    #[test]
+    fn test_kitsu_candidate() {
+        let anime = anime_document();
+        let candidate = kitsu_candidate(&anime.data);
+        assert_eq!(candidate.id, "46474");
+        assert_eq!(
+            candidate.titles,
+            vec![
+                "Sousou no Frieren",
+                "Frieren: Beyond Journey's End",
+                "葬送のフリーレン",
+                "葬送的芙莉莲",
+                "Frieren at the Funeral"
+            ]
+        );
+        assert_eq!(candidate.year, Some(2023));
+        assert_eq!(candidate.type_field, Some(ItemType::Tv));
+        assert_eq!(candidate.episodes, Some(28));
+    }
+}
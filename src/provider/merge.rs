@@ -0,0 +1,236 @@
+use crate::model::{AlternativeTitle, UnifiedMetadata, UniversalTitle};
+use std::collections::BTreeMap;
+
+// Fields some provider is known to fill best, tried before the usual order
+fn preferred(field: &str) -> &'static [&'static str] {
+    match field {
+        // AniList lists each character's role and voice actor
+        "characters" => &["anilist"],
+        // TMDb titles follow the requested language; these keep the original
+        "title.native" => &["anilist", "mal"],
+        _ => &[],
+    }
+}
+
+/// Combines results from several providers into one, filling each field
+/// from the first provider that has it. `results` are in priority order and
+/// the first one supplies `id`; `sources` records where each field came from.
+pub fn merge(results: Vec<(&'static str, UnifiedMetadata)>) -> Option<UnifiedMetadata> {
+    let (primary, first) = results.first()?;
+    let mut picker = Picker {
+        results: &results,
+        sources: BTreeMap::from([("id".to_string(), primary.to_string())]),
+    };
+
+    let mut title = UniversalTitle {
+        romaji: picker.pick("title.romaji", |m| m.title.romaji.clone()),
+        english: picker.pick("title.english", |m| m.title.english.clone()),
+        native: picker.pick("title.native", |m| m.title.native.clone()),
+        season: picker.pick("title.season", |m| m.title.season.clone()),
+        alternatives: Vec::new(),
+    };
+    let cover_image = picker.pick("coverImage", |m| {
+        m.cover_image.large.as_ref().map(|_| m.cover_image.clone())
+    });
+    let description = picker.pick("description", |m| {
+        Some((m.description.clone()?, m.description_language.clone()))
+    });
+
+    let mut merged = UnifiedMetadata {
+        id: first.id.clone(),
+        cover_image: cover_image.unwrap_or_default(),
+        average_score: picker.pick("averageScore", |m| m.average_score),
+        episodes: picker.pick("episodes", |m| m.episodes),
+        genres: picker
+            .pick("genres", |m| non_empty(&m.genres))
+            .unwrap_or_default(),
+        description_language: description.as_ref().and_then(|(_, l)| l.clone()),
+        description: description.map(|(d, _)| d),
+        studios: picker
+            .pick("studios", |m| non_empty(&m.studios))
+            .unwrap_or_default(),
+        characters: picker
+            .pick("characters", |m| non_empty(&m.characters))
+            .unwrap_or_default(),
+        staff: picker
+            .pick("staff", |m| non_empty(&m.staff))
+            .unwrap_or_default(),
+        episodes_list: picker
+            .pick("episodesList", |m| non_empty(&m.episodes_list))
+            .unwrap_or_default(),
+        is_finished: first.is_finished,
+        total_seasons: picker.pick("totalSeasons", |m| m.total_seasons),
+        current_season: picker.pick("currentSeason", |m| m.current_season),
+        runtime: picker.pick("runtime", |m| m.runtime),
+        content_rating: picker.pick("contentRating", |m| m.content_rating.clone()),
+        external_links: picker
+            .pick("externalLinks", |m| non_empty(&m.external_links))
+            .unwrap_or_default(),
+        streaming_episodes: picker
+            .pick("streamingEpisodes", |m| non_empty(&m.streaming_episodes))
+            .unwrap_or_default(),
+        next_episode: None,
+        // The least certain search result among those merged
+        match_confidence: results
+            .iter()
+            .filter_map(|(_, m)| m.match_confidence)
+            .reduce(f64::min),
+        ..Default::default()
+    };
+
+    // Every other title any provider gave is kept for matching
+    for (_, m) in &results {
+        let t = &m.title;
+        for other in [&t.romaji, &t.english, &t.native].into_iter().flatten() {
+            title.add_alternative(AlternativeTitle {
+                title: other.clone(),
+                ..Default::default()
+            });
+        }
+        for alternative in &t.alternatives {
+            title.add_alternative(alternative.clone());
+        }
+        // So are their IDs on other sites, the first provider's winning
+        for (site, id) in &m.external_ids {
+            merged
+                .external_ids
+                .entry(site.clone())
+                .or_insert_with(|| id.clone());
+        }
+    }
+    merged.title = title;
+    merged.sources = picker.sources;
+    Some(merged)
+}
+
+struct Picker<'a> {
+    results: &'a [(&'static str, UnifiedMetadata)],
+    sources: BTreeMap<String, String>,
+}
+
+impl Picker<'_> {
+    fn pick<T>(&mut self, field: &str, get: impl Fn(&UnifiedMetadata) -> Option<T>) -> Option<T> {
+        let preferred = preferred(field)
+            .iter()
+            .filter_map(|p| self.results.iter().find(|(name, _)| name == p));
+        let (provider, value) = preferred
+            .chain(self.results)
+            .find_map(|(provider, m)| Some((provider, get(m)?)))?;
+        self.sources.insert(field.to_string(), provider.to_string());
+        Some(value)
+    }
+}
+
+fn non_empty<T: Clone>(v: &[T]) -> Option<Vec<T>> {
+    (!v.is_empty()).then(|| v.to_vec())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::{UniversalCharacter, UniversalCoverImage, UniversalEpisode, UniversalStaff};
+
+    fn tmdb() -> UnifiedMetadata {
+        UnifiedMetadata {
+            id: "tv/209867/season/1".into(),
+            title: UniversalTitle {
+                native: Some("Frieren: Beyond Journey's End".into()),
+                season: Some("Season 1".into()),
+                ..Default::default()
+            },
+            cover_image: UniversalCoverImage {
+                large: Some("https://image.tmdb.org/t/p/w500/a.jpg".into()),
+                ..Default::default()
+            },
+            description: Some("TMDb overview".into()),
+            description_language: Some("ja".into()),
+            staff: vec![UniversalStaff {
+                name: "Keiichiro Saito".into(),
+                role: "Director".into(),
+                department: Some("Directing".into()),
+                ..Default::default()
+            }],
+            episodes_list: vec![UniversalEpisode {
+                number: 1,
+                ..Default::default()
+            }],
+            characters: vec![UniversalCharacter {
+                name: "Frieren".into(),
+                voice_actor: Some("Atsumi Tanezaki".into()),
+                role: Some("Cast".into()),
+                ..Default::default()
+            }],
+            current_season: Some(1),
+            ..Default::default()
+        }
+    }
+
+    fn anilist() -> UnifiedMetadata {
+        UnifiedMetadata {
+            id: "154587".into(),
+            title: UniversalTitle {
+                romaji: Some("Sousou no Frieren".into()),
+                native: Some("葬送のフリーレン".into()),
+                ..Default::default()
+            },
+            average_score: Some(91),
+            genres: vec!["Adventure".into()],
+            description: Some("AniList description".into()),
+            description_language: Some("en".into()),
+            characters: vec![UniversalCharacter {
+                name: "Frieren".into(),
+                voice_actor: Some("Atsumi Tanezaki".into()),
+                role: Some("MAIN".into()),
+                ..Default::default()
+            }],
+            is_finished: true,
+            match_confidence: Some(0.8),
+            external_ids: BTreeMap::from([
+                ("anilist".into(), "154587".into()),
+                ("mal".into(), "52991".into()),
+            ]),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_merge_fills_fields_from_best_source() {
+        let merged = merge(vec![("tmdb", tmdb()), ("anilist", anilist())]).unwrap();
+
+        assert_eq!(merged.id, "tv/209867/season/1");
+        assert_eq!(merged.title.native.as_deref(), Some("葬送のフリーレン"));
+        assert_eq!(merged.title.romaji.as_deref(), Some("Sousou no Frieren"));
+        assert_eq!(merged.title.season.as_deref(), Some("Season 1"));
+        assert_eq!(
+            merged.title.alternatives[0].title,
+            "Frieren: Beyond Journey's End"
+        );
+        assert_eq!(merged.description.as_deref(), Some("TMDb overview"));
+        assert_eq!(merged.description_language.as_deref(), Some("ja"));
+        assert_eq!(merged.average_score, Some(91));
+        assert_eq!(merged.characters[0].role.as_deref(), Some("MAIN"));
+        assert_eq!(merged.staff.len(), 1);
+        assert!(!merged.is_finished);
+        assert_eq!(merged.match_confidence, Some(0.8));
+        assert_eq!(
+            merged.external_ids.get("mal").map(String::as_str),
+            Some("52991")
+        );
+
+        let source = |field: &str| merged.sources.get(field).map(String::as_str);
+        assert_eq!(source("id"), Some("tmdb"));
+        assert_eq!(source("title.native"), Some("anilist"));
+        assert_eq!(source("characters"), Some("anilist"));
+        assert_eq!(source("episodesList"), Some("tmdb"));
+        assert_eq!(source("genres"), Some("anilist"));
+        assert_eq!(source("totalSeasons"), None);
+    }
+
+    #[test]
+    fn test_merge_single_and_empty() {
+        let merged = merge(vec![("anilist", anilist())]).unwrap();
+        assert_eq!(merged.id, "154587");
+        assert!(merged.is_finished);
+        assert!(merge(Vec::new()).is_none());
+    }
+}
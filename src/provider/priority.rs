@@ -0,0 +1,109 @@
+/// A metadata provider, as named in `METADATA_PROVIDERS`, the `providers`
+/// parameter and the `sources` of merged metadata.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProviderKind {
+    Tmdb,
+    Mal,
+    Kitsu,
+    Bangumi,
+    Anidb,
+    Anilist,
+}
+
+impl ProviderKind {
+    pub const ALL: [ProviderKind; 6] = [
+        ProviderKind::Tmdb,
+        ProviderKind::Mal,
+        ProviderKind::Kitsu,
+        ProviderKind::Bangumi,
+        ProviderKind::Anidb,
+        ProviderKind::Anilist,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ProviderKind::Tmdb => "tmdb",
+            ProviderKind::Mal => "mal",
+            ProviderKind::Kitsu => "kitsu",
+            ProviderKind::Bangumi => "bangumi",
+            ProviderKind::Anidb => "anidb",
+            ProviderKind::Anilist => "anilist",
+        }
+    }
+
+    /// Whether `/api/metadata` only uses this provider for an ID it has,
+    /// never to search by title or in a merged lookup. AniDB bans clients
+    /// that request too much.
+    pub fn by_id_only(&self) -> bool {
+        matches!(self, ProviderKind::Anidb)
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        let name = name.trim();
+        Self::ALL
+            .into_iter()
+            .find(|p| p.name().eq_ignore_ascii_case(name))
+    }
+}
+
+/// Parses a comma separated provider list, keeping the first occurrence of
+/// each. An empty list is allowed and disables every provider.
+pub fn parse(list: &str) -> Result<Vec<ProviderKind>, String> {
+    let mut providers = Vec::new();
+    for name in list.split(',').map(str::trim).filter(|n| !n.is_empty()) {
+        let provider = ProviderKind::from_name(name).ok_or_else(|| {
+            let known: Vec<_> = ProviderKind::ALL.iter().map(|p| p.name()).collect();
+            format!(
+                "unknown provider '{}', expected one of {}",
+                name,
+                known.join(", ")
+            )
+        })?;
+        if !providers.contains(&provider) {
+            providers.push(provider);
+        }
+    }
+    Ok(providers)
+}
+
+/// The order to use for one request: `requested` when given, leaving out
+/// providers the deployment hasn't enabled, otherwise `enabled` as is.
+pub fn select(enabled: &[ProviderKind], requested: Option<&[ProviderKind]>) -> Vec<ProviderKind> {
+    match requested {
+        Some(requested) => requested
+            .iter()
+            .filter(|p| enabled.contains(p))
+            .copied()
+            .collect(),
+        None => enabled.to_vec(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        use ProviderKind::*;
+        assert_eq!(
+            parse("tmdb,mal,kitsu,anilist"),
+            Ok(vec![Tmdb, Mal, Kitsu, Anilist])
+        );
+        assert_eq!(parse(" AniList , mal,anilist,"), Ok(vec![Anilist, Mal]));
+        assert_eq!(parse(""), Ok(vec![]));
+        assert!(parse("tmdb,imdb").is_err());
+    }
+
+    #[test]
+    fn test_select() {
+        use ProviderKind::*;
+        let enabled = [Mal, Anilist];
+        assert_eq!(select(&enabled, None), vec![Mal, Anilist]);
+        assert_eq!(
+            select(&enabled, Some(&[Anilist, Tmdb, Mal])),
+            vec![Anilist, Mal]
+        );
+        assert_eq!(select(&enabled, Some(&[])), vec![]);
+    }
+}
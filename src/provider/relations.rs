@@ -0,0 +1,378 @@
+use crate::model::{UniversalCoverImage, UniversalTitle};
+use serde_derive::{Deserialize, Serialize};
+use std::collections::{HashSet, VecDeque};
+use worker::*;
+
+/// How a work relates to another, as AniList's `MediaRelation` names it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum RelationType {
+    Prequel,
+    Sequel,
+    Parent,
+    SideStory,
+    SpinOff,
+    Alternative,
+    Summary,
+    FullStory,
+    Source,
+    Adaptation,
+    Character,
+    Compilation,
+    Contains,
+    #[serde(other)]
+    Other,
+}
+
+impl RelationType {
+    /// Reads a relation from Jikan, which spells them out in words.
+    pub fn from_jikan(relation: &str) -> Self {
+        match relation {
+            "Prequel" => Self::Prequel,
+            "Sequel" => Self::Sequel,
+            "Parent Story" => Self::Parent,
+            "Side Story" => Self::SideStory,
+            "Spin-Off" => Self::SpinOff,
+            "Alternative Version" | "Alternative Setting" => Self::Alternative,
+            "Summary" => Self::Summary,
+            "Full Story" => Self::FullStory,
+            // On an anime, Jikan's adaptations are the works it adapts
+            "Adaptation" => Self::Source,
+            "Character" => Self::Character,
+            _ => Self::Other,
+        }
+    }
+
+    /// The relation seen from the other end, when there is one.
+    fn inverse(self) -> Option<Self> {
+        match self {
+            Self::Prequel => Some(Self::Sequel),
+            Self::Sequel => Some(Self::Prequel),
+            Self::Parent => Some(Self::SideStory),
+            Self::SideStory => Some(Self::Parent),
+            Self::Summary => Some(Self::FullStory),
+            Self::FullStory => Some(Self::Summary),
+            Self::Source => Some(Self::Adaptation),
+            Self::Adaptation => Some(Self::Source),
+            _ => None,
+        }
+    }
+}
+
+/// A work in the graph, summarized.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RelationNode {
+    /// Unique within the graph, usually `provider:providerId`.
+    pub id: String,
+    pub provider: &'static str,
+    /// The ID to pass to `/api/metadata` as `anilist_id`, `mal_id` or
+    /// `tmdb_id`.
+    pub provider_id: String,
+    /// `anime` or `manga` (which includes novels), where the provider says.
+    pub media_type: Option<String>,
+    pub format: Option<String>,
+    pub title: UniversalTitle,
+    pub cover_image: UniversalCoverImage,
+    pub year: Option<i32>,
+    pub episodes: Option<i32>,
+}
+
+impl RelationNode {
+    pub fn new(provider: &'static str, provider_id: impl Into<String>) -> Self {
+        let provider_id = provider_id.into();
+        Self {
+            id: format!("{}:{}", provider, provider_id),
+            provider,
+            provider_id,
+            ..Default::default()
+        }
+    }
+}
+
+/// `to` is the `relation` of `from`: a `SEQUEL` edge points at the sequel.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RelationEdge {
+    pub from: String,
+    pub to: String,
+    pub relation: RelationType,
+}
+
+#[derive(Debug, Default, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RelationGraph {
+    /// Node the graph was built around.
+    pub root: String,
+    pub nodes: Vec<RelationNode>,
+    pub edges: Vec<RelationEdge>,
+    /// The chain of prequels and sequels through `root`, first to last.
+    pub watch_order: Vec<String>,
+}
+
+impl RelationGraph {
+    /// A graph of `root` and the works directly related to it.
+    pub fn around(root: RelationNode, related: Vec<(RelationType, RelationNode)>) -> Self {
+        let root_id = root.id.clone();
+        let mut graph = Self {
+            root: root_id.clone(),
+            nodes: vec![root],
+            ..Default::default()
+        };
+        for (relation, node) in related {
+            graph.add_edge(&root_id, relation, &node.id);
+            graph.add_node(node, false);
+        }
+        graph
+    }
+
+    /// Adds `node`. A `fetched` node, the work's own copy rather than a
+    /// neighbour's summary of it, replaces any summary already there; the
+    /// root keeps the copy the graph was built from.
+    fn add_node(&mut self, node: RelationNode, fetched: bool) {
+        match self.nodes.iter_mut().find(|n| n.id == node.id) {
+            Some(existing) if fetched && existing.id != self.root => *existing = node,
+            Some(_) => {}
+            None => self.nodes.push(node),
+        }
+    }
+
+    /// Adds an edge unless it, or the same edge seen from `to`, is known.
+    pub fn add_edge(&mut self, from: &str, relation: RelationType, to: &str) {
+        let known = self.edges.iter().any(|e| {
+            (e.from == from && e.to == to && e.relation == relation)
+                || (e.from == to && e.to == from && relation.inverse() == Some(e.relation))
+        });
+        if !known && from != to {
+            self.edges.push(RelationEdge {
+                from: from.to_string(),
+                to: to.to_string(),
+                relation,
+            });
+        }
+    }
+
+    fn merge(&mut self, other: RelationGraph) {
+        for node in other.nodes {
+            let fetched = node.id == other.root;
+            self.add_node(node, fetched);
+        }
+        for edge in other.edges {
+            self.add_edge(&edge.from, edge.relation, &edge.to);
+        }
+    }
+
+    // The node `relation` of `from`, reading edges in either direction
+    fn next(&self, from: &str, relation: RelationType) -> Option<&str> {
+        self.edges.iter().find_map(|e| {
+            if e.from == from && e.relation == relation {
+                Some(e.to.as_str())
+            } else if e.to == from && relation.inverse() == Some(e.relation) {
+                Some(e.from.as_str())
+            } else {
+                None
+            }
+        })
+    }
+
+    // Walks prequels back from the root, then sequels forward from the first
+    fn watch_order(&self) -> Vec<String> {
+        let mut first = self.root.as_str();
+        let mut seen = HashSet::from([first]);
+        while let Some(prequel) = self
+            .next(first, RelationType::Prequel)
+            .filter(|p| seen.insert(*p))
+        {
+            first = prequel;
+        }
+
+        let mut order = vec![first.to_string()];
+        let mut seen = HashSet::from([first]);
+        let mut current = first;
+        while let Some(sequel) = self
+            .next(current, RelationType::Sequel)
+            .filter(|s| seen.insert(*s))
+        {
+            order.push(sequel.to_string());
+            current = sequel;
+        }
+        order
+    }
+}
+
+/// A provider that can list the works related to one of its entries.
+pub trait RelationSource {
+    /// The graph around the work with this provider ID.
+    async fn related(&self, id: &str) -> Result<RelationGraph>;
+
+    /// Whether to fetch the relations of `node` too, which is `relation` of
+    /// a fetched work. By default the chain of prequels and sequels is
+    /// followed, leaving out print media.
+    fn follow(&self, relation: RelationType, node: &RelationNode) -> bool {
+        matches!(relation, RelationType::Prequel | RelationType::Sequel)
+            && node.media_type.as_deref() != Some("manga")
+    }
+}
+
+/// Builds the graph around `id`, following relations as the source asks
+/// with at most `max_fetches` requests. They are made one at a time, since
+/// the providers rate limit.
+pub async fn build_graph(
+    source: &impl RelationSource,
+    id: &str,
+    max_fetches: usize,
+) -> Result<RelationGraph> {
+    let mut graph = source.related(id).await?;
+    let mut fetched = HashSet::from([graph.root.clone()]);
+    let mut queue: VecDeque<(String, String)> = VecDeque::new();
+    enqueue(source, &graph, &graph.root, &mut queue);
+
+    while let Some((node_id, provider_id)) = queue.pop_front() {
+        if fetched.len() >= max_fetches {
+            break;
+        }
+        if !fetched.insert(node_id.clone()) {
+            continue;
+        }
+        match source.related(&provider_id).await {
+            Ok(related) => {
+                let root = related.root.clone();
+                graph.merge(related);
+                enqueue(source, &graph, &root, &mut queue);
+            }
+            Err(e) => console_log!("Relations of {} failed {:?}", node_id, e),
+        }
+    }
+
+    graph.watch_order = graph.watch_order();
+    Ok(graph)
+}
+
+// Queues the neighbours of `from` the source wants followed
+fn enqueue(
+    source: &impl RelationSource,
+    graph: &RelationGraph,
+    from: &str,
+    queue: &mut VecDeque<(String, String)>,
+) {
+    for edge in graph.edges.iter().filter(|e| e.from == from) {
+        if let Some(node) = graph.nodes.iter().find(|n| n.id == edge.to)
+            && source.follow(edge.relation, node)
+        {
+            queue.push_back((node.id.clone(), node.provider_id.clone()));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::executor::block_on;
+    use std::cell::RefCell;
+
+    // A franchise as AniList would list it: three seasons in a chain, the
+    // manga they adapt and a side story
+    struct MockSource {
+        fetched: RefCell<Vec<String>>,
+    }
+
+    fn node(id: &str, media_type: &str) -> RelationNode {
+        RelationNode {
+            media_type: Some(media_type.to_string()),
+            ..RelationNode::new("anilist", id)
+        }
+    }
+
+    impl RelationSource for MockSource {
+        async fn related(&self, id: &str) -> Result<RelationGraph> {
+            use RelationType::*;
+            self.fetched.borrow_mut().push(id.to_string());
+            let related = match id {
+                "1" => vec![(Sequel, node("2", "anime")), (Source, node("10", "manga"))],
+                "2" => vec![
+                    (Prequel, node("1", "anime")),
+                    (Sequel, node("3", "anime")),
+                    (SideStory, node("4", "anime")),
+                ],
+                "3" => vec![(Prequel, node("2", "anime"))],
+                _ => return Err(Error::RustError("not found".into())),
+            };
+            let mut root = node(id, "anime");
+            root.episodes = Some(12);
+            Ok(RelationGraph::around(root, related))
+        }
+    }
+
+    #[test]
+    fn test_build_graph() {
+        let source = MockSource {
+            fetched: RefCell::new(Vec::new()),
+        };
+        let graph = block_on(build_graph(&source, "2", 10)).unwrap();
+
+        assert_eq!(graph.root, "anilist:2");
+        assert_eq!(*source.fetched.borrow(), vec!["2", "1", "3"]);
+        assert_eq!(graph.nodes.len(), 5);
+        // Fetched nodes replace the summaries their neighbours gave
+        assert!(
+            graph
+                .nodes
+                .iter()
+                .filter(|n| ["anilist:1", "anilist:3"].contains(&n.id.as_str()))
+                .all(|n| n.episodes == Some(12))
+        );
+
+        let edges: Vec<_> = graph
+            .edges
+            .iter()
+            .map(|e| (e.from.as_str(), e.relation, e.to.as_str()))
+            .collect();
+        assert_eq!(
+            edges,
+            vec![
+                ("anilist:2", RelationType::Prequel, "anilist:1"),
+                ("anilist:2", RelationType::Sequel, "anilist:3"),
+                ("anilist:2", RelationType::SideStory, "anilist:4"),
+                ("anilist:1", RelationType::Source, "anilist:10"),
+            ]
+        );
+        assert_eq!(
+            graph.watch_order,
+            vec!["anilist:1", "anilist:2", "anilist:3"]
+        );
+    }
+
+    #[test]
+    fn test_build_graph_keeps_root() {
+        let source = MockSource {
+            fetched: RefCell::new(Vec::new()),
+        };
+        let graph = block_on(build_graph(&source, "2", 10)).unwrap();
+
+        // "1" and "3" both summarize "2" without its episodes
+        let root = graph.nodes.iter().find(|n| n.id == graph.root).unwrap();
+        assert_eq!(root.episodes, Some(12));
+        assert_eq!(graph.nodes.iter().filter(|n| n.id == graph.root).count(), 1);
+    }
+
+    #[test]
+    fn test_build_graph_fetch_limit() {
+        let source = MockSource {
+            fetched: RefCell::new(Vec::new()),
+        };
+        let graph = block_on(build_graph(&source, "1", 1)).unwrap();
+
+        assert_eq!(*source.fetched.borrow(), vec!["1"]);
+        assert_eq!(graph.nodes.len(), 3);
+        assert_eq!(graph.watch_order, vec!["anilist:1", "anilist:2"]);
+    }
+
+    #[test]
+    fn test_from_jikan() {
+        assert_eq!(
+            RelationType::from_jikan("Side Story"),
+            RelationType::SideStory
+        );
+        assert_eq!(RelationType::from_jikan("Adaptation"), RelationType::Source);
+        assert_eq!(RelationType::from_jikan("Other"), RelationType::Other);
+    }
+}
@@ -0,0 +1,245 @@
+use super::tmdb::normalize_title;
+use crate::model::ItemType;
+use crate::search::normalize;
+use serde_derive::Serialize;
+use std::collections::HashSet;
+
+// Relative weight of each signal. Signals missing on either side are left
+// out, so a candidate isn't penalised for what a provider doesn't report.
+const WEIGHT_TITLE: f64 = 0.6;
+const WEIGHT_YEAR: f64 = 0.2;
+const WEIGHT_TYPE: f64 = 0.1;
+const WEIGHT_EPISODES: f64 = 0.1;
+
+/// What we know about the title being looked up.
+#[derive(Debug, Clone, Default)]
+pub struct MatchQuery {
+    /// The bangumi-data title first, then its `TitleTranslate` entries.
+    pub titles: Vec<String>,
+    pub year: Option<i32>,
+    pub type_field: Option<ItemType>,
+    pub episodes: Option<i32>,
+}
+
+impl MatchQuery {
+    /// Keeps the non-empty titles in order, without duplicates.
+    pub fn new<'a>(titles: impl IntoIterator<Item = &'a str>) -> Self {
+        let mut query = Self::default();
+        for t in titles.into_iter().map(str::trim) {
+            if !t.is_empty() && !query.titles.iter().any(|known| known == t) {
+                query.titles.push(t.to_string());
+            }
+        }
+        query
+    }
+
+    /// The title to send to provider search endpoints.
+    pub fn primary_title(&self) -> Option<&str> {
+        self.titles.first().map(String::as_str)
+    }
+}
+
+/// A provider search result, reduced to what scoring and curators look at.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Candidate {
+    /// An ID the provider's `fetch` accepts.
+    pub id: String,
+    pub titles: Vec<String>,
+    pub year: Option<i32>,
+    #[serde(rename = "type")]
+    pub type_field: Option<ItemType>,
+    pub episodes: Option<i32>,
+    pub poster: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScoredCandidate {
+    #[serde(flatten)]
+    pub candidate: Candidate,
+    /// Between 0 and 1.
+    pub confidence: f64,
+}
+
+/// Scores `candidate` against `query`, between 0 and 1.
+pub fn score(query: &MatchQuery, candidate: &Candidate) -> f64 {
+    let mut signals = vec![(WEIGHT_TITLE, title_similarity(query, candidate))];
+
+    if let (Some(a), Some(b)) = (query.year, candidate.year) {
+        let year = match (a - b).abs() {
+            0 => 1.0,
+            1 => 0.5,
+            _ => 0.0,
+        };
+        signals.push((WEIGHT_YEAR, year));
+    }
+
+    if let (Some(a), Some(b)) = (&query.type_field, &candidate.type_field) {
+        let type_field = match (a, b) {
+            _ if a == b => 1.0,
+            // Many web series also air on TV, and providers disagree on which
+            (ItemType::Tv, ItemType::Web) | (ItemType::Web, ItemType::Tv) => 0.5,
+            _ => 0.0,
+        };
+        signals.push((WEIGHT_TYPE, type_field));
+    }
+
+    if let (Some(a), Some(b)) = (query.episodes, candidate.episodes)
+        && a > 0
+        && b > 0
+    {
+        let episodes = 1.0 - (a - b).abs() as f64 / a.max(b) as f64;
+        signals.push((WEIGHT_EPISODES, episodes));
+    }
+
+    let total: f64 = signals.iter().map(|(w, _)| w).sum();
+    signals.iter().map(|(w, s)| w * s).sum::<f64>() / total
+}
+
+/// Scores and sorts candidates, best first. Ties keep the provider's order.
+pub fn rank(query: &MatchQuery, candidates: Vec<Candidate>) -> Vec<ScoredCandidate> {
+    let mut scored: Vec<ScoredCandidate> = candidates
+        .into_iter()
+        .map(|candidate| ScoredCandidate {
+            confidence: score(query, &candidate),
+            candidate,
+        })
+        .collect();
+    scored.sort_by(|a, b| b.confidence.total_cmp(&a.confidence));
+    scored
+}
+
+/// The best candidate, if it is confident enough to use.
+pub fn best(query: &MatchQuery, candidates: Vec<Candidate>) -> Option<ScoredCandidate> {
+    rank(query, candidates)
+        .into_iter()
+        .next()
+        .filter(|c| c.confidence >= crate::config::METADATA_MIN_CONFIDENCE)
+}
+
+// Best Dice coefficient over character bigrams between any query title and
+// any candidate title. Query titles are also tried without season suffixes,
+// since providers often title a sequel's entry after the show.
+fn title_similarity(query: &MatchQuery, candidate: &Candidate) -> f64 {
+    let wanted: Vec<String> = query
+        .titles
+        .iter()
+        .flat_map(|t| [normalize(t), normalize(&normalize_title(t))])
+        .filter(|t| !t.is_empty())
+        .collect();
+
+    candidate
+        .titles
+        .iter()
+        .map(|t| normalize(t))
+        .filter(|t| !t.is_empty())
+        .flat_map(|t| wanted.iter().map(move |w| dice(w, &t)))
+        .fold(0.0, f64::max)
+}
+
+fn dice(a: &str, b: &str) -> f64 {
+    if a == b {
+        return 1.0;
+    }
+    let (a, b) = (bigrams(a), bigrams(b));
+    if a.is_empty() || b.is_empty() {
+        return 0.0;
+    }
+    2.0 * a.intersection(&b).count() as f64 / (a.len() + b.len()) as f64
+}
+
+fn bigrams(s: &str) -> HashSet<(char, char)> {
+    let chars: Vec<char> = s.chars().collect();
+    chars.windows(2).map(|w| (w[0], w[1])).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn candidate(titles: &[&str], year: Option<i32>, type_field: Option<ItemType>) -> Candidate {
+        Candidate {
+            id: titles[0].to_string(),
+            titles: titles.iter().map(|t| t.to_string()).collect(),
+            year,
+            type_field,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_match_query_dedupes_titles() {
+        let query = MatchQuery::new([
+            "葬送のフリーレン",
+            " Frieren: Beyond Journey's End ",
+            "",
+            "葬送のフリーレン",
+        ]);
+        assert_eq!(
+            query.titles,
+            vec!["葬送のフリーレン", "Frieren: Beyond Journey's End"]
+        );
+        assert_eq!(query.primary_title(), Some("葬送のフリーレン"));
+    }
+
+    #[test]
+    fn test_score_signals() {
+        let query = MatchQuery {
+            titles: vec!["進撃の巨人 Season 3".into(), "Attack on Titan".into()],
+            year: Some(2018),
+            type_field: Some(ItemType::Tv),
+            episodes: Some(12),
+        };
+
+        let exact = candidate(&["Attack on Titan"], Some(2018), Some(ItemType::Tv));
+        assert_eq!(score(&query, &exact), 1.0);
+
+        let wrong_year = candidate(&["Attack on Titan"], Some(2013), Some(ItemType::Tv));
+        let movie = candidate(&["Attack on Titan"], Some(2018), Some(ItemType::Movie));
+        let unrelated = candidate(&["Frieren"], Some(2018), Some(ItemType::Tv));
+        assert!(score(&query, &wrong_year) < 1.0);
+        assert!(score(&query, &movie) < 1.0);
+        assert!(score(&query, &unrelated) < 0.5);
+
+        // The season suffix is dropped, and unknown signals are left out
+        // rather than counted against
+        let title_only = candidate(&["進撃の巨人"], None, None);
+        assert_eq!(score(&query, &title_only), 1.0);
+
+        let mut episodes = exact.clone();
+        episodes.episodes = Some(24);
+        assert!((score(&query, &episodes) - 0.95).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_rank_and_best() {
+        let query = MatchQuery {
+            titles: vec!["葬送のフリーレン".into()],
+            year: Some(2023),
+            type_field: Some(ItemType::Tv),
+            ..Default::default()
+        };
+        let candidates = vec![
+            candidate(
+                &["葬送のフリーレン ～魔法って楽しいね～"],
+                Some(2023),
+                Some(ItemType::Web),
+            ),
+            candidate(
+                &["Sousou no Frieren", "葬送のフリーレン"],
+                Some(2023),
+                Some(ItemType::Tv),
+            ),
+            candidate(&["ダンジョン飯"], Some(2024), Some(ItemType::Tv)),
+        ];
+
+        let ranked = rank(&query, candidates.clone());
+        assert_eq!(ranked[0].candidate.id, "Sousou no Frieren");
+        assert_eq!(ranked[2].candidate.id, "ダンジョン飯");
+
+        let best = best(&query, candidates).unwrap();
+        assert_eq!(best.confidence, 1.0);
+        assert!(super::best(&query, vec![candidate(&["ダンジョン飯"], None, None)]).is_none());
+    }
+}
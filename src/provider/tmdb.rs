@@ -1,13 +1,20 @@
 use super::MetadataProvider;
-use crate::model;
+use super::credits::{CreditsPage, CreditsSource};
+use super::relations::{RelationGraph, RelationNode, RelationSource, RelationType};
+use super::scoring::{Candidate, MatchQuery};
+use crate::locale::language_tag;
+use crate::model::ItemType;
+use crate::{config, model, utils};
 use regex::Regex;
-use std::sync::OnceLock;
+use serde_derive::Deserialize;
+use std::sync::{Once, OnceLock};
 use tmdb_client::async_apis::AsyncAPIClient;
 use tmdb_client::models;
 use worker::*;
 
 pub struct TmdbProvider<'a> {
     env: &'a Env,
+    language: model::Language,
 }
 
 struct SyncApiClient(AsyncAPIClient);
@@ -20,22 +27,30 @@ unsafe impl Send for SyncApiClient {}
 unsafe impl Sync for SyncApiClient {}
 
 static TMDB_CLIENT: OnceLock<Option<SyncApiClient>> = OnceLock::new();
+static MISSING_READ_TOKEN: Once = Once::new();
 
 impl<'a> TmdbProvider<'a> {
-    pub fn new(env: &'a Env) -> Self {
-        Self { env }
+    pub fn new(env: &'a Env, language: model::Language) -> Self {
+        Self { env, language }
+    }
+
+    fn api_token(&self) -> Option<String> {
+        self.env
+            .secret("TMDB_TOKEN")
+            .map(|s| s.to_string())
+            .or_else(|_| self.env.var("TMDB_TOKEN").map(|s| s.to_string()))
+            .ok()
+    }
+
+    /// Whether a token is configured, so requests can be made at all.
+    pub fn has_token(&self) -> bool {
+        self.api_token().is_some()
     }
 
     fn get_client(&self) -> Result<&AsyncAPIClient> {
         let client_opt = TMDB_CLIENT.get_or_init(|| {
-            let api_token = self
-                .env
-                .secret("TMDB_TOKEN")
-                .map(|s| s.to_string())
-                .or_else(|_| self.env.var("TMDB_TOKEN").map(|s| s.to_string()))
-                .ok();
-
-            api_token.map(|t| SyncApiClient(AsyncAPIClient::new_with_api_key(t)))
+            self.api_token()
+                .map(|t| SyncApiClient(AsyncAPIClient::new_with_api_key(t)))
         });
 
         client_opt
@@ -43,32 +58,467 @@ impl<'a> TmdbProvider<'a> {
             .map(|w| &w.0)
             .ok_or_else(|| Error::RustError("TMDB_TOKEN not set".into()))
     }
+
+    /// Languages to try for the description: the requested locale first,
+    /// then `TMDB_DESCRIPTION_LANGUAGES` (comma-separated) or the default.
+    fn description_languages(&self, locale: &str) -> Vec<String> {
+        let configured = self
+            .env
+            .var("TMDB_DESCRIPTION_LANGUAGES")
+            .map(|v| v.to_string())
+            .unwrap_or_else(|_| config::TMDB_DESCRIPTION_LANGUAGES.to_string());
+
+        let mut languages = vec![locale.to_string()];
+        for lang in configured.split(',').map(str::trim) {
+            if !lang.is_empty() && !languages.iter().any(|l| l.eq_ignore_ascii_case(lang)) {
+                languages.push(lang.to_string());
+            }
+        }
+        languages
+    }
+
+    /// The v4 read access token, sent as a bearer token by [`Self::fetch_api`].
+    fn read_token(&self) -> Option<String> {
+        let token = self
+            .env
+            .secret("TMDB_READ_TOKEN")
+            .map(|s| s.to_string())
+            .or_else(|_| self.env.var("TMDB_READ_TOKEN").map(|s| s.to_string()))
+            .ok();
+        if token.is_none() {
+            MISSING_READ_TOKEN.call_once(|| {
+                console_log!(
+                    "TMDB_READ_TOKEN not set; TMDb translations, alternative titles and relations are unavailable"
+                )
+            });
+        }
+        token
+    }
+
+    /// Whether the read access token is configured, which translations,
+    /// alternative titles and relations need.
+    pub fn has_read_token(&self) -> bool {
+        self.read_token().is_some()
+    }
+
+    /// GETs `/{path}` for endpoints the client doesn't cover. The token goes
+    /// in the `Authorization` header rather than the URL, which Cloudflare's
+    /// cache is keyed on and which fetch errors report.
+    async fn fetch_api<T: for<'de> serde::Deserialize<'de>>(
+        &self,
+        path: &str,
+        query: &[(&str, &str)],
+    ) -> Result<Option<T>> {
+        let token = self
+            .read_token()
+            .ok_or_else(|| Error::RustError("TMDB_READ_TOKEN not set".into()))?;
+        let url = Url::parse_with_params(&format!("{}/{}", config::TMDB_API_BASE, path), query)
+            .map_err(|e| Error::RustError(format!("Invalid TMDb URL: {}", e)))?;
+        let authorization = format!("Bearer {}", token);
+        utils::fetch_json_with_headers(url.as_str(), &[("Authorization", &authorization)]).await
+    }
+
+    /// Fetches `/{path}/translations`, where `path` is a unified ID such as
+    /// `tv/1/season/2`.
+    async fn fetch_translations(&self, path: &str) -> Result<Vec<Translation>> {
+        let response: Option<TranslationsResponse> = self
+            .fetch_api(&format!("{}/translations", path), &[])
+            .await?;
+        Ok(response.map(|r| r.translations).unwrap_or_default())
+    }
+
+    /// Fetches the translations and alternative titles of a movie or show in
+    /// one request, appended to its details.
+    async fn fetch_extras(&self, path: &str) -> Result<TmdbExtras> {
+        let extras: Option<TmdbExtras> = self
+            .fetch_api(
+                path,
+                &[("append_to_response", "translations,alternative_titles")],
+            )
+            .await?;
+        Ok(extras.unwrap_or_default())
+    }
+
+    /// Fetches `/{path}` in the requested language, for endpoints the client
+    /// doesn't cover.
+    async fn fetch_localized<T: for<'de> serde::Deserialize<'de>>(&self, path: &str) -> Result<T> {
+        self.fetch_api(path, &[("language", self.language.tmdb_locale())])
+            .await?
+            .ok_or_else(|| Error::RustError(format!("TMDb {} not found", path)))
+    }
+
+    /// Fills an empty description from TMDb translations, trying season
+    /// translations before the show's for each language in priority order.
+    async fn fill_description(
+        &self,
+        unified: &mut model::UnifiedMetadata,
+        locale: &str,
+        show_translations: &[Translation],
+    ) {
+        if unified.description.is_some() {
+            unified.description_language = Some(language_tag(locale));
+            return;
+        }
+
+        let mut season_translations = Vec::new();
+        if unified.id.contains("/season/") && self.has_read_token() {
+            match self.fetch_translations(&unified.id).await {
+                Ok(translations) => season_translations = translations,
+                Err(e) => console_log!("TMDb translations failed {:?}", e),
+            }
+        }
+
+        let sources = [season_translations.as_slice(), show_translations];
+        if let Some((language, overview)) =
+            pick_description(&sources, &self.description_languages(locale))
+        {
+            unified.description = Some(overview);
+            unified.description_language = Some(language_tag(&language));
+        }
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct TmdbExtras {
+    #[serde(default)]
+    translations: TranslationsResponse,
+    #[serde(default)]
+    alternative_titles: AlternativeTitlesResponse,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct TranslationsResponse {
+    #[serde(default)]
+    translations: Vec<Translation>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Translation {
+    iso_639_1: String,
+    #[serde(default)]
+    iso_3166_1: String,
+    #[serde(default)]
+    data: TranslationData,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct TranslationData {
+    // Movies name it `title`, shows `name`
+    #[serde(default, alias = "name")]
+    title: Option<String>,
+    overview: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct AlternativeTitlesResponse {
+    // Movies list them under `titles`, shows under `results`
+    #[serde(default, alias = "results")]
+    titles: Vec<TmdbAlternativeTitle>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TmdbAlternativeTitle {
+    #[serde(default)]
+    iso_3166_1: String,
+    title: String,
+}
+
+/// Adds the translated titles, tagged with their language, then the
+/// alternative titles, which TMDb tags only by country.
+fn add_alternative_titles(
+    title: &mut model::UniversalTitle,
+    translations: &[Translation],
+    titles: Vec<TmdbAlternativeTitle>,
+) {
+    for t in translations {
+        if let Some(translated) = &t.data.title {
+            title.add_alternative(model::AlternativeTitle {
+                title: translated.clone(),
+                language: Some(t.iso_639_1.clone()),
+                region: (!t.iso_3166_1.is_empty()).then(|| t.iso_3166_1.clone()),
+            });
+        }
+    }
+    for t in titles {
+        title.add_alternative(model::AlternativeTitle {
+            title: t.title,
+            language: None,
+            region: (!t.iso_3166_1.is_empty()).then_some(t.iso_3166_1),
+        });
+    }
+}
+
+/// Picks the first non-empty overview by language priority. `sources` are
+/// ordered most specific first (season, then show). A priority entry with a
+/// region (`en-US`) prefers that exact locale, then any region of the same
+/// language. Returns the locale of the overview and its text.
+fn pick_description(sources: &[&[Translation]], priority: &[String]) -> Option<(String, String)> {
+    let overview = |t: &Translation| {
+        t.data
+            .overview
+            .as_deref()
+            .map(str::trim)
+            .filter(|o| !o.is_empty())
+            .map(|o| (translation_locale(t), o.to_string()))
+    };
+
+    priority.iter().find_map(|wanted| {
+        let language = wanted.split('-').next().unwrap_or(wanted);
+        sources.iter().find_map(|translations| {
+            translations
+                .iter()
+                .filter(|t| translation_locale(t).eq_ignore_ascii_case(wanted))
+                .find_map(overview)
+                .or_else(|| {
+                    translations
+                        .iter()
+                        .filter(|t| t.iso_639_1.eq_ignore_ascii_case(language))
+                        .find_map(overview)
+                })
+        })
+    })
+}
+
+fn translation_locale(t: &Translation) -> String {
+    if t.iso_3166_1.is_empty() {
+        t.iso_639_1.clone()
+    } else {
+        format!("{}-{}", t.iso_639_1, t.iso_3166_1)
+    }
 }
 
 impl<'a> MetadataProvider for TmdbProvider<'a> {
-    async fn fetch(
-        &self,
-        id: Option<&str>,
-        title: Option<&str>,
-        year: Option<i32>,
-    ) -> Result<model::UnifiedMetadata> {
+    async fn search(&self, query: &MatchQuery) -> Result<Vec<Candidate>> {
+        let client = self.get_client()?;
+        let title = query
+            .primary_title()
+            .ok_or_else(|| Error::RustError("Title required".into()))?;
+
+        let results = client
+            .search_api()
+            .get_search_multi_paginated(
+                &normalize_title(title),
+                Some(self.language.tmdb_locale()),
+                Some(1),
+                Some(false),
+                None,
+            )
+            .await
+            .map_err(|e| Error::RustError(format!("TMDb search failed: {}", e)))?;
+
+        Ok(results
+            .results
+            .unwrap_or_default()
+            .iter()
+            .filter_map(|res| search_candidate(&serde_json::to_value(res).ok()?))
+            .collect())
+    }
+
+    async fn fetch_by_id(&self, id: &str) -> Result<model::UnifiedMetadata> {
         let client = self.get_client()?;
+        let locale = self.language.tmdb_locale();
 
-        // 1. Resolve ID (Search if needed)
-        let (media_id, media_type) = if let Some(id) = id {
-            parse_tmdb_id(id)?
-        } else if let Some(search_title) = title {
-            search_media(client, search_title, year).await?
+        // 1. Fetch Details based on type
+        let mut unified = match parse_tmdb_id(id)? {
+            (media_id, MediaType::Movie) => get_movie_details(client, media_id, locale).await?,
+            (_, MediaType::Tv { show_id, season }) => {
+                get_tv_details(client, show_id, season, locale).await?
+            }
+        };
+
+        // 2. Fall back to other languages when the overview is untranslated,
+        // and collect alternative titles, which TMDb keeps per show
+        let show_path = unified
+            .id
+            .split("/season/")
+            .next()
+            .unwrap_or_default()
+            .to_string();
+        let extras = if self.has_read_token() {
+            self.fetch_extras(&show_path).await.unwrap_or_else(|e| {
+                console_log!("TMDb translations failed {:?}", e);
+                TmdbExtras::default()
+            })
         } else {
-            return Err(Error::RustError("ID or Title required".into()));
+            TmdbExtras::default()
+        };
+        let translations = extras.translations.translations;
+        self.fill_description(&mut unified, locale, &translations)
+            .await;
+        add_alternative_titles(
+            &mut unified.title,
+            &translations,
+            extras.alternative_titles.titles,
+        );
+        Ok(unified)
+    }
+}
+
+impl<'a> CreditsSource for TmdbProvider<'a> {
+    async fn credits(&self, id: &str, page: usize, per_page: usize) -> Result<CreditsPage> {
+        let client = self.get_client()?;
+        let locale = self.language.tmdb_locale();
+
+        // Season credits first, as for the metadata lookup
+        let credits = match parse_tmdb_id(id)? {
+            (movie_id, MediaType::Movie) => fetch_movie(client, movie_id, locale).await?.credits,
+            (_, MediaType::Tv { show_id, season }) => {
+                let (show, season) = fetch_tv(client, show_id, season, locale).await?;
+                season.credits.or(show.credits)
+            }
         };
+        let (characters, staff) = credits.map(credits_to_unified).unwrap_or_default();
+        Ok(CreditsPage::slice(characters, staff, page, per_page))
+    }
+}
 
-        // 2. Fetch Details based on type
-        match media_type {
-            MediaType::Movie => get_movie_details(client, media_id).await,
-            MediaType::Tv { show_id, season } => get_tv_details(client, show_id, season).await,
+// TMDb only knows the films of a collection and the seasons of a show, so
+// each request already returns the whole chain
+impl<'a> RelationSource for TmdbProvider<'a> {
+    async fn related(&self, id: &str) -> Result<RelationGraph> {
+        match parse_tmdb_id(id)? {
+            (movie_id, MediaType::Movie) => {
+                let movie_id = movie_id.split('-').next().unwrap_or(&movie_id);
+                let movie: TmdbMovieSummary =
+                    self.fetch_localized(&format!("movie/{}", movie_id)).await?;
+                let parts = match &movie.belongs_to_collection {
+                    Some(collection) => {
+                        let collection: TmdbCollection = self
+                            .fetch_localized(&format!("collection/{}", collection.id))
+                            .await?;
+                        collection.parts
+                    }
+                    None => Vec::new(),
+                };
+                Ok(collection_graph(movie, parts))
+            }
+            (_, MediaType::Tv { show_id, season }) => {
+                let show_id = show_id.split('-').next().unwrap_or(&show_id);
+                let show: TmdbShowSummary =
+                    self.fetch_localized(&format!("tv/{}", show_id)).await?;
+                Ok(seasons_graph(show, season))
+            }
         }
     }
+
+    fn follow(&self, _relation: RelationType, _node: &RelationNode) -> bool {
+        false
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct TmdbMovieSummary {
+    id: i64,
+    title: Option<String>,
+    poster_path: Option<String>,
+    release_date: Option<String>,
+    #[serde(default)]
+    belongs_to_collection: Option<TmdbCollectionRef>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TmdbCollectionRef {
+    id: i64,
+}
+
+#[derive(Debug, Deserialize)]
+struct TmdbCollection {
+    #[serde(default)]
+    parts: Vec<TmdbMovieSummary>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TmdbShowSummary {
+    id: i64,
+    name: Option<String>,
+    poster_path: Option<String>,
+    #[serde(default)]
+    seasons: Vec<TmdbSeasonSummary>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TmdbSeasonSummary {
+    season_number: i32,
+    name: Option<String>,
+    poster_path: Option<String>,
+    air_date: Option<String>,
+    episode_count: Option<i32>,
+}
+
+fn poster(path: Option<&str>) -> model::UniversalCoverImage {
+    model::UniversalCoverImage {
+        large: path.map(|p| format!("https://image.tmdb.org/t/p/w500{}", p)),
+        extra_large: path.map(|p| format!("https://image.tmdb.org/t/p/original{}", p)),
+    }
+}
+
+fn year_of(date: Option<&str>) -> Option<i32> {
+    date?.get(0..4)?.parse().ok()
+}
+
+fn movie_node(movie: &TmdbMovieSummary) -> RelationNode {
+    RelationNode {
+        media_type: Some("anime".to_string()),
+        format: Some("MOVIE".to_string()),
+        title: model::UniversalTitle {
+            native: movie.title.clone(),
+            ..Default::default()
+        },
+        cover_image: poster(movie.poster_path.as_deref()),
+        year: year_of(movie.release_date.as_deref()),
+        episodes: Some(1),
+        ..RelationNode::new("tmdb", format!("movie/{}", movie.id))
+    }
+}
+
+/// The films of a collection, each the sequel of the one released before.
+fn collection_graph(movie: TmdbMovieSummary, mut parts: Vec<TmdbMovieSummary>) -> RelationGraph {
+    let root = movie_node(&movie);
+    if !parts.iter().any(|p| p.id == movie.id) {
+        parts.push(movie);
+    }
+    // Unreleased films have an empty date and go last
+    parts.sort_by_key(|p| {
+        let date = p.release_date.clone().filter(|d| !d.is_empty());
+        (date.is_none(), date)
+    });
+    chain_graph(root.id, parts.iter().map(movie_node).collect())
+}
+
+/// The seasons of a show, each the sequel of the one before. Specials
+/// (season 0) are left out.
+fn seasons_graph(show: TmdbShowSummary, season: i32) -> RelationGraph {
+    let nodes = show
+        .seasons
+        .iter()
+        .filter(|s| s.season_number > 0)
+        .map(|s| RelationNode {
+            media_type: Some("anime".to_string()),
+            format: Some("TV".to_string()),
+            title: model::UniversalTitle {
+                native: show.name.clone(),
+                season: s.name.clone().filter(|n| !n.is_empty()),
+                ..Default::default()
+            },
+            cover_image: poster(s.poster_path.as_deref().or(show.poster_path.as_deref())),
+            year: year_of(s.air_date.as_deref()),
+            episodes: s.episode_count,
+            ..RelationNode::new("tmdb", format!("tv/{}/season/{}", show.id, s.season_number))
+        })
+        .collect();
+    chain_graph(format!("tmdb:tv/{}/season/{}", show.id, season), nodes)
+}
+
+fn chain_graph(root: String, nodes: Vec<RelationNode>) -> RelationGraph {
+    let mut graph = RelationGraph {
+        root,
+        ..Default::default()
+    };
+    for pair in nodes.windows(2) {
+        graph.add_edge(&pair[0].id, RelationType::Sequel, &pair[1].id);
+    }
+    graph.nodes = nodes;
+    graph
 }
 
 #[derive(Debug, PartialEq)]
@@ -117,85 +567,47 @@ fn parse_tmdb_id(id: &str) -> Result<(String, MediaType)> {
     }
 }
 
-async fn search_media(
-    client: &AsyncAPIClient,
-    title: &str,
-    year: Option<i32>,
-) -> Result<(String, MediaType)> {
-    // Try normalized title search
-    let normalized = normalize_title(title);
-
-    let results = client
-        .search_api()
-        .get_search_multi_paginated(&normalized, Some("ja-JP"), Some(1), Some(false), None)
-        .await
-        .map_err(|e| Error::RustError(format!("TMDb search failed: {}", e)))?;
-
-    // Filter and find best match
-    if let Some(results_vec) = results.results {
-        for res in results_vec {
-            let media_type = res.get("media_type").and_then(|v| v.as_str());
-
-            match media_type {
-                Some("movie") => {
-                    let id = res.get("id").and_then(|v| v.as_i64());
-                    let release_date = res.get("release_date").and_then(|v| v.as_str());
-
-                    if let Some(id) = id {
-                        let id_str = id.to_string();
-                        // Check year if provided
-                        if let Some(y) = year {
-                            if let Some(date_str) = release_date
-                                && date_str.starts_with(&y.to_string())
-                            {
-                                return Ok((id_str, MediaType::Movie));
-                            }
-                        } else {
-                            return Ok((id_str, MediaType::Movie));
-                        }
-                    }
-                }
-                Some("tv") => {
-                    let id = res.get("id").and_then(|v| v.as_i64());
-                    let first_air_date = res.get("first_air_date").and_then(|v| v.as_str());
-
-                    if let Some(id) = id {
-                        let id_str = id.to_string();
-                        // Check year if provided
-                        if let Some(y) = year {
-                            if let Some(date_str) = first_air_date
-                                && date_str.starts_with(&y.to_string())
-                            {
-                                return Ok((
-                                    id_str.clone(),
-                                    MediaType::Tv {
-                                        show_id: id_str,
-                                        season: 1,
-                                    },
-                                ));
-                            }
-                        } else {
-                            return Ok((
-                                id_str.clone(),
-                                MediaType::Tv {
-                                    show_id: id_str,
-                                    season: 1,
-                                },
-                            ));
-                        }
-                    }
-                }
-                _ => continue,
-            }
-        }
-    }
+/// Reads a `search/multi` result; people and other media types are skipped.
+fn search_candidate(res: &serde_json::Value) -> Option<Candidate> {
+    let (media_type, type_field, title_keys, date_key) = match res.get("media_type")?.as_str()? {
+        "movie" => (
+            "movie",
+            ItemType::Movie,
+            ["title", "original_title"],
+            "release_date",
+        ),
+        "tv" => (
+            "tv",
+            ItemType::Tv,
+            ["name", "original_name"],
+            "first_air_date",
+        ),
+        _ => return None,
+    };
+    let id = res.get("id")?.as_i64()?;
+    let field = |key: &str| {
+        res.get(key)
+            .and_then(|v| v.as_str())
+            .filter(|s| !s.is_empty())
+    };
 
-    Err(Error::RustError("No suitable match found".into()))
+    Some(Candidate {
+        id: format!("{}/{}", media_type, id),
+        titles: title_keys
+            .iter()
+            .filter_map(|k| field(k))
+            .map(String::from)
+            .collect(),
+        year: field(date_key).and_then(|d| d.get(0..4)?.parse().ok()),
+        type_field: Some(type_field),
+        episodes: None,
+        poster: field("poster_path").map(|p| format!("https://image.tmdb.org/t/p/w500{}", p)),
+    })
 }
 
 static TITLE_NORMALIZE_REGEX: OnceLock<Regex> = OnceLock::new();
 
-fn normalize_title(title: &str) -> String {
+pub(crate) fn normalize_title(title: &str) -> String {
     let normalized = title.replace("-", " - ");
 
     let re = TITLE_NORMALIZE_REGEX.get_or_init(|| {
@@ -225,7 +637,18 @@ fn normalize_title(title: &str) -> String {
 async fn get_movie_details(
     client: &AsyncAPIClient,
     movie_id: String,
+    locale: &str,
 ) -> Result<model::UnifiedMetadata> {
+    Ok(movie_to_unified(
+        fetch_movie(client, movie_id, locale).await?,
+    ))
+}
+
+async fn fetch_movie(
+    client: &AsyncAPIClient,
+    movie_id: String,
+    locale: &str,
+) -> Result<models::MovieDetails> {
     // Extract ID if it contains a slug (fallback to parsing the whole string if no slug)
     let id: i32 = movie_id
         .split('-')
@@ -236,18 +659,29 @@ async fn get_movie_details(
 
     let movie = client
         .movies_api()
-        .get_movie_details(id, Some("ja-JP"), None, Some("release_dates,credits"))
+        .get_movie_details(id, Some(locale), None, Some("release_dates,credits"))
         .await
         .map_err(|e| Error::RustError(format!("Failed to fetch movie details: {}", e)))?;
 
-    Ok(movie_to_unified(movie))
+    Ok(movie)
 }
 
 async fn get_tv_details(
     client: &AsyncAPIClient,
     show_id: String,
     season_number: i32,
+    locale: &str,
 ) -> Result<model::UnifiedMetadata> {
+    let (show, season) = fetch_tv(client, show_id, season_number, locale).await?;
+    Ok(tv_to_unified(show, season))
+}
+
+async fn fetch_tv(
+    client: &AsyncAPIClient,
+    show_id: String,
+    season_number: i32,
+    locale: &str,
+) -> Result<(models::TvDetails, models::SeasonDetails)> {
     // Extract ID if it contains a slug
     let id: i32 = show_id
         .split('-')
@@ -258,27 +692,36 @@ async fn get_tv_details(
 
     let show = client
         .tv_api()
-        .get_tv_details(id, Some("ja-JP"), None, Some("content_ratings,credits"))
+        .get_tv_details(id, Some(locale), None, Some("content_ratings,credits"))
         .await
         .map_err(|e| Error::RustError(format!("Failed to fetch TV details: {}", e)))?;
 
     let season = client
         .tv_seasons_api()
-        .get_tv_season_details(id, season_number, Some("ja-JP"), None, Some("credits"))
+        .get_tv_season_details(id, season_number, Some(locale), None, Some("credits"))
         .await
         .map_err(|e| Error::RustError(format!("Failed to fetch Season details: {}", e)))?;
 
-    Ok(tv_to_unified(show, season))
+    Ok((show, season))
 }
 
 fn movie_to_unified(movie: models::MovieDetails) -> model::UnifiedMetadata {
     use model::*;
 
-    let title = UniversalTitle {
+    let mut title = UniversalTitle {
         romaji: None,
         english: None,
         native: movie.title.clone(),
+        season: None,
+        alternatives: Vec::new(),
     };
+    if let Some(original) = movie.original_title.clone() {
+        title.add_alternative(AlternativeTitle {
+            title: original,
+            language: movie.original_language.clone(),
+            region: None,
+        });
+    }
 
     let cover_image = UniversalCoverImage {
         large: movie
@@ -304,29 +747,7 @@ fn movie_to_unified(movie: models::MovieDetails) -> model::UnifiedMetadata {
         .filter_map(|s| s.name)
         .collect();
 
-    let mut characters = Vec::new();
-    let mut staff = Vec::new();
-
-    if let Some(credits) = movie.credits {
-        if let Some(cast) = credits.cast {
-            for member in cast.into_iter().take(6) {
-                characters.push(UniversalCharacter {
-                    name: member.character.unwrap_or_default(),
-                    voice_actor: member.name,
-                    role: Some("Cast".to_string()),
-                });
-            }
-        }
-        if let Some(crew) = credits.crew {
-            for member in crew.into_iter().take(10) {
-                staff.push(UniversalStaff {
-                    name: member.name.unwrap_or_default(),
-                    role: member.job.unwrap_or_default(),
-                    department: member.department,
-                });
-            }
-        }
-    }
+    let (characters, staff) = movie.credits.map(credits_preview).unwrap_or_default();
 
     // Content Ratings (Release Dates for Movies)
     let content_rating = movie.release_dates.and_then(|dates| {
@@ -354,7 +775,8 @@ fn movie_to_unified(movie: models::MovieDetails) -> model::UnifiedMetadata {
         average_score: movie.vote_average.map(|v| (v * 10.0) as i32),
         episodes: None,
         genres,
-        description: movie.overview,
+        description: movie.overview.filter(|s| !s.trim().is_empty()),
+        description_language: None,
         studios,
         characters,
         staff,
@@ -370,21 +792,32 @@ fn movie_to_unified(movie: models::MovieDetails) -> model::UnifiedMetadata {
                 None
             }
         }),
+        next_episode: None,
+        match_confidence: None,
+        external_ids: Default::default(),
+        external_links: Vec::new(),
+        streaming_episodes: Vec::new(),
+        sources: Default::default(),
     }
 }
 
 fn tv_to_unified(show: models::TvDetails, season: models::SeasonDetails) -> model::UnifiedMetadata {
     use model::*;
 
-    let title = UniversalTitle {
+    let mut title = UniversalTitle {
         romaji: None,
         english: None,
-        native: Some(format!(
-            "{} : {}",
-            show.name.clone().unwrap_or_default(),
-            season.name.clone().unwrap_or_default()
-        )),
+        native: show.name.clone(),
+        season: season.name.clone().filter(|s| !s.is_empty()),
+        alternatives: Vec::new(),
     };
+    if let Some(original) = show.original_name.clone() {
+        title.add_alternative(AlternativeTitle {
+            title: original,
+            language: show.original_language.clone(),
+            region: None,
+        });
+    }
 
     let poster_path = season.poster_path.or(show.poster_path.clone());
     let cover_image = UniversalCoverImage {
@@ -409,32 +842,12 @@ fn tv_to_unified(show: models::TvDetails, season: models::SeasonDetails) -> mode
         .filter_map(|s| s.name)
         .collect();
 
-    let mut characters = Vec::new();
-    let mut staff = Vec::new();
-
     // Prefer season credits, fall back to show credits
-    let credits = season.credits.or(show.credits);
-
-    if let Some(credits) = credits {
-        if let Some(cast) = credits.cast {
-            for member in cast.into_iter().take(6) {
-                characters.push(UniversalCharacter {
-                    name: member.character.unwrap_or_default(),
-                    voice_actor: member.name,
-                    role: Some("Cast".to_string()),
-                });
-            }
-        }
-        if let Some(crew) = credits.crew {
-            for member in crew.into_iter().take(10) {
-                staff.push(UniversalStaff {
-                    name: member.name.unwrap_or_default(),
-                    role: member.job.unwrap_or_default(),
-                    department: member.department,
-                });
-            }
-        }
-    }
+    let (characters, staff) = season
+        .credits
+        .or(show.credits)
+        .map(credits_preview)
+        .unwrap_or_default();
 
     let episodes_list: Vec<_> = season
         .episodes
@@ -446,6 +859,8 @@ fn tv_to_unified(show: models::TvDetails, season: models::SeasonDetails) -> mode
             air_date: e.air_date,
             overview: e.overview,
             runtime: None,
+            thumbnail: None,
+            titles: Vec::new(),
         })
         .collect();
 
@@ -485,7 +900,11 @@ fn tv_to_unified(show: models::TvDetails, season: models::SeasonDetails) -> mode
         average_score: show.vote_average.map(|v| (v * 10.0) as i32),
         episodes: Some(season_episodes_len as i32),
         genres,
-        description: season.overview.filter(|s| !s.is_empty()).or(show.overview),
+        description: season
+            .overview
+            .filter(|s| !s.trim().is_empty())
+            .or(show.overview.filter(|s| !s.trim().is_empty())),
+        description_language: None,
         studios,
         characters,
         staff,
@@ -501,9 +920,61 @@ fn tv_to_unified(show: models::TvDetails, season: models::SeasonDetails) -> mode
                 None
             }
         }),
+        next_episode: None,
+        match_confidence: None,
+        external_ids: Default::default(),
+        external_links: Vec::new(),
+        streaming_episodes: Vec::new(),
+        sources: Default::default(),
     }
 }
 
+/// The first of the cast and crew, as `/api/metadata` shows them; the rest
+/// are paged by [`CreditsSource::credits`].
+fn credits_preview(
+    credits: models::Credits,
+) -> (Vec<model::UniversalCharacter>, Vec<model::UniversalStaff>) {
+    let (mut characters, mut staff) = credits_to_unified(credits);
+    characters.truncate(config::TMDB_CAST_PREVIEW);
+    staff.truncate(config::TMDB_CREW_PREVIEW);
+    (characters, staff)
+}
+
+/// The whole cast and crew, in TMDb's order. TMDb has no character images,
+/// so the cast photo is the actor's.
+fn credits_to_unified(
+    credits: models::Credits,
+) -> (Vec<model::UniversalCharacter>, Vec<model::UniversalStaff>) {
+    let profile =
+        |path: Option<String>| path.map(|p| format!("https://image.tmdb.org/t/p/w185{}", p));
+
+    let characters = credits
+        .cast
+        .unwrap_or_default()
+        .into_iter()
+        .map(|member| model::UniversalCharacter {
+            name: member.character.unwrap_or_default(),
+            voice_actor: member.name,
+            role: Some("Cast".to_string()),
+            image: None,
+            voice_actor_image: profile(member.profile_path),
+            voice_actor_language: None,
+        })
+        .collect();
+    let staff = credits
+        .crew
+        .unwrap_or_default()
+        .into_iter()
+        .map(|member| model::UniversalStaff {
+            name: member.name.unwrap_or_default(),
+            role: member.job.unwrap_or_default(),
+            department: member.department,
+            image: profile(member.profile_path),
+        })
+        .collect();
+    (characters, staff)
+}
+
 fn find_best_rating<T, FCountry, FRating>(
     results: &[T],
     get_country: FCountry,
@@ -774,11 +1245,13 @@ mod tests {
                 name: "Character 1".into(),
                 voice_actor: Some("Actor 1".into()),
                 role: Some("Cast".into()),
+                ..Default::default()
             }],
             staff: vec![model::UniversalStaff {
                 name: "Director 1".into(),
                 role: "Director".into(),
                 department: Some("Directing".into()),
+                ..Default::default()
             }],
             is_finished: true,
             runtime: Some(120),
@@ -951,10 +1424,8 @@ mod tests_tv_transformation {
         let result = tv_to_unified(show, season);
 
         assert_eq!(result.id, "tv/100/season/1");
-        assert_eq!(
-            result.title.native,
-            Some("Show Title : Season 1".to_string())
-        );
+        assert_eq!(result.title.native, Some("Show Title".to_string()));
+        assert_eq!(result.title.season, Some("Season 1".to_string()));
         // Season poster should take precedence
         assert!(
             result
@@ -976,6 +1447,45 @@ mod tests_tv_transformation {
         assert!(result.is_finished);
     }
 
+    #[test]
+    fn test_credits_to_unified() {
+        let credits = models::Credits {
+            cast: Some(
+                (0..8)
+                    .map(|i| models::Cast {
+                        name: Some(format!("Actor {}", i)),
+                        character: Some(format!("Character {}", i)),
+                        profile_path: Some(format!("/{}.jpg", i)),
+                        ..Default::default()
+                    })
+                    .collect(),
+            ),
+            crew: Some(
+                (0..12)
+                    .map(|i| models::Crew {
+                        name: Some(format!("Crew {}", i)),
+                        job: Some("Key Animation".to_string()),
+                        ..Default::default()
+                    })
+                    .collect(),
+            ),
+            guest_stars: None,
+            id: None,
+        };
+
+        let (characters, staff) = credits_preview(credits.clone());
+        assert_eq!((characters.len(), staff.len()), (6, 10));
+
+        let (characters, staff) = credits_to_unified(credits);
+        assert_eq!(characters.len(), 8);
+        assert_eq!(staff.len(), 12);
+        assert_eq!(
+            characters[7].voice_actor_image.as_deref(),
+            Some("https://image.tmdb.org/t/p/w185/7.jpg")
+        );
+        assert_eq!(staff[11].image, None);
+    }
+
     #[test]
     fn test_tv_to_unified_credits_fallback() {
         // Case 1: Season has credits -> expect Season credits
@@ -1052,4 +1562,164 @@ mod tests_tv_transformation {
         let result2 = tv_to_unified(show_no_runtime, season);
         assert_eq!(result2.runtime, None);
     }
+
+    #[test]
+    fn test_pick_description() {
+        let translations = |json: serde_json::Value| -> Vec<Translation> {
+            serde_json::from_value::<TranslationsResponse>(json)
+                .unwrap()
+                .translations
+        };
+        let season = translations(serde_json::json!({ "translations": [
+            { "iso_639_1": "ja", "iso_3166_1": "JP", "data": { "overview": "" } },
+            { "iso_639_1": "en", "iso_3166_1": "GB", "data": { "overview": "Season (GB)" } },
+        ]}));
+        let show = translations(serde_json::json!({ "translations": [
+            { "iso_639_1": "zh", "iso_3166_1": "TW", "data": { "overview": "節目簡介" } },
+            { "iso_639_1": "en", "iso_3166_1": "US", "data": { "overview": "Show (US)" } },
+        ]}));
+        let sources = [season.as_slice(), show.as_slice()];
+        let priority =
+            |langs: &[&str]| -> Vec<String> { langs.iter().map(|l| l.to_string()).collect() };
+
+        // Empty overviews are skipped; the show is used when the season lacks a language
+        assert_eq!(
+            pick_description(&sources, &priority(&["ja-JP", "zh-TW", "en-US"])),
+            Some(("zh-TW".to_string(), "節目簡介".to_string()))
+        );
+        // The season's other region wins over the show's exact locale
+        assert_eq!(
+            pick_description(&sources, &priority(&["en-US"])),
+            Some(("en-GB".to_string(), "Season (GB)".to_string()))
+        );
+        assert_eq!(pick_description(&sources, &priority(&["ko-KR"])), None);
+    }
+
+    #[test]
+    fn test_add_alternative_titles() {
+        let extras: TmdbExtras = serde_json::from_value(serde_json::json!({
+            "id": 209867,
+            "name": "葬送のフリーレン",
+            "translations": { "translations": [
+                { "iso_639_1": "en", "iso_3166_1": "US", "data": { "name": "Frieren: Beyond Journey's End" } },
+                { "iso_639_1": "zh", "iso_3166_1": "TW", "data": { "name": "葬送的芙莉蓮" } },
+                { "iso_639_1": "ko", "iso_3166_1": "KR", "data": { "name": "" } },
+            ]},
+            "alternative_titles": { "results": [
+                { "iso_3166_1": "US", "title": "Frieren: Beyond Journey's End", "type": "" },
+                { "iso_3166_1": "CN", "title": "葬送的芙莉莲", "type": "" },
+                { "iso_3166_1": "JP", "title": "葬送のフリーレン", "type": "" },
+            ]}
+        }))
+        .unwrap();
+
+        let mut title = model::UniversalTitle {
+            native: Some("葬送のフリーレン".into()),
+            ..Default::default()
+        };
+        add_alternative_titles(
+            &mut title,
+            &extras.translations.translations,
+            extras.alternative_titles.titles,
+        );
+
+        let alternatives: Vec<_> = title
+            .alternatives
+            .iter()
+            .map(|a| (a.title.as_str(), a.language.as_deref(), a.region.as_deref()))
+            .collect();
+        assert_eq!(
+            alternatives,
+            vec![
+                ("Frieren: Beyond Journey's End", Some("en"), Some("US")),
+                ("葬送的芙莉蓮", Some("zh"), Some("TW")),
+                ("葬送的芙莉莲", None, Some("CN")),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_collection_graph() {
+        let parts: Vec<TmdbMovieSummary> = serde_json::from_value(serde_json::json!([
+            { "id": 3, "title": "Part 3", "poster_path": null, "release_date": "" },
+            { "id": 2, "title": "Part 2", "poster_path": "/2.jpg", "release_date": "2012-06-01" },
+            { "id": 1, "title": "Part 1", "poster_path": null, "release_date": "2009-01-01" }
+        ]))
+        .unwrap();
+        let movie: TmdbMovieSummary = serde_json::from_value(serde_json::json!({
+            "id": 2, "title": "Part 2", "poster_path": "/2.jpg", "release_date": "2012-06-01",
+            "belongs_to_collection": { "id": 10, "name": "Collection" }
+        }))
+        .unwrap();
+
+        let graph = collection_graph(movie, parts);
+        assert_eq!(graph.root, "tmdb:movie/2");
+        let ids: Vec<_> = graph.nodes.iter().map(|n| n.id.as_str()).collect();
+        assert_eq!(ids, vec!["tmdb:movie/1", "tmdb:movie/2", "tmdb:movie/3"]);
+        assert_eq!(graph.edges.len(), 2);
+        assert_eq!(graph.edges[0].from, "tmdb:movie/1");
+        assert_eq!(graph.edges[0].relation, RelationType::Sequel);
+        assert_eq!(graph.nodes[1].year, Some(2012));
+        assert_eq!(graph.nodes[1].provider_id, "movie/2");
+    }
+
+    #[test]
+    fn test_seasons_graph() {
+        let show: TmdbShowSummary = serde_json::from_value(serde_json::json!({
+            "id": 209867,
+            "name": "葬送のフリーレン",
+            "poster_path": "/show.jpg",
+            "seasons": [
+                { "season_number": 0, "name": "Specials", "poster_path": null, "air_date": null, "episode_count": 3 },
+                { "season_number": 1, "name": "Season 1", "poster_path": "/s1.jpg", "air_date": "2023-09-29", "episode_count": 28 },
+                { "season_number": 2, "name": "", "poster_path": null, "air_date": "2026-01-16", "episode_count": 10 }
+            ]
+        }))
+        .unwrap();
+
+        let graph = seasons_graph(show, 2);
+        assert_eq!(graph.root, "tmdb:tv/209867/season/2");
+        assert_eq!(graph.nodes.len(), 2);
+        assert_eq!(graph.nodes[0].title.season.as_deref(), Some("Season 1"));
+        assert_eq!(graph.nodes[1].title.season, None);
+        assert_eq!(
+            graph.nodes[1].cover_image.large.as_deref(),
+            Some("https://image.tmdb.org/t/p/w500/show.jpg")
+        );
+        assert_eq!(graph.edges.len(), 1);
+        assert_eq!(graph.edges[0].to, "tmdb:tv/209867/season/2");
+    }
+
+    #[test]
+    fn test_search_candidate() {
+        let tv = serde_json::json!({
+            "id": 209867,
+            "media_type": "tv",
+            "name": "葬送のフリーレン",
+            "original_name": "葬送のフリーレン",
+            "first_air_date": "2023-09-29",
+            "poster_path": "/poster.jpg"
+        });
+        assert_eq!(
+            search_candidate(&tv),
+            Some(Candidate {
+                id: "tv/209867".into(),
+                titles: vec!["葬送のフリーレン".into(), "葬送のフリーレン".into()],
+                year: Some(2023),
+                type_field: Some(ItemType::Tv),
+                episodes: None,
+                poster: Some("https://image.tmdb.org/t/p/w500/poster.jpg".into()),
+            })
+        );
+
+        let movie = serde_json::json!({
+            "id": 1, "media_type": "movie", "title": "Movie", "release_date": ""
+        });
+        let movie = search_candidate(&movie).unwrap();
+        assert_eq!(movie.id, "movie/1");
+        assert_eq!(movie.year, None);
+
+        let person = serde_json::json!({ "id": 2, "media_type": "person", "name": "Someone" });
+        assert_eq!(search_candidate(&person), None);
+    }
 }
@@ -0,0 +1,422 @@
+use crate::datetime::Timestamp;
+use crate::model::{BroadcastSchedule, Item, NextEpisode, UniversalEpisode};
+use serde_derive::Serialize;
+use std::collections::BTreeMap;
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScheduleResponse<'a> {
+    pub time_zone: &'a str,
+    pub from: Timestamp,
+    pub to: Timestamp,
+    pub days: Vec<ScheduleDay<'a>>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScheduleDay<'a> {
+    /// Local calendar date in the requested time zone, `YYYY-MM-DD`.
+    pub date: String,
+    pub airings: Vec<Airing<'a>>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Airing<'a> {
+    pub airs_at: Timestamp,
+    /// Local wall-clock time in the requested time zone, `HH:MM`.
+    pub local_time: String,
+    pub item: &'a Item,
+}
+
+/// Parses an item's `end`. Date-only values mean "through that day".
+pub fn parse_end(value: &str) -> Option<Timestamp> {
+    let end = Timestamp::parse(value)?;
+    Some(if end.time() == (0, 0, 0) {
+        end.add_secs(86399)
+    } else {
+        end
+    })
+}
+
+/// Projects the upcoming episode of an ongoing series.
+///
+/// Known episode air dates (e.g. from TMDb) win; their time of day is taken
+/// from the broadcast recurrence when one airs within a day of the listed
+/// date. Otherwise the recurrence is counted from its start, continuing the
+/// numbering after the last aired episode when a partial list is known.
+pub fn project_next_episode(
+    episodes: &[UniversalEpisode],
+    schedule: Option<&BroadcastSchedule>,
+    end: Option<Timestamp>,
+    now: Timestamp,
+) -> Option<NextEpisode> {
+    let dated: Vec<(i32, Timestamp)> = episodes
+        .iter()
+        .filter_map(|e| Some((e.number, Timestamp::parse(e.air_date.as_deref()?)?)))
+        .collect();
+
+    let mut upcoming: Vec<(i32, Timestamp)> = dated
+        .iter()
+        .copied()
+        .filter(|(_, date)| date.add_secs(86399) >= now)
+        .collect();
+    upcoming.sort_by_key(|(_, date)| *date);
+    // An episode listed for today has aired once its slot, or the day's
+    // start when there's no slot, has passed
+    for (number, date) in upcoming {
+        let airs_at = schedule
+            .and_then(|s| s.next_after(date.add_secs(-86400)))
+            .map(|(_, at)| at)
+            .filter(|at| at.as_secs() - date.as_secs() < 2 * 86400)
+            .unwrap_or(date);
+        if airs_at >= now {
+            return Some(NextEpisode { number, airs_at });
+        }
+    }
+
+    let (index, airs_at) = schedule?.next_after(now)?;
+    if end.is_some_and(|e| airs_at > e) {
+        return None;
+    }
+
+    let number = match dated.iter().map(|(number, _)| *number).max() {
+        Some(last_aired) => last_aired + 1,
+        None => index as i32 + 1,
+    };
+    Some(NextEpisode { number, airs_at })
+}
+
+/// Parses a `from`/`to` query bound. Date-only values are taken as local
+/// midnight in the requested zone, or the last second of that day when
+/// `end_of_day` is set.
+pub fn parse_bound(
+    value: &str,
+    end_of_day: bool,
+    utc_offset: impl Fn(Timestamp) -> i64,
+) -> Option<Timestamp> {
+    let parsed = Timestamp::parse(value)?;
+    if value.trim().len() != 10 {
+        return Some(parsed);
+    }
+    let local = if end_of_day {
+        parsed.add_secs(86399)
+    } else {
+        parsed
+    };
+    Some(local.add_secs(-utc_offset(local)))
+}
+
+/// Expands the items running within `[from, to]` into concrete airings,
+/// grouped by local date.
+///
+/// `utc_offset` maps an instant to the requested zone's offset in seconds;
+/// it is a parameter so the grouping does not depend on the JS runtime.
+pub fn group_airings<'a>(
+    items: &'a [Item],
+    from: Timestamp,
+    to: Timestamp,
+    utc_offset: impl Fn(Timestamp) -> i64,
+) -> Vec<ScheduleDay<'a>> {
+    // Looking up the offset is a JS call, so it's done once per UTC day, and
+    // per airing only on days the offset changes
+    let mut offsets: BTreeMap<i64, Option<i64>> = BTreeMap::new();
+    let mut local = |at: Timestamp| {
+        let day = at.as_secs().div_euclid(86400);
+        let fixed = *offsets.entry(day).or_insert_with(|| {
+            let start = Timestamp::from_secs(day * 86400);
+            let offset = utc_offset(start);
+            (utc_offset(start.add_secs(86399)) == offset).then_some(offset)
+        });
+        at.add_secs(fixed.unwrap_or_else(|| utc_offset(at)))
+    };
+
+    let mut airings: Vec<(String, Airing<'a>)> = Vec::new();
+    for item in items {
+        let begin = item.begin.as_deref().and_then(Timestamp::parse);
+        let end = item.end.as_deref().and_then(parse_end);
+
+        if begin.is_some_and(|b| b > to) || end.is_some_and(|e| e < from) {
+            continue;
+        }
+
+        let window_end = end.map_or(to, |e| e.min(to));
+        let times = match item.broadcast_schedule {
+            Some(schedule) => schedule.occurrences(from, window_end),
+            // Without a recurrence the premiere is the only known airing
+            None => begin
+                .filter(|b| (from..=window_end).contains(b))
+                .into_iter()
+                .collect(),
+        };
+
+        for airs_at in times {
+            let local = local(airs_at);
+            let (year, month, day) = local.date();
+            let (hour, minute, _) = local.time();
+            airings.push((
+                format!("{:04}-{:02}-{:02}", year, month, day),
+                Airing {
+                    airs_at,
+                    local_time: format!("{:02}:{:02}", hour, minute),
+                    item,
+                },
+            ));
+        }
+    }
+
+    airings.sort_by(|(_, a), (_, b)| {
+        a.airs_at
+            .cmp(&b.airs_at)
+            .then_with(|| a.item.title.cmp(&b.item.title))
+    });
+
+    let mut days: BTreeMap<String, Vec<Airing<'a>>> = BTreeMap::new();
+    for (date, airing) in airings {
+        days.entry(date).or_default().push(airing);
+    }
+
+    days.into_iter()
+        .map(|(date, airings)| ScheduleDay { date, airings })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item(title: &str, begin: &str, broadcast: Option<&str>, end: &str) -> Item {
+        let mut item = Item {
+            title: title.to_string(),
+            begin: Some(begin.to_string()),
+            broadcast: broadcast.map(str::to_string),
+            end: Some(end.to_string()),
+            ..Default::default()
+        };
+        item.resolve_schedules();
+        item
+    }
+
+    fn at(s: &str) -> Timestamp {
+        Timestamp::parse(s).unwrap()
+    }
+
+    const JST: i64 = 9 * 3600;
+
+    #[test]
+    fn test_group_airings_by_local_day() {
+        let items = vec![
+            // Saturday 00:00 JST, i.e. Friday 15:00 UTC
+            item(
+                "Late Night",
+                "2024-01-05T15:00:00.000Z",
+                Some("R/2024-01-05T15:00:00.000Z/P7D"),
+                "",
+            ),
+            item(
+                "Morning",
+                "2024-01-06T00:00:00.000Z",
+                Some("R/2024-01-06T00:00:00.000Z/P7D"),
+                "2024-01-13",
+            ),
+        ];
+
+        let days = group_airings(
+            &items,
+            at("2024-01-06T00:00:00+09:00"),
+            at("2024-01-19T23:59:59+09:00"),
+            |_| JST,
+        );
+
+        let dates: Vec<_> = days.iter().map(|d| d.date.as_str()).collect();
+        assert_eq!(dates, vec!["2024-01-06", "2024-01-13"]);
+
+        let first: Vec<_> = days[0]
+            .airings
+            .iter()
+            .map(|a| (a.item.title.as_str(), a.local_time.as_str()))
+            .collect();
+        assert_eq!(first, vec![("Late Night", "00:00"), ("Morning", "09:00")]);
+
+        // A date-only end still includes that day's airing
+        let second: Vec<_> = days[1]
+            .airings
+            .iter()
+            .map(|a| a.item.title.as_str())
+            .collect();
+        assert_eq!(second, vec!["Late Night", "Morning"]);
+    }
+
+    #[test]
+    fn test_group_airings_looks_up_offset_per_day() {
+        let items = vec![
+            item(
+                "Daily",
+                "2024-03-01T12:00:00.000Z",
+                Some("R/2024-03-01T12:00:00.000Z/P1D"),
+                "",
+            ),
+            item(
+                "Evening",
+                "2024-03-01T20:00:00.000Z",
+                Some("R/2024-03-01T20:00:00.000Z/P1D"),
+                "",
+            ),
+        ];
+
+        // Clocks go forward an hour on the second day, between both airings
+        let change = at("2024-03-02T15:00:00Z");
+        let calls = std::cell::Cell::new(0);
+        let offset = |t: Timestamp| {
+            calls.set(calls.get() + 1);
+            if t >= change { 3600 } else { 0 }
+        };
+
+        let days = group_airings(&items, at("2024-03-01"), at("2024-03-03T23:59:59Z"), offset);
+        let times: Vec<_> = days
+            .iter()
+            .flat_map(|d| d.airings.iter().map(|a| a.local_time.as_str()))
+            .collect();
+        assert_eq!(
+            times,
+            vec!["12:00", "20:00", "12:00", "21:00", "13:00", "21:00"]
+        );
+        // Two lookups a day, plus one per airing on the day of the change
+        assert_eq!(calls.get(), 8);
+    }
+
+    fn episode(number: i32, air_date: &str) -> UniversalEpisode {
+        UniversalEpisode {
+            number,
+            air_date: Some(air_date.to_string()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_project_next_episode_from_recurrence() {
+        let schedule: BroadcastSchedule = "R/2024-01-06T15:00:00Z/P7D".parse().unwrap();
+
+        let next = project_next_episode(&[], Some(&schedule), None, at("2024-01-20T16:00:00Z"));
+        assert_eq!(
+            next,
+            Some(NextEpisode {
+                number: 4,
+                airs_at: at("2024-01-27T15:00:00Z"),
+            })
+        );
+
+        // Finished before the next airing
+        let ended = project_next_episode(
+            &[],
+            Some(&schedule),
+            parse_end("2024-01-20"),
+            at("2024-01-20T16:00:00Z"),
+        );
+        assert_eq!(ended, None);
+
+        assert_eq!(
+            project_next_episode(&[], None, None, at("2024-01-20")),
+            None
+        );
+    }
+
+    #[test]
+    fn test_project_next_episode_prefers_air_dates() {
+        // Episode 3 was delayed a week, so pure recurrence math would be off by one
+        let episodes = vec![
+            episode(1, "2024-01-07"),
+            episode(2, "2024-01-14"),
+            episode(3, "2024-01-28"),
+        ];
+        let schedule: BroadcastSchedule = "R/2024-01-06T15:00:00Z/P7D".parse().unwrap();
+
+        let next =
+            project_next_episode(&episodes, Some(&schedule), None, at("2024-01-20T00:00:00Z"));
+        assert_eq!(
+            next,
+            Some(NextEpisode {
+                number: 3,
+                airs_at: at("2024-01-27T15:00:00Z"),
+            })
+        );
+
+        // Without a recurrence the listed date is used as-is
+        let next = project_next_episode(&episodes, None, None, at("2024-01-20T00:00:00Z"));
+        assert_eq!(next.unwrap().airs_at, at("2024-01-28"));
+
+        // Listed for today but its slot has passed
+        let next =
+            project_next_episode(&episodes, Some(&schedule), None, at("2024-01-28T10:00:00Z"));
+        assert_eq!(
+            next,
+            Some(NextEpisode {
+                number: 4,
+                airs_at: at("2024-02-03T15:00:00Z"),
+            })
+        );
+        assert_eq!(
+            project_next_episode(&episodes, None, None, at("2024-01-28T10:00:00Z")),
+            None
+        );
+
+        // Past the last listed episode, continue numbering from it
+        let next =
+            project_next_episode(&episodes, Some(&schedule), None, at("2024-01-29T00:00:00Z"));
+        assert_eq!(
+            next,
+            Some(NextEpisode {
+                number: 4,
+                airs_at: at("2024-02-03T15:00:00Z"),
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_bound() {
+        assert_eq!(
+            parse_bound("2024-01-06", false, |_| JST)
+                .unwrap()
+                .to_string(),
+            "2024-01-05T15:00:00Z"
+        );
+        assert_eq!(
+            parse_bound("2024-01-06", true, |_| JST)
+                .unwrap()
+                .to_string(),
+            "2024-01-06T14:59:59Z"
+        );
+        assert_eq!(
+            parse_bound("2024-01-06T12:00:00Z", true, |_| JST)
+                .unwrap()
+                .to_string(),
+            "2024-01-06T12:00:00Z"
+        );
+        assert!(parse_bound("tomorrow", false, |_| JST).is_none());
+    }
+
+    #[test]
+    fn test_group_airings_skips_items_outside_window() {
+        let items = vec![
+            item(
+                "Finished",
+                "2023-10-01T15:00:00.000Z",
+                Some("R/2023-10-01T15:00:00.000Z/P7D"),
+                "2023-12-24T15:00:00.000Z",
+            ),
+            item(
+                "Upcoming",
+                "2024-04-01T15:00:00.000Z",
+                Some("R/2024-04-01T15:00:00.000Z/P7D"),
+                "",
+            ),
+            item("Movie", "2024-01-10T00:00:00.000Z", None, ""),
+        ];
+
+        let days = group_airings(&items, at("2024-01-06"), at("2024-01-12"), |_| 0);
+
+        assert_eq!(days.len(), 1);
+        assert_eq!(days[0].date, "2024-01-10");
+        assert_eq!(days[0].airings[0].item.title, "Movie");
+    }
+}
@@ -0,0 +1,422 @@
+use crate::model::Item;
+use serde_derive::Serialize;
+
+pub mod index;
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchHit<'a> {
+    /// Year and season of the data file listing the item, for linking to
+    /// `/api/items`.
+    pub year: i32,
+    pub season: &'static str,
+    pub score: u32,
+    /// The title or translation that matched the query best.
+    pub matched_title: &'a str,
+    pub item: &'a Item,
+}
+
+// Match quality, best first
+const SCORE_EXACT: u32 = 100;
+const SCORE_PREFIX: u32 = 75;
+const SCORE_SUBSTRING: u32 = 50;
+const SCORE_ALL_TERMS: u32 = 25;
+
+/// Ranks `items` against `query`, matching the title and every translation.
+/// A `site:id` query such as `mal:52991` matches that site ID exactly.
+///
+/// `items` pairs each item with the year and month of the data file it came
+/// from, which the hit's `year` and `season` are taken from so that they
+/// point at the season listing the item. Hits are ordered by score, then
+/// newest first.
+pub fn search<'a>(
+    query: &str,
+    items: impl IntoIterator<Item = (i32, u32, &'a Item)>,
+    limit: usize,
+) -> Vec<SearchHit<'a>> {
+    let needle = normalize(query);
+    let terms: Vec<String> = query
+        .split_whitespace()
+        .map(normalize)
+        .filter(|t| !t.is_empty())
+        .collect();
+    if needle.is_empty() {
+        return Vec::new();
+    }
+
+    let mut hits: Vec<SearchHit<'a>> = items
+        .into_iter()
+        .filter_map(|(year, month, item)| {
+            let (score, matched_title) = if has_site_id(item, query) {
+                (SCORE_EXACT, item.title.as_str())
+            } else {
+                titles(item)
+                    .filter_map(|title| Some((score(&normalize(title), &needle, &terms)?, title)))
+                    .max_by_key(|(score, _)| *score)?
+            };
+
+            Some(SearchHit {
+                year,
+                season: season_of_month(month),
+                score,
+                matched_title,
+                item,
+            })
+        })
+        .collect();
+
+    hits.sort_by(|a, b| {
+        b.score
+            .cmp(&a.score)
+            .then_with(|| b.item.begin.cmp(&a.item.begin))
+            .then_with(|| a.item.title.cmp(&b.item.title))
+    });
+    hits.truncate(limit);
+    hits
+}
+
+fn score(title: &str, needle: &str, terms: &[String]) -> Option<u32> {
+    if title == needle {
+        Some(SCORE_EXACT)
+    } else if title.starts_with(needle) {
+        Some(SCORE_PREFIX)
+    } else if title.contains(needle) {
+        Some(SCORE_SUBSTRING)
+    } else if terms.len() > 1 && terms.iter().all(|t| title.contains(t.as_str())) {
+        Some(SCORE_ALL_TERMS)
+    } else {
+        None
+    }
+}
+
+fn has_site_id(item: &Item, query: &str) -> bool {
+    let Some((site, id)) = query.trim().split_once(':') else {
+        return false;
+    };
+    item.sites
+        .iter()
+        .any(|s| s.site.eq_ignore_ascii_case(site.trim()) && s.id.as_deref() == Some(id.trim()))
+}
+
+fn titles(item: &Item) -> impl Iterator<Item = &str> {
+    let translate = &item.title_translate;
+    std::iter::once(item.title.as_str()).chain(
+        [
+            &translate.ja,
+            &translate.en,
+            &translate.zh_hans,
+            &translate.zh_hant,
+        ]
+        .into_iter()
+        .flatten()
+        .flatten()
+        .map(String::as_str),
+    )
+}
+
+fn season_of_month(month: u32) -> &'static str {
+    match month {
+        1..=3 => "Winter",
+        4..=6 => "Spring",
+        7..=9 => "Summer",
+        _ => "Autumn",
+    }
+}
+
+/// Folds a title into a form where trivially different spellings compare
+/// equal: case, full-width ASCII, katakana vs hiragana, traditional Chinese
+/// or Japanese kanji vs simplified Chinese, and punctuation or spacing.
+pub fn normalize(s: &str) -> String {
+    s.chars()
+        .map(|c| match c {
+            // Full-width ASCII variants
+            '\u{FF01}'..='\u{FF5E}' => char::from_u32(c as u32 - 0xFEE0).unwrap_or(c),
+            // Katakana to hiragana; ヷ-ヺ have no hiragana counterpart
+            '\u{30A1}'..='\u{30F6}' => char::from_u32(c as u32 - 0x60).unwrap_or(c),
+            _ => simplify(c),
+        })
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+// Traditional Chinese and Japanese shinjitai forms that commonly appear in
+// titles, mapped to their simplified Chinese equivalent. Not exhaustive; it
+// only needs to cover characters that make titles disagree across languages.
+const VARIANTS: &[(char, char)] = &[
+    ('與', '与'),
+    ('個', '个'),
+    ('們', '们'),
+    ('來', '来'),
+    ('傳', '传'),
+    ('伝', '传'),
+    ('僕', '仆'),
+    ('兒', '儿'),
+    ('児', '儿'),
+    ('剣', '剑'),
+    ('劍', '剑'),
+    ('動', '动'),
+    ('勝', '胜'),
+    ('區', '区'),
+    ('處', '处'),
+    ('処', '处'),
+    ('國', '国'),
+    ('圖', '图'),
+    ('図', '图'),
+    ('園', '园'),
+    ('圓', '圆'),
+    ('円', '圆'),
+    ('堅', '坚'),
+    ('場', '场'),
+    ('奪', '夺'),
+    ('姫', '姬'),
+    ('學', '学'),
+    ('實', '实'),
+    ('実', '实'),
+    ('對', '对'),
+    ('対', '对'),
+    ('將', '将'),
+    ('專', '专'),
+    ('島', '岛'),
+    ('帰', '归'),
+    ('歸', '归'),
+    ('師', '师'),
+    ('強', '强'),
+    ('後', '后'),
+    ('從', '从'),
+    ('従', '从'),
+    ('戀', '恋'),
+    ('愛', '爱'),
+    ('戦', '战'),
+    ('戰', '战'),
+    ('擊', '击'),
+    ('撃', '击'),
+    ('敗', '败'),
+    ('時', '时'),
+    ('書', '书'),
+    ('會', '会'),
+    ('東', '东'),
+    ('機', '机'),
+    ('樂', '乐'),
+    ('楽', '乐'),
+    ('歲', '岁'),
+    ('歳', '岁'),
+    ('殺', '杀'),
+    ('気', '气'),
+    ('氣', '气'),
+    ('無', '无'),
+    ('為', '为'),
+    ('爲', '为'),
+    ('獸', '兽'),
+    ('獣', '兽'),
+    ('異', '异'),
+    ('畫', '画'),
+    ('發', '发'),
+    ('発', '发'),
+    ('盜', '盗'),
+    ('種', '种'),
+    ('節', '节'),
+    ('約', '约'),
+    ('紀', '纪'),
+    ('級', '级'),
+    ('紅', '红'),
+    ('純', '纯'),
+    ('結', '结'),
+    ('絵', '绘'),
+    ('繪', '绘'),
+    ('緑', '绿'),
+    ('綠', '绿'),
+    ('線', '线'),
+    ('縁', '缘'),
+    ('緣', '缘'),
+    ('總', '总'),
+    ('総', '总'),
+    ('聖', '圣'),
+    ('聲', '声'),
+    ('蓮', '莲'),
+    ('蟲', '虫'),
+    ('術', '术'),
+    ('裏', '里'),
+    ('裡', '里'),
+    ('見', '见'),
+    ('覚', '觉'),
+    ('覺', '觉'),
+    ('記', '记'),
+    ('話', '话'),
+    ('語', '语'),
+    ('説', '说'),
+    ('說', '说'),
+    ('譚', '谭'),
+    ('變', '变'),
+    ('変', '变'),
+    ('貓', '猫'),
+    ('貴', '贵'),
+    ('買', '买'),
+    ('賣', '卖'),
+    ('売', '卖'),
+    ('車', '车'),
+    ('転', '转'),
+    ('轉', '转'),
+    ('這', '这'),
+    ('連', '连'),
+    ('進', '进'),
+    ('遊', '游'),
+    ('運', '运'),
+    ('過', '过'),
+    ('達', '达'),
+    ('還', '还'),
+    ('選', '选'),
+    ('郷', '乡'),
+    ('鄉', '乡'),
+    ('録', '录'),
+    ('錄', '录'),
+    ('鉄', '铁'),
+    ('鐵', '铁'),
+    ('銀', '银'),
+    ('鋼', '钢'),
+    ('長', '长'),
+    ('門', '门'),
+    ('開', '开'),
+    ('間', '间'),
+    ('関', '关'),
+    ('關', '关'),
+    ('闘', '斗'),
+    ('鬥', '斗'),
+    ('陰', '阴'),
+    ('陽', '阳'),
+    ('隊', '队'),
+    ('際', '际'),
+    ('雙', '双'),
+    ('難', '难'),
+    ('雲', '云'),
+    ('電', '电'),
+    ('霊', '灵'),
+    ('靈', '灵'),
+    ('頭', '头'),
+    ('願', '愿'),
+    ('風', '风'),
+    ('飛', '飞'),
+    ('館', '馆'),
+    ('馬', '马'),
+    ('騎', '骑'),
+    ('驚', '惊'),
+    ('鬪', '斗'),
+    ('魚', '鱼'),
+    ('鳥', '鸟'),
+    ('黃', '黄'),
+    ('龍', '龙'),
+    ('竜', '龙'),
+];
+
+fn simplify(c: char) -> char {
+    // Everything in the table is a CJK unified ideograph
+    if !('\u{4E00}'..='\u{9FFF}').contains(&c) {
+        return c;
+    }
+    VARIANTS
+        .iter()
+        .find(|(from, _)| *from == c)
+        .map_or(c, |(_, to)| *to)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn items() -> Vec<Item> {
+        serde_json::from_str(
+            r#"[
+                {
+                    "title": "進撃の巨人",
+                    "titleTranslate": { "zh-Hans": ["进击的巨人"], "en": ["Attack on Titan"] },
+                    "type": "tv", "lang": "ja", "officialSite": "",
+                    "begin": "2013-04-06T16:58:00.000Z", "sites": []
+                },
+                {
+                    "title": "進撃の巨人 Season 2",
+                    "titleTranslate": { "zh-Hant": ["進擊的巨人 第二季"] },
+                    "type": "tv", "lang": "ja", "officialSite": "",
+                    "begin": "2017-03-31T15:30:00.000Z", "sites": []
+                },
+                {
+                    "title": "ソードアート・オンライン",
+                    "titleTranslate": { "en": ["Sword Art Online"] },
+                    "type": "tv", "lang": "ja", "officialSite": "",
+                    "begin": "2012-07-07T15:00:00.000Z",
+                    "sites": [{ "site": "mal", "id": "11757" }]
+                },
+                {
+                    "title": "鉄腕アトム",
+                    "titleTranslate": {},
+                    "type": "tv", "lang": "ja", "officialSite": "",
+                    "sites": []
+                }
+            ]"#,
+        )
+        .unwrap()
+    }
+
+    // The data file each fixture item is listed in
+    fn indexed(items: &[Item]) -> impl Iterator<Item = (i32, u32, &Item)> {
+        let files = [(2013, 4), (2017, 4), (2012, 7), (1963, 1)];
+        files
+            .into_iter()
+            .zip(items)
+            .map(|((year, month), item)| (year, month, item))
+    }
+
+    fn found<'a>(hits: &[SearchHit<'a>]) -> Vec<&'a str> {
+        hits.iter().map(|h| h.item.title.as_str()).collect()
+    }
+
+    #[test]
+    fn test_normalize() {
+        assert_eq!(normalize("ＳＰＹ×ＦＡＭＩＬＹ"), "spyfamily");
+        assert_eq!(
+            normalize("ソードアート・オンライン"),
+            "そーどあーとおんらいん"
+        );
+        assert_eq!(normalize("進撃の巨人"), normalize("进击の巨人"));
+        assert_eq!(normalize("進擊的巨人"), normalize("进击的巨人"));
+        assert_eq!(normalize("Re:ゼロ"), "reぜろ");
+    }
+
+    #[test]
+    fn test_search_ranking() {
+        let items = items();
+
+        let hits = search("進撃の巨人", indexed(&items), 10);
+        assert_eq!(found(&hits), vec!["進撃の巨人", "進撃の巨人 Season 2"]);
+        assert_eq!(hits[0].score, SCORE_EXACT);
+        assert_eq!((hits[0].year, hits[0].season), (2013, "Spring"));
+        // Premiered on April 1st in Japan, which is still March in UTC
+        assert_eq!((hits[1].year, hits[1].season), (2017, "Spring"));
+
+        // Simplified Chinese query against traditional and Japanese titles
+        let hits = search("进击的巨人", indexed(&items), 10);
+        assert_eq!(found(&hits), vec!["進撃の巨人", "進撃の巨人 Season 2"]);
+        assert_eq!(hits[1].matched_title, "進擊的巨人 第二季");
+    }
+
+    #[test]
+    fn test_search_kana_and_terms() {
+        let items = items();
+
+        let hits = search("そーどあーと", indexed(&items), 10);
+        assert_eq!(found(&hits), vec!["ソードアート・オンライン"]);
+        assert_eq!(hits[0].score, SCORE_PREFIX);
+
+        let hits = search("online sword", indexed(&items), 10);
+        assert_eq!(found(&hits), vec!["ソードアート・オンライン"]);
+        assert_eq!(hits[0].matched_title, "Sword Art Online");
+
+        let hits = search("鐵腕", indexed(&items), 10);
+        assert_eq!((hits[0].year, hits[0].season), (1963, "Winter"));
+
+        assert!(search("  ", indexed(&items), 10).is_empty());
+
+        let hits = search("MAL:11757", indexed(&items), 10);
+        assert_eq!(found(&hits), vec!["ソードアート・オンライン"]);
+        assert_eq!(search("巨人", indexed(&items), 1).len(), 1);
+    }
+}
@@ -0,0 +1,519 @@
+use super::normalize;
+use crate::config;
+use crate::kv::{self, KeyValueStore};
+use crate::model::Item;
+use serde::de::DeserializeOwned;
+use serde_derive::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use worker::Result;
+
+/// Where the index builder gets a year's worth of items from, each paired
+/// with the month of the data file listing it.
+pub trait ItemSource {
+    async fn fetch_year(&self, year: i32) -> Result<Vec<(u32, Item)>>;
+}
+
+/// An indexed item and the data file it came from.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Doc {
+    #[serde(rename = "y")]
+    pub year: i32,
+    #[serde(rename = "m")]
+    pub month: u32,
+    #[serde(rename = "i")]
+    pub item: Item,
+}
+
+impl Doc {
+    fn tokens(&self) -> BTreeSet<String> {
+        let mut tokens: BTreeSet<String> = super::titles(&self.item)
+            .flat_map(|title| {
+                let key = normalize(title);
+                // A one-character title has no bigrams, so it's indexed whole
+                if key.chars().count() == 1 {
+                    vec![key]
+                } else {
+                    bigrams(&key)
+                }
+            })
+            .collect();
+        tokens.extend(
+            self.item
+                .sites
+                .iter()
+                .filter_map(|s| Some(site_token(&s.site, s.id.as_deref()?))),
+        );
+        tokens
+    }
+}
+
+// Postings point at a document by its year and position within that year
+type DocRef = (i32, u32);
+type Shard = BTreeMap<String, Vec<DocRef>>;
+
+/// Inverted index over titles and site IDs of every bangumi-data year.
+///
+/// Titles are indexed by character bigrams of their normalized form, which
+/// works for CJK text without a tokenizer; site IDs are indexed as
+/// `site:id`. Postings are spread over [`config::SEARCH_INDEX_SHARDS`]
+/// values by a hash of the token, and documents are stored in chunks of
+/// [`config::SEARCH_INDEX_CHUNK`] per year, so a search only reads the
+/// shards of its tokens and the chunks holding candidates.
+///
+/// This struct is what's kept under [`config::SEARCH_INDEX_KEY`]. The index
+/// is refreshed a few years at a time, so `cursor` remembers where the last
+/// run stopped.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct SearchIndex {
+    #[serde(default)]
+    pub cursor: i32,
+    /// Whether every year has been indexed. Until then searches are not
+    /// served from the index, since most years would be missing.
+    #[serde(default)]
+    pub complete: bool,
+    /// Documents stored for each year.
+    #[serde(default)]
+    pub years: BTreeMap<i32, u32>,
+}
+
+impl SearchIndex {
+    pub async fn load(store: &impl KeyValueStore) -> Result<Self> {
+        match store.get_text(config::SEARCH_INDEX_KEY).await? {
+            Some(text) => Ok(serde_json::from_str(&text)?),
+            None => Ok(Self::default()),
+        }
+    }
+
+    pub async fn save(&self, store: &impl KeyValueStore) -> Result<()> {
+        store
+            .put_text(config::SEARCH_INDEX_KEY, serde_json::to_string(self)?)
+            .await
+    }
+
+    /// The current year plus the next `years_per_run` years after `cursor`,
+    /// wrapping back to [`config::START_YEAR`]. Advances `cursor`, and marks
+    /// the index complete once it has wrapped.
+    fn next_years(&mut self, current_year: i32, years_per_run: usize) -> Vec<i32> {
+        let past = config::START_YEAR..current_year;
+        let mut cursor = if past.contains(&self.cursor) {
+            self.cursor
+        } else {
+            config::START_YEAR
+        };
+
+        let mut years = vec![current_year];
+        for _ in 0..years_per_run.min(past.len()) {
+            years.push(cursor);
+            cursor = if cursor + 1 < current_year {
+                cursor + 1
+            } else {
+                self.complete = true;
+                config::START_YEAR
+            };
+        }
+        self.cursor = cursor;
+        years
+    }
+}
+
+/// Refreshes the next batch of years, see [`SearchIndex::next_years`].
+/// Returns the years that were refreshed.
+pub async fn update(
+    store: &impl KeyValueStore,
+    source: &impl ItemSource,
+    current_year: i32,
+) -> Result<Vec<i32>> {
+    refresh(
+        store,
+        source,
+        current_year,
+        config::SEARCH_INDEX_YEARS_PER_RUN,
+    )
+    .await
+}
+
+async fn refresh(
+    store: &impl KeyValueStore,
+    source: &impl ItemSource,
+    current_year: i32,
+    years_per_run: usize,
+) -> Result<Vec<i32>> {
+    let mut index = SearchIndex::load(store).await?;
+    let years = index.next_years(current_year, years_per_run);
+
+    let fetched = futures::future::join_all(years.iter().map(|&y| source.fetch_year(y))).await;
+    let mut docs: BTreeMap<i32, Vec<Doc>> = BTreeMap::new();
+    for (&year, items) in years.iter().zip(fetched) {
+        let items = items?.into_iter();
+        docs.insert(
+            year,
+            items
+                .map(|(month, item)| Doc { year, month, item })
+                .collect(),
+        );
+    }
+
+    for (&year, docs) in &docs {
+        let stored = index.years.get(&year).copied().unwrap_or(0) as usize;
+        let chunks: Vec<&[Doc]> = docs.chunks(config::SEARCH_INDEX_CHUNK).collect();
+        for (chunk, docs) in chunks.iter().enumerate() {
+            put_changed(store, &chunk_key(year, chunk), docs).await?;
+        }
+        for chunk in chunks.len()..stored.div_ceil(config::SEARCH_INDEX_CHUNK) {
+            store.delete(&chunk_key(year, chunk)).await?;
+        }
+        index.years.insert(year, docs.len() as u32);
+    }
+
+    let mut added: Vec<Shard> = vec![Shard::new(); config::SEARCH_INDEX_SHARDS];
+    for (&year, docs) in &docs {
+        for (i, doc) in docs.iter().enumerate() {
+            for token in doc.tokens() {
+                let shard = kv::shard_of(&token, config::SEARCH_INDEX_SHARDS);
+                added[shard]
+                    .entry(token)
+                    .or_default()
+                    .push((year, i as u32));
+            }
+        }
+    }
+
+    // Every shard may hold postings of the refreshed years, so each is read,
+    // but only those that changed are written back
+    for (i, added) in added.into_iter().enumerate() {
+        let mut shard = get_json::<Shard>(store, shard_key(i))
+            .await?
+            .unwrap_or_default();
+        shard.retain(|_, refs| {
+            refs.retain(|(year, _)| !docs.contains_key(year));
+            !refs.is_empty()
+        });
+        for (token, refs) in added {
+            let postings = shard.entry(token).or_default();
+            postings.extend(refs);
+            postings.sort_unstable();
+        }
+        put_changed(store, &shard_key(i), &shard).await?;
+    }
+
+    let mut ongoing = get_json::<Vec<Doc>>(store, ongoing_key())
+        .await?
+        .unwrap_or_default();
+    ongoing.retain(|doc| !docs.contains_key(&doc.year));
+    ongoing.extend(
+        docs.values()
+            .flatten()
+            .filter(|doc| is_ongoing(&doc.item))
+            .cloned(),
+    );
+    ongoing.sort_by_key(|doc| doc.year);
+    put_changed(store, &ongoing_key(), &ongoing).await?;
+
+    index.save(store).await?;
+    Ok(years)
+}
+
+/// Items of every year that air on a recurrence with no end in sight, so
+/// that the schedule can include shows that premiered long ago. `None`
+/// until the index covers every year.
+pub async fn ongoing(store: &impl KeyValueStore) -> Result<Option<Vec<Doc>>> {
+    if !SearchIndex::load(store).await?.complete {
+        return Ok(None);
+    }
+    Ok(Some(
+        get_json(store, ongoing_key()).await?.unwrap_or_default(),
+    ))
+}
+
+fn is_ongoing(item: &Item) -> bool {
+    item.broadcast.is_some() && item.end.as_deref().is_none_or(|e| e.trim().is_empty())
+}
+
+/// Documents that may match `query`: those sharing a site ID with it, or
+/// containing every bigram of every query term. Callers still rank the
+/// result with [`super::search`]. `None` until the index covers every year.
+pub async fn candidates(store: &impl KeyValueStore, query: &str) -> Result<Option<Vec<Doc>>> {
+    let index = SearchIndex::load(store).await?;
+    if !index.complete {
+        return Ok(None);
+    }
+
+    let mut refs = match query.trim().split_once(':') {
+        Some((site, id)) => postings(store, &BTreeSet::from([site_token(site, id)])).await?,
+        None => Vec::new(),
+    };
+    if refs.is_empty() {
+        let mut tokens: BTreeSet<String> = query
+            .split_whitespace()
+            .flat_map(|term| bigrams(&normalize(term)))
+            .collect();
+        if tokens.is_empty() {
+            // Single-character queries match single-character titles
+            tokens.extend(Some(normalize(query)).filter(|key| !key.is_empty()));
+        }
+        refs = postings(store, &tokens).await?;
+    }
+
+    let mut chunks: BTreeMap<(i32, usize), Vec<usize>> = BTreeMap::new();
+    for (year, i) in refs {
+        let i = i as usize;
+        chunks
+            .entry((year, i / config::SEARCH_INDEX_CHUNK))
+            .or_default()
+            .push(i % config::SEARCH_INDEX_CHUNK);
+    }
+    let loaded = futures::future::join_all(
+        chunks
+            .keys()
+            .map(|&(year, chunk)| get_json::<Vec<Doc>>(store, chunk_key(year, chunk))),
+    )
+    .await;
+
+    let mut docs = Vec::new();
+    for (positions, chunk) in chunks.values().zip(loaded) {
+        let Some(chunk) = chunk? else {
+            continue;
+        };
+        // A refresh may be midway, so positions past the end are skipped
+        docs.extend(positions.iter().filter_map(|&i| chunk.get(i).cloned()));
+    }
+    Ok(Some(docs))
+}
+
+/// The documents holding every token in `tokens`, in index order.
+async fn postings(store: &impl KeyValueStore, tokens: &BTreeSet<String>) -> Result<Vec<DocRef>> {
+    if tokens.is_empty() {
+        return Ok(Vec::new());
+    }
+    let shards: BTreeSet<usize> = tokens
+        .iter()
+        .map(|t| kv::shard_of(t, config::SEARCH_INDEX_SHARDS))
+        .collect();
+    let loaded = futures::future::join_all(
+        shards
+            .iter()
+            .map(|&i| get_json::<Shard>(store, shard_key(i))),
+    )
+    .await;
+    let mut loaded_shards: BTreeMap<usize, Shard> = BTreeMap::new();
+    for (&i, shard) in shards.iter().zip(loaded) {
+        loaded_shards.insert(i, shard?.unwrap_or_default());
+    }
+
+    let mut matched: Option<Vec<DocRef>> = None;
+    for token in tokens {
+        let shard = &loaded_shards[&kv::shard_of(token, config::SEARCH_INDEX_SHARDS)];
+        let Some(refs) = shard.get(token) else {
+            return Ok(Vec::new());
+        };
+        matched = Some(match matched {
+            None => refs.clone(),
+            Some(prev) => intersect(&prev, refs),
+        });
+    }
+    Ok(matched.unwrap_or_default())
+}
+
+async fn get_json<T: DeserializeOwned>(
+    store: &impl KeyValueStore,
+    key: String,
+) -> Result<Option<T>> {
+    match store.get_text(&key).await? {
+        Some(text) => Ok(Some(serde_json::from_str(&text)?)),
+        None => Ok(None),
+    }
+}
+
+// Skips the write when the stored value is already the same, which it is for
+// most past years, since KV writes are limited per day
+async fn put_changed<T: serde::Serialize + ?Sized>(
+    store: &impl KeyValueStore,
+    key: &str,
+    value: &T,
+) -> Result<()> {
+    let text = serde_json::to_string(value)?;
+    if store.get_text(key).await?.as_deref() != Some(text.as_str()) {
+        store.put_text(key, text).await?;
+    }
+    Ok(())
+}
+
+fn shard_key(shard: usize) -> String {
+    format!("{}:postings:{}", config::SEARCH_INDEX_KEY, shard)
+}
+
+fn ongoing_key() -> String {
+    format!("{}:ongoing", config::SEARCH_INDEX_KEY)
+}
+
+fn chunk_key(year: i32, chunk: usize) -> String {
+    format!("{}:docs:{}:{}", config::SEARCH_INDEX_KEY, year, chunk)
+}
+
+fn site_token(site: &str, id: &str) -> String {
+    format!("{}:{}", site.trim().to_lowercase(), id.trim())
+}
+
+fn bigrams(key: &str) -> Vec<String> {
+    let chars: Vec<char> = key.chars().collect();
+    chars.windows(2).map(|w| w.iter().collect()).collect()
+}
+
+// Both inputs are sorted, since postings are kept in index order
+fn intersect(a: &[DocRef], b: &[DocRef]) -> Vec<DocRef> {
+    let (mut i, mut j) = (0, 0);
+    let mut out = Vec::new();
+    while i < a.len() && j < b.len() {
+        match a[i].cmp(&b[j]) {
+            std::cmp::Ordering::Less => i += 1,
+            std::cmp::Ordering::Greater => j += 1,
+            std::cmp::Ordering::Equal => {
+                out.push(a[i]);
+                i += 1;
+                j += 1;
+            }
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kv::MemoryStore;
+    use futures::executor::block_on;
+    use std::collections::HashMap;
+
+    struct FixtureSource(HashMap<i32, Vec<Item>>);
+
+    impl ItemSource for FixtureSource {
+        async fn fetch_year(&self, year: i32) -> Result<Vec<(u32, Item)>> {
+            let items = self.0.get(&year).cloned().unwrap_or_default();
+            Ok(items.into_iter().map(|item| (10, item)).collect())
+        }
+    }
+
+    fn item(title: &str, mal_id: &str) -> Item {
+        serde_json::from_value(serde_json::json!({
+            "title": title,
+            "titleTranslate": { "en": [format!("{} (en)", title)] },
+            "type": "tv", "lang": "ja", "officialSite": "",
+            "begin": "2024-10-01T15:00:00.000Z", "broadcast": "R/2024-10-01T15:00:00.000Z/P7D",
+            "sites": [{ "site": "mal", "id": mal_id }]
+        }))
+        .unwrap()
+    }
+
+    fn finished(title: &str, mal_id: &str) -> Item {
+        Item {
+            end: Some("2024-12-24".into()),
+            ..item(title, mal_id)
+        }
+    }
+
+    const CURRENT: i32 = config::START_YEAR + 3;
+
+    fn source() -> FixtureSource {
+        FixtureSource(HashMap::from([
+            (config::START_YEAR, vec![item("桃太郎 海の神兵", "1")]),
+            (
+                config::START_YEAR + 1,
+                vec![item("進撃の巨人", "16498"), finished("K", "13183")],
+            ),
+            (CURRENT, vec![item("葬送のフリーレン", "52991")]),
+        ]))
+    }
+
+    fn titles(store: &MemoryStore, query: &str) -> Vec<String> {
+        block_on(candidates(store, query))
+            .unwrap()
+            .unwrap()
+            .into_iter()
+            .map(|d| d.item.title)
+            .collect()
+    }
+
+    #[test]
+    fn test_next_years_walks_incrementally() {
+        let mut index = SearchIndex::default();
+
+        let years = index.next_years(CURRENT, 2);
+        assert_eq!(
+            years,
+            vec![CURRENT, config::START_YEAR, config::START_YEAR + 1]
+        );
+        assert!(!index.complete);
+
+        // The next run continues after the cursor and wraps around
+        let years = index.next_years(CURRENT, 2);
+        assert_eq!(
+            years,
+            vec![CURRENT, config::START_YEAR + 2, config::START_YEAR]
+        );
+        assert_eq!(index.cursor, config::START_YEAR + 1);
+        assert!(index.complete);
+    }
+
+    #[test]
+    fn test_refresh_serves_once_complete() {
+        let store = MemoryStore::default();
+        let source = source();
+
+        block_on(refresh(&store, &source, CURRENT, 2)).unwrap();
+        assert_eq!(block_on(candidates(&store, "進撃")).unwrap(), None);
+        assert_eq!(block_on(ongoing(&store)).unwrap(), None);
+
+        block_on(refresh(&store, &source, CURRENT, 2)).unwrap();
+        let docs = block_on(candidates(&store, "mal:52991")).unwrap().unwrap();
+        assert_eq!(
+            docs,
+            vec![Doc {
+                year: CURRENT,
+                month: 10,
+                item: item("葬送のフリーレン", "52991"),
+            }]
+        );
+
+        let ongoing: Vec<_> = block_on(ongoing(&store))
+            .unwrap()
+            .unwrap()
+            .into_iter()
+            .map(|d| d.item.title)
+            .collect();
+        assert_eq!(
+            ongoing,
+            vec!["桃太郎 海の神兵", "進撃の巨人", "葬送のフリーレン"]
+        );
+    }
+
+    #[test]
+    fn test_refresh_replaces_year() {
+        let store = MemoryStore::default();
+        let mut source = source();
+        block_on(refresh(&store, &source, CURRENT, 3)).unwrap();
+        assert_eq!(titles(&store, "進撃"), vec!["進撃の巨人"]);
+
+        source.0.insert(
+            config::START_YEAR + 1,
+            vec![item("薬屋のひとりごと", "54492")],
+        );
+        block_on(refresh(&store, &source, CURRENT, 3)).unwrap();
+        assert!(titles(&store, "進撃").is_empty());
+        assert!(titles(&store, "mal:16498").is_empty());
+        assert_eq!(titles(&store, "薬屋"), vec!["薬屋のひとりごと"]);
+    }
+
+    #[test]
+    fn test_candidates() {
+        let store = MemoryStore::default();
+        block_on(refresh(&store, &source(), CURRENT, 3)).unwrap();
+
+        assert_eq!(titles(&store, "进击"), vec!["進撃の巨人"]);
+        assert_eq!(titles(&store, "フリーレン 葬送"), vec!["葬送のフリーレン"]);
+        assert_eq!(titles(&store, "MAL:52991"), vec!["葬送のフリーレン"]);
+        assert_eq!(titles(&store, "フリーレン (en)"), vec!["葬送のフリーレン"]);
+        assert!(titles(&store, "ガンダム").is_empty());
+        assert_eq!(titles(&store, "ｋ"), vec!["K"]);
+        assert!(titles(&store, "の").is_empty());
+    }
+}
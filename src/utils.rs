@@ -1,19 +1,58 @@
 use worker::*;
 
 pub async fn fetch_json<T: for<'de> serde::Deserialize<'de>>(url: &str) -> Result<Option<T>> {
+    fetch_json_with_headers(url, &[]).await
+}
+
+/// Like [`fetch_json`], for APIs that want extra headers such as a
+/// `User-Agent`.
+pub async fn fetch_json_with_headers<T: for<'de> serde::Deserialize<'de>>(
+    url: &str,
+    extra_headers: &[(&str, &str)],
+) -> Result<Option<T>> {
+    match fetch_cached(url, extra_headers, crate::config::CACHE_TTL_SECONDS).await? {
+        Some(mut response) => response.json().await.map(Some),
+        None => Ok(None),
+    }
+}
+
+/// A `cache_ttl` that keeps responses out of Cloudflare's cache.
+pub const NO_CACHE: i32 = -1;
+
+/// Fetches a response body as text, for APIs that don't speak JSON, keeping
+/// successful responses in Cloudflare's cache for `cache_ttl` seconds.
+pub async fn fetch_text(url: &str, cache_ttl: i32) -> Result<Option<String>> {
+    match fetch_cached(url, &[], cache_ttl).await? {
+        Some(mut response) => response.text().await.map(Some),
+        None => Ok(None),
+    }
+}
+
+// GETs `url` through Cloudflare's cache. `None` for a 404, an error for any
+// other status but 200.
+async fn fetch_cached(
+    url: &str,
+    extra_headers: &[(&str, &str)],
+    cache_ttl: i32,
+) -> Result<Option<Response>> {
+    let headers = Headers::new();
+    for (name, value) in extra_headers {
+        headers.set(name, value)?;
+    }
+
     let mut init = RequestInit::new();
-    init.with_method(Method::Get);
+    init.with_method(Method::Get).with_headers(headers);
 
     let mut cf = CfProperties::new();
     let ttl_by_status = std::collections::HashMap::from([
-        ("200".to_string(), crate::config::CACHE_TTL_SECONDS),
+        ("200".to_string(), cache_ttl),
         ("404".to_string(), crate::config::CACHE_TTL_404),
     ]);
     cf.cache_ttl_by_status = Some(ttl_by_status);
     init.with_cf_properties(cf);
 
     let request = Request::new_with_init(url, &init)?;
-    let mut response = Fetch::Request(request).send().await?;
+    let response = Fetch::Request(request).send().await?;
 
     if response.status_code() == 404 {
         return Ok(None);
@@ -27,8 +66,44 @@ pub async fn fetch_json<T: for<'de> serde::Deserialize<'de>>(url: &str) -> Resul
         )));
     }
 
-    match response.json().await {
-        Ok(json) => Ok(Some(json)),
-        Err(e) => Err(e),
+    Ok(Some(response))
+}
+
+/// POSTs a JSON body, as GraphQL APIs expect. Responses aren't cached.
+pub async fn post_json<T: for<'de> serde::Deserialize<'de>>(
+    url: &str,
+    body: &serde_json::Value,
+) -> Result<T> {
+    post_json_with_headers(url, body, &[]).await
+}
+
+pub async fn post_json_with_headers<T: for<'de> serde::Deserialize<'de>>(
+    url: &str,
+    body: &serde_json::Value,
+    extra_headers: &[(&str, &str)],
+) -> Result<T> {
+    let headers = Headers::new();
+    headers.set("Content-Type", "application/json")?;
+    headers.set("Accept", "application/json")?;
+    for (name, value) in extra_headers {
+        headers.set(name, value)?;
+    }
+
+    let mut init = RequestInit::new();
+    init.with_method(Method::Post)
+        .with_headers(headers)
+        .with_body(Some(wasm_bindgen::JsValue::from_str(&body.to_string())));
+
+    let request = Request::new_with_init(url, &init)?;
+    let mut response = Fetch::Request(request).send().await?;
+
+    if response.status_code() != 200 {
+        return Err(Error::RustError(format!(
+            "Failed to post {}: status {}",
+            url,
+            response.status_code()
+        )));
     }
+
+    response.json().await
 }
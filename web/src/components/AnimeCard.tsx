@@ -52,11 +52,15 @@ export default function AnimeCard({
     try {
       const tmdbSite = item.sites?.find((s) => s.site === "tmdb");
       const malSite = item.sites?.find((s) => s.site === "mal");
+      const kitsuSite = item.sites?.find((s) => s.site === "kitsu");
       const anilistSite = item.sites?.find(
         (s) => s.site === "aniList" || s.site === "anilist",
       );
+      const bangumiSite = item.sites?.find((s) => s.site === "bangumi");
+      const anidbSite = item.sites?.find((s) => s.site === "anidb");
 
-      let url = `/api/metadata?title=${encodeURIComponent(item.title)}`;
+      // Merge so the details modal gets every field any provider has
+      let url = `/api/metadata?merge=true&title=${encodeURIComponent(item.title)}`;
 
       if (tmdbSite?.id) {
         url += `&tmdb_id=${encodeURIComponent(tmdbSite.id)}`;
@@ -66,14 +70,41 @@ export default function AnimeCard({
         url += `&mal_id=${encodeURIComponent(malSite.id)}`;
       }
 
+      if (kitsuSite?.id) {
+        url += `&kitsu_id=${encodeURIComponent(kitsuSite.id)}`;
+      }
+
       if (anilistSite?.id) {
         url += `&anilist_id=${encodeURIComponent(anilistSite.id)}`;
       }
 
+      if (bangumiSite?.id) {
+        url += `&bangumi_id=${encodeURIComponent(bangumiSite.id)}`;
+      }
+
+      if (anidbSite?.id) {
+        url += `&anidb_id=${encodeURIComponent(anidbSite.id)}`;
+      }
+
+      // Translations help the worker pick the right search result
+      for (const title of new Set(
+        Object.values(item.titleTranslate ?? {}).flat(),
+      )) {
+        if (title) url += `&alt_title=${encodeURIComponent(title)}`;
+      }
+
+      if (item.type) {
+        url += `&type=${encodeURIComponent(item.type)}`;
+      }
+
       if (item.begin) {
         url += `&begin=${encodeURIComponent(item.begin)}`;
       }
 
+      if (item.broadcast) {
+        url += `&broadcast=${encodeURIComponent(item.broadcast)}`;
+      }
+
       const response = await fetch(url);
       if (!response.ok) throw new Error("Metadata fetch failed");
       const data = await response.json();
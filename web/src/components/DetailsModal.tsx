@@ -12,13 +12,20 @@ import { useEffect, useRef, useState } from "react";
 import type {
   AnimeItem,
   SiteMeta,
+  StreamingEpisode,
   UnifiedMetadata,
   UniversalEpisode,
   UniversalStaff,
 } from "../types";
 import { sortSites } from "../utils/siteUtils";
 
-function EpisodeItem({ ep }: { ep: UniversalEpisode }) {
+function EpisodeItem({
+  ep,
+  stream,
+}: {
+  ep: UniversalEpisode;
+  stream?: StreamingEpisode;
+}) {
   const [isExpanded, setIsExpanded] = useState(false);
   const [isOverflowing, setIsOverflowing] = useState(false);
   const textRef = useRef<HTMLParagraphElement>(null);
@@ -40,10 +47,30 @@ function EpisodeItem({ ep }: { ep: UniversalEpisode }) {
         <span className="w-6 shrink-0 text-center font-black text-blue-600 dark:text-blue-400">
           {ep.number}
         </span>
+        {ep.thumbnail && (
+          <img
+            src={ep.thumbnail}
+            alt=""
+            loading="lazy"
+            className="h-9 w-16 shrink-0 rounded-md object-cover"
+          />
+        )}
         <span className="truncate font-medium text-gray-700 transition-colors group-hover/ep:text-blue-600 dark:text-gray-200 dark:group-hover/ep:text-blue-400">
           {ep.title || `Episode ${ep.number}`}
         </span>
         <div className="ml-auto flex shrink-0 items-center gap-2">
+          {stream && (
+            <a
+              href={stream.url}
+              target="_blank"
+              rel="noopener noreferrer"
+              title={stream.site}
+              onClick={(e) => e.stopPropagation()}
+              className="text-blue-500 transition-colors hover:text-blue-700 dark:text-blue-400 dark:hover:text-blue-300"
+            >
+              <PlayCircle size={16} />
+            </a>
+          )}
           {ep.runtime && (
             <span className="self-center rounded bg-gray-100 px-1.5 py-0.5 text-[10px] font-bold text-gray-400 dark:bg-gray-800 dark:text-gray-500">
               {ep.runtime}分
@@ -236,6 +263,7 @@ export default function DetailsModal({
                             </span>
                             <span className="text-gray-700 dark:text-gray-200">
                               {info.title.native}
+                              {info.title.season && ` : ${info.title.season}`}
                             </span>
                           </div>
                         )}
@@ -368,6 +396,38 @@ export default function DetailsModal({
                         );
                       })}
 
+                      {/* Streaming links from the metadata providers */}
+                      {!!info?.externalLinks?.some(
+                        (l) => l.type === "STREAMING",
+                      ) && (
+                        <div>
+                          <h4 className="mb-2 text-sm font-black tracking-wider text-gray-400 uppercase dark:text-gray-500">
+                            配信サービス
+                          </h4>
+                          <div className="mb-4 flex flex-wrap gap-2">
+                            {info.externalLinks
+                              .filter((l) => l.type === "STREAMING")
+                              .map((link, idx) => (
+                                <a
+                                  key={`${link.site}-${idx}`}
+                                  href={link.url}
+                                  target="_blank"
+                                  rel="noopener noreferrer"
+                                  className="flex items-center gap-1.5 rounded-xl border border-blue-100 bg-blue-50 px-3 py-1.5 text-sm font-bold text-blue-600 transition-colors hover:bg-blue-100 dark:border-blue-800/50 dark:bg-blue-900/20 dark:text-blue-400 dark:hover:bg-blue-900/30"
+                                >
+                                  {link.site}
+                                  {link.language && (
+                                    <span className="text-[10px] font-medium opacity-70">
+                                      {link.language}
+                                    </span>
+                                  )}
+                                  <ExternalLink size={12} />
+                                </a>
+                              ))}
+                          </div>
+                        </div>
+                      )}
+
                       {/* Description */}
                       {info?.description && (
                         <div>
@@ -388,7 +448,13 @@ export default function DetailsModal({
                           </h4>
                           <div className="custom-scrollbar grid max-h-80 grid-cols-1 gap-2 overflow-y-auto pr-2">
                             {info.episodesList.map((ep: UniversalEpisode) => (
-                              <EpisodeItem key={ep.number} ep={ep} />
+                              <EpisodeItem
+                                key={ep.number}
+                                ep={ep}
+                                stream={info.streamingEpisodes?.find(
+                                  (s) => s.number === ep.number,
+                                )}
+                              />
                             ))}
                           </div>
                         </div>
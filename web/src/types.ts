@@ -1,9 +1,21 @@
+export interface BroadcastSchedule {
+  start: string;
+  period: { months: number; days: number };
+  repetitions?: number | null;
+}
+
+export interface NextEpisode {
+  number: number;
+  airsAt: string;
+}
+
 export interface Site {
   site: string;
   id?: string;
   url?: string;
   begin?: string;
   broadcast?: string;
+  broadcastSchedule?: BroadcastSchedule | null;
   comment?: string;
   regions?: string[];
 }
@@ -14,17 +26,28 @@ export interface TitleTranslate {
 
 export interface AnimeItem {
   title: string;
+  displayTitle?: string;
   type: "tv" | "movie" | "ova" | "ona" | "special" | string;
   lang: string;
   officialSite: string;
   begin: string;
   broadcast?: string;
+  broadcastSchedule?: BroadcastSchedule | null;
+  nextEpisode?: NextEpisode | null;
   end: string;
   comment?: string;
   sites?: Site[];
   titleTranslate?: TitleTranslate;
 }
 
+export interface SearchHit {
+  year: number;
+  season: "Winter" | "Spring" | "Summer" | "Autumn";
+  score: number;
+  matchedTitle: string;
+  item: AnimeItem;
+}
+
 export interface SiteMetaItem {
   title: string;
   urlTemplate?: string;
@@ -48,10 +71,18 @@ export interface Config {
   };
 }
 
+export interface AlternativeTitle {
+  title: string;
+  language?: string;
+  region?: string;
+}
+
 export interface UniversalTitle {
   romaji?: string;
   english?: string;
   native?: string;
+  season?: string;
+  alternatives?: AlternativeTitle[];
 }
 
 export interface UniversalCoverImage {
@@ -63,12 +94,28 @@ export interface UniversalCharacter {
   name: string;
   voiceActor?: string;
   role?: string;
+  image?: string;
+  voiceActorImage?: string;
+  voiceActorLanguage?: string;
 }
 
 export interface UniversalStaff {
   name: string;
   role: string;
   department?: string;
+  image?: string;
+}
+
+export interface CreditsPage {
+  provider: MetadataCandidate["provider"];
+  id: string;
+  page: number;
+  perPage: number;
+  totalCharacters: number;
+  totalStaff: number;
+  hasNextPage: boolean;
+  characters: UniversalCharacter[];
+  staff: UniversalStaff[];
 }
 
 export interface UniversalEpisode {
@@ -77,6 +124,8 @@ export interface UniversalEpisode {
   airDate?: string;
   overview?: string;
   runtime?: number;
+  thumbnail?: string;
+  titles?: AlternativeTitle[];
 }
 
 export interface UnifiedMetadata {
@@ -87,6 +136,7 @@ export interface UnifiedMetadata {
   episodes?: number;
   genres: string[];
   description?: string;
+  descriptionLanguage?: string;
   studios: string[];
   characters: UniversalCharacter[];
   staff: UniversalStaff[];
@@ -96,4 +146,77 @@ export interface UnifiedMetadata {
   currentSeason?: number;
   runtime?: number;
   contentRating?: string;
+  nextEpisode?: NextEpisode | null;
+  matchConfidence?: number | null;
+  externalIds?: Record<string, string>;
+  externalLinks?: ExternalLinkInfo[];
+  streamingEpisodes?: StreamingEpisode[];
+  sources?: Record<string, string>;
+}
+
+export interface ExternalLinkInfo {
+  site: string;
+  url: string;
+  type?: "STREAMING" | "INFO" | "SOCIAL";
+  language?: string;
+}
+
+export interface StreamingEpisode {
+  number?: number | null;
+  title?: string | null;
+  thumbnail?: string | null;
+  url: string;
+  site: string;
+}
+
+export interface MetadataCandidate {
+  provider: "tmdb" | "mal" | "kitsu" | "bangumi" | "anidb" | "anilist";
+  id: string;
+  titles: string[];
+  year?: number | null;
+  type?: string | null;
+  episodes?: number | null;
+  poster?: string | null;
+  confidence: number;
+}
+
+export type RelationType =
+  | "PREQUEL"
+  | "SEQUEL"
+  | "PARENT"
+  | "SIDE_STORY"
+  | "SPIN_OFF"
+  | "ALTERNATIVE"
+  | "SUMMARY"
+  | "FULL_STORY"
+  | "SOURCE"
+  | "ADAPTATION"
+  | "CHARACTER"
+  | "COMPILATION"
+  | "CONTAINS"
+  | "OTHER";
+
+export interface RelationNode {
+  id: string;
+  provider: "anilist" | "mal" | "tmdb";
+  providerId: string;
+  mediaType?: string | null;
+  format?: string | null;
+  title: UniversalTitle;
+  coverImage: UniversalCoverImage;
+  year?: number | null;
+  episodes?: number | null;
+}
+
+export interface RelationEdge {
+  from: string;
+  to: string;
+  relation: RelationType;
+}
+
+export interface RelationGraph {
+  root: string;
+  nodes: RelationNode[];
+  edges: RelationEdge[];
+  watchOrder: string[];
 }